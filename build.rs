@@ -37,5 +37,32 @@ fn main() -> std::io::Result<()> {
         }
     }
 
+    let dest_path = PathBuf::from(&out_dir).join("spec_tests_generated.rs");
+    let mut f = fs::File::create(dest_path)?;
+
+    // Every script becomes a test that runs all of its directives, the hand-written ones in
+    // `test/spec` have to pass completely while the vendored testsuite reports what passes
+    for (spec_dir, kind) in [("./test/spec", "spec"), ("./test/testsuite", "testsuite")] {
+        for entry in fs::read_dir(spec_dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if path.extension().and_then(|s| s.to_str()) == Some("wast") {
+                let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap();
+                let sanitized_file_stem = file_stem.replace(|c: char| !c.is_alphanumeric(), "_");
+
+                writeln!(f, "#[test]")?;
+                writeln!(f, "fn {}_{}() {{", kind, sanitized_file_stem)?;
+                writeln!(
+                    f,
+                    "    run_{}_test({:?});",
+                    kind,
+                    path.display().to_string()
+                )?;
+                writeln!(f, "}}")?;
+            }
+        }
+    }
+
     Ok(())
 }
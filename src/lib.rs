@@ -1,23 +1,27 @@
+pub mod parse_error;
 pub mod section;
 pub mod types;
 pub const VERSION: u32 = 1;
 
 pub mod module;
 pub mod runtime;
+pub mod validate;
 pub mod wasi;
 
-use crate::section::{Section, SectionType};
+use crate::{
+    parse_error::{fail, IResult, InputError, ParseErrorKind},
+    section::{Section, Sections},
+};
 use nom::{
     bytes::complete::tag,
     combinator::cut,
     error::{ContextError, ParseError},
-    IResult, Parser,
+    Parser,
 };
-use std::collections::HashMap;
 
 pub fn repeat_until_empty<'a, T, F, E>(
     mut parse: F,
-) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<T>, E>
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], Vec<T>, E>
 where
     T: 'a,
     F: Parser<&'a [u8], T, E>,
@@ -34,22 +38,51 @@ where
     }
 }
 
-pub fn parse_sections(input: &[u8]) -> IResult<&[u8], HashMap<SectionType, Section>> {
-    let (input, _) = tag("\0asm")(input)?;
+/// Error for a header field that didn't match, a binary that stops within the field is truncated
+/// instead of wrong
+fn header_error(input: &[u8], kind: ParseErrorKind) -> nom::Err<InputError<&[u8]>> {
+    let kind = if input.len() < 4 {
+        ParseErrorKind::UnexpectedEnd
+    } else {
+        kind
+    };
+    nom::Err::Failure(InputError::new(input, kind))
+}
+
+pub fn parse_sections<'a>(input: &'a [u8]) -> IResult<&'a [u8], Sections<'a>> {
+    let original_input = input;
+    let (input, _) = tag("\0asm")(input).map_err(|_: nom::Err<InputError<&[u8]>>| {
+        header_error(input, ParseErrorKind::MagicHeader)
+    })?;
 
-    let (input, _) = tag(VERSION.to_le_bytes())(input)?;
+    let (input, _) =
+        tag(VERSION.to_le_bytes())(input).map_err(|_: nom::Err<InputError<&[u8]>>| {
+            header_error(input, ParseErrorKind::UnknownVersion)
+        })?;
 
-    let (input, sections) =
-        repeat_until_empty(cut(Section::parse.map(|s| (s.get_variant(), s))))(input)?;
+    let (input, sections) = repeat_until_empty(cut(|section_input: &'a [u8]| {
+        let offset = section_input.as_ptr() as usize - original_input.as_ptr() as usize;
+        Section::parse
+            .map(|s| (section_input, offset, s))
+            .parse(section_input)
+    }))(input)?;
 
-    let mut sections_hashmap = HashMap::with_capacity(sections.len());
+    let mut parsed_sections = Sections::with_capacity(sections.len());
 
-    for (t, s) in sections.into_iter() {
-        if sections_hashmap.insert(t, s).is_some() {
-            panic!("Duplicate sections")
+    let mut last_order = 0;
+    for (section_input, offset, section) in sections.into_iter() {
+        let order = section.order();
+        if let Some(order) = order {
+            if order == last_order {
+                return fail(section_input, ParseErrorKind::DuplicateSection);
+            }
+            if order < last_order {
+                return fail(section_input, ParseErrorKind::SectionOutOfOrder);
+            }
+            last_order = order;
         }
+        parsed_sections.insert(offset, section);
     }
-    assert!(input.is_empty(), "{:?}", input);
 
-    Ok((input, sections_hashmap))
+    Ok((input, parsed_sections))
 }
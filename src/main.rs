@@ -1,11 +1,78 @@
-use reactor::{module::Module, runtime::Runtime};
+use std::process::exit;
+
+use reactor::{
+    module::Module,
+    runtime::{Config, Engine, InstantiationError, Linker, MemoryHandle, Store, TableHandle, Trap},
+    types::{ImportDesc, MemoryType},
+    wasi::Wasi,
+};
 
 fn main() {
     let file = std::env::args().nth(1).unwrap();
     let file = std::fs::read(file).unwrap();
 
-    let module = Module::new(&file[..]);
+    // `REACTOR_ENGINE=stack` runs the module on the stack engine instead, to compare the two.
+    // The arguments all belong to the module
+    let engine = match std::env::var("REACTOR_ENGINE").as_deref() {
+        Ok("stack") => Engine::Stack,
+        _ => Engine::Registers,
+    };
+
+    let module = match Module::new(&file[..]) {
+        Ok(module) => module,
+        Err(err) => {
+            eprintln!("Failed to parse module: {}", err);
+            exit(1);
+        }
+    };
+
+    let mut linker = Linker::new();
+    Wasi::new().add_to_linker(&mut linker);
+
+    // Modules linked with `--import-memory` expect the embedder to create their memory and
+    // function table
+    for import in module.imports() {
+        match import.desc {
+            ImportDesc::Memory(MemoryType(limit)) => {
+                linker.memory(import.mod_name, import.name, MemoryHandle::new(limit));
+            }
+            ImportDesc::Table(table_type) => {
+                linker.table(import.mod_name, import.name, TableHandle::new(table_type));
+            }
+            ImportDesc::Func(_) | ImportDesc::Global(_) => {}
+        }
+    }
 
-    let runtime = Runtime::new(&module);
-    runtime.execute();
+    let mut store = Store::with_config(Config {
+        engine,
+        ..Config::default()
+    });
+    let result = match store.instantiate(&linker, &module) {
+        Ok(instance) => {
+            let Some(start) = instance.get_func("_start") else {
+                eprintln!("Wasi module expected to export a function _start");
+                exit(1);
+            };
+            let result = start.call(&[]);
+            // `REACTOR_FUSIONS=1` reports which fusions the register engine applied
+            if std::env::var_os("REACTOR_FUSIONS").is_some() {
+                eprint!("{}", instance.fusions());
+            }
+            result
+        }
+        Err(InstantiationError::Trap(trap)) => Err(trap),
+        Err(err) => {
+            eprintln!("Failed to instantiate module: {}", err);
+            exit(1);
+        }
+    };
+    match result {
+        Ok(_) => {}
+        Err(Trap::Exit(code)) => exit(code),
+        Err(trap) => {
+            eprintln!("wasm trap: {}", trap);
+            // Same exit code wasmtime uses for a trapping guest
+            exit(134);
+        }
+    }
 }
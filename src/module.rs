@@ -1,25 +1,33 @@
 use std::rc::Rc;
 
 use crate::{
+    parse_error::ParseError,
     parse_sections,
     section::{global::GlobalInitializer, r#type::TypeSection},
-    types::{Data, Element, FuncIdx, FuncType, FuncTypeIdx, FunctionCode, Limit, TableType},
+    types::{
+        Data, Element, Export, ExportDesc, FuncIdx, FuncType, FuncTypeIdx, FunctionCode,
+        GlobalType, Import, ImportDesc, Limit, MemoryType, TableType,
+    },
 };
 
 use self::{
-    data::take_datas,
+    data::{take_data_count, take_datas},
     elements::take_element_declarations,
+    exports::take_exports,
     functions::{take_functions, Function},
     globals::take_globals,
+    imports::take_imports,
     memory::take_memory_declaration,
-    start::{get_main_index, take_start_index},
+    start::take_start_index,
     tables::take_table_declarations,
 };
 
 mod data;
 mod elements;
+mod exports;
 pub mod functions;
 mod globals;
+mod imports;
 mod memory;
 mod start;
 mod tables;
@@ -27,45 +35,55 @@ mod tables;
 #[derive(Debug)]
 pub struct Module<'a> {
     functions: Vec<Function<'a>>,
+    /// Functions past this index hold the constant expressions of globals, elements and datas
+    amount_of_functions: usize,
     function_types: TypeSection,
     elements: Vec<Element>,
     datas: Vec<Data>,
     globals: Vec<GlobalInitializer>,
 
     tables: Vec<TableType>,
-    main: FuncIdx,
     start: Option<FuncIdx>,
-    memory: Limit,
+    memory: Option<Limit>,
+    data_count: Option<u32>,
+    imports: Vec<Import<'a>>,
+    exports: Vec<Export<'a>>,
 }
 
 impl<'t> Module<'t> {
-    pub fn new(input: &'t [u8]) -> Self {
-        let (_, mut sections) = parse_sections(input).unwrap();
+    pub fn new(input: &'t [u8]) -> Result<Self, ParseError> {
+        let (_, mut sections) =
+            parse_sections(input).map_err(|err| ParseError::from_nom(input, err))?;
 
-        let (mut functions, function_types) = take_functions(&mut sections);
+        let (mut functions, function_types) = take_functions(&mut sections)?;
+        let amount_of_functions = functions.len();
 
-        let main_idx =
-            get_main_index(&sections).expect("Wasi module expected to export a function _start");
+        let imports = take_imports(&mut sections);
+        let exports = take_exports(&mut sections);
         let memory = take_memory_declaration(&mut sections);
 
-        let datas = take_datas(&mut sections, &mut functions);
+        let data_count = take_data_count(&mut sections);
+        let datas = take_datas(&mut sections, &mut functions, data_count)?;
 
         let globals = take_globals(&mut sections, &mut functions);
         let tables = take_table_declarations(&mut sections);
         let elements = take_element_declarations(&mut sections, &mut functions);
         let start = take_start_index(&mut sections);
 
-        Self {
+        Ok(Self {
             start,
             elements,
             globals,
             datas,
             functions,
+            amount_of_functions,
             function_types,
             tables,
-            main: main_idx,
             memory,
-        }
+            data_count,
+            imports,
+            exports,
+        })
     }
 
     pub fn elements(&self) -> &[Element] {
@@ -76,27 +94,33 @@ impl<'t> Module<'t> {
         self.function_types.get_function_type(idx)
     }
 
+    pub fn function_types(&self) -> &TypeSection {
+        &self.function_types
+    }
+
     pub fn tables(&self) -> &[TableType] {
         &self.tables
     }
 
-    pub fn memory_limit(&self) -> Limit {
+    pub fn memory_limit(&self) -> Option<Limit> {
         self.memory
     }
 
-    pub fn get_main(&self) -> (FuncIdx, &Function) {
-        (
-            self.main,
-            self.get_function(self.main)
-                .expect("starting index should be valid"),
-        )
-    }
-
     pub fn get_initializer(&self) -> Option<FuncIdx> {
         self.start
     }
 
-    pub fn get_function(&self, FuncIdx(idx): FuncIdx) -> Option<&Function> {
+    /// Amount of imported and defined functions, excluding constant expressions
+    pub fn amount_of_functions(&self) -> usize {
+        self.amount_of_functions
+    }
+
+    /// Imported and defined functions followed by the constant expressions
+    pub fn functions(&self) -> &[Function<'t>] {
+        &self.functions
+    }
+
+    pub fn get_function(&self, FuncIdx(idx): FuncIdx) -> Option<&Function<'_>> {
         self.functions.get(idx as usize)
     }
 
@@ -107,4 +131,47 @@ impl<'t> Module<'t> {
     pub fn global_initializers(&self) -> &[GlobalInitializer] {
         &self.globals
     }
+
+    pub fn data_count(&self) -> Option<u32> {
+        self.data_count
+    }
+
+    pub fn imports(&self) -> &[Import<'t>] {
+        &self.imports
+    }
+
+    /// Limits of the memory if the module imports it instead of defining one
+    pub fn imported_memory(&self) -> Option<Limit> {
+        self.imports.iter().find_map(|import| match import.desc {
+            ImportDesc::Memory(MemoryType(limit)) => Some(limit),
+            _ => None,
+        })
+    }
+
+    /// Types of the imported tables, which come first in the table index space
+    pub fn imported_tables(&self) -> impl Iterator<Item = TableType> + '_ {
+        self.imports.iter().filter_map(|import| match import.desc {
+            ImportDesc::Table(table_type) => Some(table_type),
+            _ => None,
+        })
+    }
+
+    /// Types of the imported globals, which come first in the global index space
+    pub fn imported_globals(&self) -> impl Iterator<Item = GlobalType> + '_ {
+        self.imports.iter().filter_map(|import| match import.desc {
+            ImportDesc::Global(global_type) => Some(global_type),
+            _ => None,
+        })
+    }
+
+    pub fn get_export(&self, name: &str) -> Option<&ExportDesc> {
+        self.exports
+            .iter()
+            .find(|export| export.name == name)
+            .map(|export| &export.desc)
+    }
+
+    pub fn exports(&self) -> &[Export<'t>] {
+        &self.exports
+    }
 }
@@ -1,26 +1,42 @@
-use std::collections::HashMap;
-
 use crate::{
-    section::{Section, SectionType},
+    parse_error::{ParseError, ParseErrorKind},
+    section::{Section, SectionType, Sections},
     types::Data,
 };
 
 use super::functions::Function;
 
+pub fn take_data_count(sections: &mut Sections) -> Option<u32> {
+    let Section::DataCount(data_count) = sections.remove(&SectionType::DataCount)? else {
+        unreachable!();
+    };
+    Some(data_count.amount_of_datas)
+}
+
 pub fn take_datas<'a>(
-    sections: &mut HashMap<SectionType<'a>, Section<'a>>,
+    sections: &mut Sections<'a>,
     functions: &mut Vec<Function<'_>>,
-) -> Vec<Data> {
+    data_count: Option<u32>,
+) -> Result<Vec<Data>, ParseError> {
     let Some(datas) = sections.remove(&SectionType::Data) else {
-        return vec![];
+        return match data_count {
+            Some(0) | None => Ok(vec![]),
+            Some(_) => {
+                Err(sections.error(SectionType::DataCount, ParseErrorKind::DataCountMismatch))
+            }
+        };
     };
     let Section::Data(data_section) = datas else {
         unreachable!();
     };
 
-    data_section
+    if data_count.is_some_and(|count| count as usize != data_section.0.len()) {
+        return Err(sections.error(SectionType::DataCount, ParseErrorKind::DataCountMismatch));
+    }
+
+    Ok(data_section
         .0
         .into_iter()
         .map(|e| e.add_to_module(functions))
-        .collect()
+        .collect())
 }
@@ -1,14 +1,12 @@
-use std::collections::HashMap;
-
 use crate::{
-    section::{Section, SectionType},
+    section::{Section, SectionType, Sections},
     types::Element,
 };
 
 use super::functions::Function;
 
 pub fn take_element_declarations<'a>(
-    sections: &mut HashMap<SectionType<'a>, Section<'a>>,
+    sections: &mut Sections<'a>,
     functions: &mut Vec<Function<'_>>,
 ) -> Vec<Element> {
     if let Some(elements) = sections.remove(&SectionType::Element) {
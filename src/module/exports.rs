@@ -0,0 +1,15 @@
+use crate::{
+    section::{Section, SectionType, Sections},
+    types::Export,
+};
+
+pub fn take_exports<'a>(sections: &mut Sections<'a>) -> Vec<Export<'a>> {
+    if let Some(exports) = sections.remove(&SectionType::Export) {
+        let Section::Export(exports) = exports else {
+            unreachable!();
+        };
+        exports.exports
+    } else {
+        vec![]
+    }
+}
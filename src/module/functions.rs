@@ -1,8 +1,12 @@
-use std::{collections::HashMap, rc::Rc};
+use std::rc::Rc;
 
 use crate::{
-    section::{import::ImportSection, r#type::TypeSection, Section, SectionType},
-    types::{Expr, FuncIdx, FuncType, FunctionCode, ImportDesc, LocalTypes},
+    parse_error::{ParseError, ParseErrorKind},
+    section::{
+        code::CodeSection, function::FunctionSection, import::ImportSection, r#type::TypeSection,
+        Section, SectionType, Sections,
+    },
+    types::{Expr, FuncIdx, FuncType, FuncTypeIdx, FunctionCode, ImportDesc, LocalTypes},
 };
 
 #[derive(Debug)]
@@ -46,71 +50,60 @@ impl Function<'_> {
 }
 
 pub fn take_functions<'a>(
-    sections: &mut HashMap<SectionType<'a>, Section<'a>>,
-) -> (Vec<Function<'a>>, TypeSection) {
-    let type_section = sections.remove(&SectionType::Type);
+    sections: &mut Sections<'a>,
+) -> Result<(Vec<Function<'a>>, TypeSection), ParseError> {
+    let type_section = match sections.remove(&SectionType::Type) {
+        Some(Section::Type(type_section)) => type_section,
+        _ => TypeSection::empty(),
+    };
+    let function_section = match sections.remove(&SectionType::Function) {
+        Some(Section::Function(function_section)) => function_section,
+        _ => FunctionSection::empty(),
+    };
+    let code_section = match sections.remove(&SectionType::Code) {
+        Some(Section::Code(code_section)) => code_section,
+        _ => CodeSection::empty(),
+    };
 
-    let function_section = sections.remove(&SectionType::Function);
-
-    let code_section = sections.remove(&SectionType::Code);
-
-    if type_section.is_none() && function_section.is_none() && code_section.is_none() {
-        return (vec![], TypeSection::empty());
+    if function_section.len() != code_section.functions.len() {
+        return Err(sections.error(
+            SectionType::Code,
+            ParseErrorKind::InconsistentFunctionAndCode,
+        ));
     }
 
-    if let (
-        Some(Section::Type(type_section)),
-        Some(Section::Function(function_section)),
-        Some(Section::Code(code_section)),
-    ) = (type_section, function_section, code_section)
-    {
-        let imports = sections.get_mut(&SectionType::Import);
+    let get_function_type = |section: SectionType<'a>, type_idx @ FuncTypeIdx(idx)| {
+        type_section
+            .get_function_type(type_idx)
+            .ok_or_else(|| sections.error(section, ParseErrorKind::UnknownType(idx)))
+    };
 
-        let mut imported_functions = if let Some(Section::Import(ImportSection(imports))) = imports
-        {
-            let mut imported_functions = vec![];
-            for import in imports {
-                match import.desc {
-                    ImportDesc::Func(signature) => {
-                        imported_functions.push(Function::Imported(ImportedFunction {
-                            mod_name: import.mod_name,
-                            name: import.name,
-                            signature: type_section
-                                .get_function_type(signature)
-                                .expect("Import function type index to be valid")
-                                .clone(),
-                        }));
-                    }
-
-                    _ => continue,
+    let mut functions = vec![];
+    if let Some(Section::Import(ImportSection(imports))) = sections.get(&SectionType::Import) {
+        for import in imports {
+            match import.desc {
+                ImportDesc::Func(signature) => {
+                    functions.push(Function::Imported(ImportedFunction {
+                        mod_name: import.mod_name,
+                        name: import.name,
+                        signature: get_function_type(SectionType::Import, signature)?,
+                    }));
                 }
-            }
-            imported_functions
-        } else {
-            vec![]
-        };
 
-        let functions = code_section
-            .functions
-            .into_iter()
-            .enumerate()
-            .map(|(i, code)| {
-                Function::Local(LocalFunction {
-                    signature: type_section
-                        .get_function_type(
-                            function_section
-                                .get_func_type_idx(FuncIdx(i as u32))
-                                .expect("Function section len should local len"),
-                        )
-                        .expect("Every type idx in the funciton section should be valid"),
-                    code,
-                })
-            })
-            .collect::<Vec<_>>();
+                _ => continue,
+            }
+        }
+    }
 
-        imported_functions.extend(functions);
-        (imported_functions, type_section)
-    } else {
-        panic!("Invalid function sections some missing")
+    for (i, code) in code_section.functions.into_iter().enumerate() {
+        let type_idx = function_section
+            .get_func_type_idx(FuncIdx(i as u32))
+            .expect("Function section length was checked against the code section");
+        functions.push(Function::Local(LocalFunction {
+            signature: get_function_type(SectionType::Function, type_idx)?,
+            code,
+        }));
     }
+
+    Ok((functions, type_section))
 }
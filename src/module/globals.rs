@@ -1,11 +1,9 @@
-use std::collections::HashMap;
-
-use crate::section::{global::GlobalInitializer, Section, SectionType};
+use crate::section::{global::GlobalInitializer, Section, SectionType, Sections};
 
 use super::functions::Function;
 
 pub fn take_globals<'a>(
-    sections: &mut HashMap<SectionType<'a>, Section<'a>>,
+    sections: &mut Sections<'a>,
     functions: &mut Vec<Function<'_>>,
 ) -> Vec<GlobalInitializer> {
     let globals = sections.remove(&SectionType::Global);
@@ -0,0 +1,15 @@
+use crate::{
+    section::{Section, SectionType, Sections},
+    types::Import,
+};
+
+pub fn take_imports<'a>(sections: &mut Sections<'a>) -> Vec<Import<'a>> {
+    if let Some(imports) = sections.remove(&SectionType::Import) {
+        let Section::Import(imports) = imports else {
+            unreachable!();
+        };
+        imports.0
+    } else {
+        vec![]
+    }
+}
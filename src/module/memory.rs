@@ -1,16 +1,12 @@
-use std::collections::HashMap;
-
 use crate::{
-    section::{Section, SectionType},
+    section::{Section, SectionType, Sections},
     types::Limit,
 };
 
-pub fn take_memory_declaration<'a>(sections: &mut HashMap<SectionType<'a>, Section<'a>>) -> Limit {
-    let Some(mem) = sections.remove(&SectionType::Memory) else {
-        panic!("wasi requires a memory to be declared in the module");
-    };
+pub fn take_memory_declaration<'a>(sections: &mut Sections<'a>) -> Option<Limit> {
+    let mem = sections.remove(&SectionType::Memory)?;
     let Section::Memory(mem) = mem else {
         unreachable!();
     };
-    mem.memories.first().expect("Has to have one element").0
+    mem.memories.first().map(|memory| memory.0)
 }
@@ -0,0 +1,164 @@
+use std::fmt::{self, Display};
+
+use nom::error::{ContextError, ErrorKind, ParseError as NomParseError};
+
+pub type IResult<I, O> = nom::IResult<I, O, InputError<I>>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnexpectedEnd,
+    MagicHeader,
+    UnknownVersion,
+    MalformedSectionId(u8),
+    SectionSizeMismatch,
+    DuplicateSection,
+    SectionOutOfOrder,
+    IntegerTooLong,
+    MalformedUtf8,
+    MalformedValueType(u8),
+    MalformedRefType(u8),
+    MalformedFuncType(u8),
+    MalformedLimits(u8),
+    MalformedMutability(u8),
+    MalformedImportKind(u8),
+    MalformedExportKind(u8),
+    MalformedElementKind(u32),
+    MalformedDataKind(u32),
+    IllegalOpcode(u8, Option<u32>),
+    ZeroByteExpected,
+    UnknownLabel(u32),
+    UnknownType(u32),
+    TooManyLocals,
+    FunctionBodySizeMismatch,
+    InconsistentFunctionAndCode,
+    MultipleMemories,
+    DataCountMismatch,
+    Nom(ErrorKind),
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end"),
+            ParseErrorKind::MagicHeader => write!(f, "magic header not detected"),
+            ParseErrorKind::UnknownVersion => write!(f, "unknown binary version"),
+            ParseErrorKind::MalformedSectionId(id) => write!(f, "malformed section id {}", id),
+            ParseErrorKind::SectionSizeMismatch => write!(f, "section size mismatch"),
+            ParseErrorKind::DuplicateSection => write!(f, "duplicate section"),
+            ParseErrorKind::SectionOutOfOrder => write!(f, "section out of order"),
+            ParseErrorKind::IntegerTooLong => write!(f, "integer representation too long"),
+            ParseErrorKind::MalformedUtf8 => write!(f, "malformed UTF-8 encoding"),
+            ParseErrorKind::MalformedValueType(v) => write!(f, "malformed value type 0x{:x}", v),
+            ParseErrorKind::MalformedRefType(v) => write!(f, "malformed reference type 0x{:x}", v),
+            ParseErrorKind::MalformedFuncType(v) => write!(f, "malformed function type 0x{:x}", v),
+            ParseErrorKind::MalformedLimits(v) => write!(f, "malformed limits flags 0x{:x}", v),
+            ParseErrorKind::MalformedMutability(v) => write!(f, "malformed mutability 0x{:x}", v),
+            ParseErrorKind::MalformedImportKind(v) => write!(f, "malformed import kind 0x{:x}", v),
+            ParseErrorKind::MalformedExportKind(v) => write!(f, "malformed export kind 0x{:x}", v),
+            ParseErrorKind::MalformedElementKind(v) => {
+                write!(f, "malformed elements segment kind {}", v)
+            }
+            ParseErrorKind::MalformedDataKind(v) => write!(f, "malformed data segment kind {}", v),
+            ParseErrorKind::IllegalOpcode(opcode, None) => {
+                write!(f, "illegal opcode 0x{:x}", opcode)
+            }
+            ParseErrorKind::IllegalOpcode(opcode, Some(sub_opcode)) => {
+                write!(f, "illegal opcode 0x{:x} {}", opcode, sub_opcode)
+            }
+            ParseErrorKind::ZeroByteExpected => write!(f, "zero byte expected"),
+            ParseErrorKind::UnknownLabel(label) => write!(f, "unknown label {}", label),
+            ParseErrorKind::UnknownType(idx) => write!(f, "unknown type {}", idx),
+            ParseErrorKind::TooManyLocals => write!(f, "too many locals"),
+            ParseErrorKind::FunctionBodySizeMismatch => write!(f, "function body size mismatch"),
+            ParseErrorKind::InconsistentFunctionAndCode => {
+                write!(f, "function and code section have inconsistent lengths")
+            }
+            ParseErrorKind::MultipleMemories => write!(f, "multiple memories"),
+            ParseErrorKind::DataCountMismatch => {
+                write!(f, "data count and data section have inconsistent lengths")
+            }
+            ParseErrorKind::Nom(kind) => write!(f, "malformed input ({})", kind.description()),
+        }
+    }
+}
+
+/// Error of the binary parsers, keeps the input it failed at so the offset can be recovered
+#[derive(Debug)]
+pub struct InputError<I> {
+    pub input: I,
+    pub kind: ParseErrorKind,
+    pub section: Option<&'static str>,
+}
+
+impl<I> InputError<I> {
+    pub fn new(input: I, kind: ParseErrorKind) -> Self {
+        Self {
+            input,
+            kind,
+            section: None,
+        }
+    }
+
+    pub fn in_section(mut self, section: &'static str) -> Self {
+        self.section.get_or_insert(section);
+        self
+    }
+}
+
+impl<I> NomParseError<I> for InputError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        let kind = match kind {
+            ErrorKind::Eof | ErrorKind::Complete => ParseErrorKind::UnexpectedEnd,
+            ErrorKind::TooLarge => ParseErrorKind::IntegerTooLong,
+            kind => ParseErrorKind::Nom(kind),
+        };
+        Self::new(input, kind)
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I> ContextError<I> for InputError<I> {}
+
+/// Fails the current parser without allowing any backtracking
+pub fn fail<I, O>(input: I, kind: ParseErrorKind) -> IResult<I, O> {
+    Err(nom::Err::Failure(InputError::new(input, kind)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub section: Option<&'static str>,
+    pub kind: ParseErrorKind,
+}
+
+impl ParseError {
+    pub fn from_nom(original_input: &[u8], error: nom::Err<InputError<&[u8]>>) -> Self {
+        match error {
+            nom::Err::Incomplete(_) => ParseError {
+                offset: original_input.len(),
+                section: None,
+                kind: ParseErrorKind::UnexpectedEnd,
+            },
+            nom::Err::Error(error) | nom::Err::Failure(error) => ParseError {
+                offset: error.input.as_ptr() as usize - original_input.as_ptr() as usize,
+                section: error.section,
+                kind: error.kind,
+            },
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset 0x{:x}", self.kind, self.offset)?;
+        if let Some(section) = self.section {
+            write!(f, " in {} section", section)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
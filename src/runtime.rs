@@ -1,182 +1,377 @@
 use std::{
-    cell::{Cell, RefCell},
+    cell::{Cell, OnceCell, RefCell},
     ops::{Deref, DerefMut},
-    process::exit,
+    rc::Rc,
 };
 
 use crate::{
-    module::{
-        functions::{Function, LocalFunction},
-        Module,
+    module::{functions::Function, Module},
+    types::{
+        DataMode, ElementMode, FuncIdx, FuncTypeIdx, GlobalIdx, Instruction, Limit, TableIdx,
+        ValueType,
     },
-    types::{BlockIdx, DataMode, ElementMode, FuncIdx, Instruction, ValueType},
-    wasi::Wasi,
 };
 
 use self::{
-    function_state::FunctionState,
+    bytecode::{Code, Label, Op},
     globals::{Global, Globals},
-    locals::Locals,
-    memory::Memory,
+    linker::{ImportedFunc, Imports},
+    operands::{Operands, SlotOperands, StackOperands},
+    registers::{Frame, Moves, Op as RegisterOp, RegisterCode, Registers},
     stack::Stack,
+    store::{InstanceId, StoreData, StoreId},
     table::{TableElementIdx, Tables},
-    value::Value,
 };
 use paste::paste;
 
+pub use self::{
+    config::{Config, Engine},
+    globals::{GlobalHandle, SetGlobalError},
+    instance::{Func, Instance},
+    linker::{Caller, Extern, HostFunc, InstantiationError, Linker},
+    memory::MemoryHandle,
+    peephole::{Fusion, Fusions},
+    store::{InterruptHandle, Store},
+    table::TableHandle,
+    trap::Trap,
+    typed_func::{TypedFunc, WasmParams, WasmResults, WasmType},
+    value::{ExternRef, FuncRef, Ref, Value},
+};
+
+pub mod bytecode;
+mod config;
 pub mod function_state;
 mod globals;
-mod locals;
+mod instance;
+mod linker;
 pub mod memory;
+mod operands;
+mod peephole;
+pub mod registers;
 pub mod stack;
+mod store;
 mod table;
+mod trap;
+mod typed_func;
 mod value;
 mod variable;
 
 #[cfg(test)]
-mod test;
+mod spec_test;
+#[cfg(test)]
+pub(crate) mod test;
 
 pub struct Runtime<'b, 'a> {
     stack: RefCell<Stack>,
     module: &'b Module<'a>,
-    current_function_state: RefCell<FunctionState>,
+    /// Lowered code of the local functions and constant expressions, by function index
+    code: Vec<OnceCell<Code<'b>>>,
+    /// Translated code of the local functions for the register engine, by function index
+    register_code: Vec<OnceCell<RegisterCode<'b>>>,
+    registers: RefCell<Registers>,
     function_depth: Cell<usize>,
-    memory: RefCell<Memory>,
+    memory: MemoryHandle,
+    /// Data segments that can't initialize memory anymore, active ones are dropped once they
+    /// were copied into memory
+    dropped_datas: Vec<Cell<bool>>,
+    /// Element segments that can't initialize tables anymore, active and declarative ones
+    /// are dropped once the module is instantiated
+    dropped_elements: Vec<Cell<bool>>,
     globals: RefCell<Globals>,
-    wasi: RefCell<Wasi>,
-    tables: RefCell<Tables>,
+    tables: Tables,
+    /// Implementations of the imported functions, indexed like them
+    imported_functions: Vec<ImportedFunc<'b, 'a>>,
+    instance_id: InstanceId,
+    /// The other instances of the store, function references may point into them
+    store: Rc<StoreData<'b, 'a>>,
+}
+
+/// A function called from outside the instance, and what to restore once it is done
+enum Execution {
+    Stack {
+        /// Where the stack ended before the call, everything above belongs to it
+        stack_base: usize,
+        /// The functions that were running before the call
+        frames_base: usize,
+        function_depth_before: usize,
+    },
+    Registers {
+        /// The frames of the functions that were running before the call
+        frames_base: usize,
+        function_depth_before: usize,
+    },
 }
 
 macro_rules! op {
     (
         $self:expr,
+        $operands:expr,
         { $( $ident:ident : $type:ident ),* $(,)? },
         $result_type:ident => $expr:expr
     ) => {
         {
             paste! {
-                let mut stack_borrow = $self.stack.borrow_mut();
                 $(
-                    let $ident = stack_borrow.[<pop_ $type>]();
+                    let $ident = $operands.[<pop_ $type>]();
                 )*
-                stack_borrow.[<push_ $result_type>]($expr);
+                let result = $expr;
+                $operands.[<push_ $result_type>](result);
             }
         }
     };
 }
 
 macro_rules! memory_load {
-    ($self:expr, $ty:ident, $mem_func:ident, $memarg:expr) => {
+    ($self:expr, $operands:expr, $ty:ident, $mem_func:ident, $memarg:expr) => {
         paste! {
             {
-                let address = $self.stack.borrow_mut().pop_u32();
-                let value = $self.memory.borrow_mut().$mem_func(address, *$memarg);
-                $self.stack.borrow_mut().[<push_ $ty>](value);
+                let address = $operands.pop_u32();
+                let value = $self.memory.borrow_mut().$mem_func(address, *$memarg)?;
+                $operands.[<push_ $ty>](value);
             }
         }
     };
 }
 
 macro_rules! memory_store {
-    ($self:expr,$type:ident, $mem_func:ident, $memarg:expr) => {
+    ($self:expr, $operands:expr, $type:ident, $mem_func:ident, $memarg:expr) => {
         paste! {
             {
-                let value = $self.stack.borrow_mut().[<pop_ $type>]();
-                let address = $self.stack.borrow_mut().pop_u32();
-                $self.memory.borrow_mut().$mem_func(value, address, *$memarg);
+                let value = $operands.[<pop_ $type>]();
+                let address = $operands.pop_u32();
+                $self.memory.borrow_mut().$mem_func(value, address, *$memarg)?;
             }
         }
     };
 }
 
-macro_rules! block_type_to_slice {
-    ($block_type:expr) => {
-        match $block_type.0 {
-            Some(t) => &[t][..],
-            None => &[][..],
-        }
-    };
-}
-
 impl<'a, 'b> Runtime<'b, 'a> {
-    pub fn new(module: &'b Module<'a>) -> Self {
-        let (start_idx, Function::Local(starting_function)) = module.get_main() else {
-            panic!("Cannot start from imported function")
-        };
-
-        assert!(
-            starting_function.signature.params.is_empty(),
-            "_start function cannot take arguments"
-        );
-
+    fn new(
+        module: &'b Module<'a>,
+        imports: Imports<'b, 'a>,
+        instance_id: InstanceId,
+        store: Rc<StoreData<'b, 'a>>,
+    ) -> Self {
         let stack = Stack::new();
 
-        let initial_function_state = FunctionState::new_function(
-            Locals::new_no_function_parameters(&starting_function.code.locals),
-            start_idx,
-        );
-
-        let tables = Tables::new(module.tables());
+        let tables = Tables::new(imports.tables, module.tables(), store.id);
+        let memory = imports.memory.unwrap_or_else(|| {
+            MemoryHandle::new(module.memory_limit().unwrap_or(Limit {
+                min: 0,
+                max: Some(0),
+            }))
+        });
 
-        let runtime = Runtime {
-            memory: RefCell::new(Memory::new(module.memory_limit())),
+        Runtime {
+            memory,
+            dropped_datas: module.datas().iter().map(|_| Cell::new(false)).collect(),
+            dropped_elements: module.elements().iter().map(|_| Cell::new(false)).collect(),
             stack: RefCell::new(stack),
-            globals: RefCell::new(Globals::new()),
-            tables: RefCell::new(tables),
+            globals: RefCell::new(Globals::new(imports.globals)),
+            tables,
             module,
-            current_function_state: RefCell::new(initial_function_state),
-            wasi: RefCell::new(Wasi::new()),
+            code: module.functions().iter().map(|_| OnceCell::new()).collect(),
+            register_code: module.functions().iter().map(|_| OnceCell::new()).collect(),
+            registers: RefCell::new(Registers::default()),
+            imported_functions: imports.functions,
+            instance_id,
+            store,
             function_depth: Cell::new(0),
+        }
+    }
+
+    /// Initializes the globals, tables and memory of the module and runs its start function.
+    /// Whatever was written to imported tables and memories before a trap stays there
+    fn initialize(&self) -> Result<(), Trap> {
+        self.initialize_globals()?;
+        self.initilize_elements()?;
+        self.initialize_datas()?;
+        self.run_start()
+    }
+
+    /// Calls a function of the module with the given arguments and returns its results
+    pub fn call(&self, func_idx: FuncIdx, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let function = self
+            .module
+            .get_function(func_idx)
+            .expect("Called function has to exist");
+
+        if self.store.enclosing_depth() >= self.store.config().max_call_depth {
+            return Err(Trap::StackOverflow);
+        }
+        self.store.check_native_stack()?;
+        // The engines only keep the bits of a reference, not the store it belongs to
+        if !args.iter().all(|arg| arg.belongs_to(self.store.id)) {
+            return Err(Trap::ForeignReference);
+        }
+
+        if let Function::Imported(_) = function {
+            return self.call_imported(func_idx, args);
+        }
+        let execution = match self.store.config().engine {
+            Engine::Stack => {
+                let mut stack = self.stack.borrow_mut();
+                for arg in args {
+                    stack.push_value(*arg);
+                }
+                drop(stack);
+                self.start_function(func_idx)
+            }
+            Engine::Registers => self.start_registers(func_idx, args),
         };
+        self.continue_execution(execution)
+    }
 
-        runtime.initilize_elements();
-        runtime.initialize_globals();
-        runtime.initialize_datas();
-        runtime.run_start();
+    /// Runs a called function until it returns or traps. When the outermost call of the store
+    /// runs out of fuel its execution is kept as it is, so the store can resume it
+    fn continue_execution(&self, execution: Execution) -> Result<Vec<Value>, Trap> {
+        let depth = self.store.enter_call();
+        let result = match execution {
+            Execution::Stack { .. } => self.run(),
+            Execution::Registers { frames_base, .. } => self.run_registers(frames_base),
+        };
+        self.store.leave_call();
+
+        let execution = match result {
+            Err(Trap::OutOfFuel) => match self.store.suspend(self.instance_id, depth, execution) {
+                Ok(()) => return result,
+                Err(execution) => execution,
+            },
+            _ => execution,
+        };
+        self.finish_function(execution, &result);
+        result
+    }
 
-        runtime
+    fn instance_id(&self) -> InstanceId {
+        self.instance_id
     }
 
-    fn wasi_function(&self, name: &str) {
-        self.wasi.borrow_mut().run_function(
-            name,
-            self.stack.borrow_mut(),
-            self.memory.borrow_mut(),
-        );
+    fn store_id(&self) -> StoreId {
+        self.store.id
     }
 
-    pub fn run_expr<T>(&self, expr: FuncIdx, mut get_result_after_expr: impl FnMut() -> T) -> T {
-        // Swaped in next line
-        let mut function_state_before_expr = FunctionState::new_function(Locals::empty(), expr);
-        std::mem::swap(
-            &mut function_state_before_expr,
-            self.current_function_state.borrow_mut().deref_mut(),
-        );
-        self.execute();
+    fn memory(&self) -> MemoryHandle {
+        self.memory.clone()
+    }
 
-        let result = get_result_after_expr();
+    fn global(&self, global_idx: GlobalIdx) -> GlobalHandle {
+        self.globals.borrow().handle(global_idx)
+    }
 
-        assert!(self.stack.borrow().is_empty(), "Stack is empty");
-        std::mem::swap(
-            &mut function_state_before_expr,
-            self.current_function_state.borrow_mut().deref_mut(),
-        );
+    fn table(&self, table_idx: TableIdx) -> TableHandle {
+        self.tables.handle(table_idx)
+    }
 
+    /// Runs a function from its first instruction, whatever was running before continues
+    /// afterwards even if the function traps
+    fn run_function(&self, func_idx: FuncIdx) -> Result<Vec<Value>, Trap> {
+        let execution = self.start_function(func_idx);
+        let result = self.run();
+        self.finish_function(execution, &result);
         result
     }
 
-    fn run_start(&self) {
-        if let Some(start_idx) = self.module.get_initializer() {
-            self.run_expr(start_idx, || {});
+    /// Makes the function the one that runs next, its arguments have to be on the stack
+    /// already
+    fn start_function(&self, func_idx: FuncIdx) -> Execution {
+        let code = self.code(func_idx);
+        let mut stack = self.stack.borrow_mut();
+        let frames_base = stack.amount_of_frames();
+        stack.enter(func_idx, code.amount_of_params, &code.locals);
+        Execution::Stack {
+            stack_base: stack.frame().fp(),
+            frames_base,
+            function_depth_before: self.function_depth.replace(0),
         }
     }
 
-    fn initilize_elements(&self) {
-        let module_borrow = self.module;
+    /// Makes the function the one the register engine runs next, in a frame above the frames
+    /// of the functions that are running already
+    fn start_registers(&self, func_idx: FuncIdx, args: &[Value]) -> Execution {
+        let code = self.register_code(func_idx);
+        let mut registers = self.registers.borrow_mut();
+        let fp = match registers.frames.last() {
+            Some(frame) => frame.fp + self.register_code(frame.func_idx).frame_size,
+            None => 0,
+        };
+        registers.enter(fp, code);
+        for (slot, arg) in registers.slots[fp..].iter_mut().zip(args) {
+            *slot = arg.to_bits();
+        }
+        registers.frames.push(Frame {
+            func_idx,
+            pc: 0,
+            charged: 0,
+            fp,
+        });
+        Execution::Registers {
+            frames_base: registers.frames.len() - 1,
+            function_depth_before: self.function_depth.replace(0),
+        }
+    }
+
+    /// Restores what was running before the function, after a trap the stack is cleaned up
+    /// too
+    fn finish_function<T>(&self, execution: Execution, result: &Result<T, Trap>) {
+        match execution {
+            Execution::Stack {
+                stack_base,
+                frames_base,
+                function_depth_before,
+            } => {
+                if result.is_err() {
+                    let mut stack = self.stack.borrow_mut();
+                    stack.truncate(stack_base);
+                    stack.truncate_frames(frames_base);
+                }
+                self.function_depth.set(function_depth_before);
+            }
+            Execution::Registers {
+                frames_base,
+                function_depth_before,
+            } => {
+                if result.is_err() {
+                    self.registers.borrow_mut().frames.truncate(frames_base);
+                }
+                self.function_depth.set(function_depth_before);
+            }
+        }
+    }
+
+    pub fn run_expr<T>(
+        &self,
+        expr: FuncIdx,
+        mut get_result_after_expr: impl FnMut() -> T,
+    ) -> Result<T, Trap> {
+        let stack_base = self.stack.borrow().len();
+        self.run_function(expr)?;
 
-        for element in module_borrow.elements() {
+        let result = get_result_after_expr();
+
+        assert_eq!(
+            self.stack.borrow().len(),
+            stack_base,
+            "Result of the expression should be taken from the stack"
+        );
+
+        Ok(result)
+    }
+
+    fn run_start(&self) -> Result<(), Trap> {
+        match self.module.get_initializer() {
+            Some(start_idx) => match self.module.get_function(start_idx) {
+                Some(Function::Imported(_)) => self.call_imported(start_idx, &[]).map(drop),
+                _ => self.run_expr(start_idx, || {}),
+            },
+            None => Ok(()),
+        }
+    }
+
+    fn initilize_elements(&self) -> Result<(), Trap> {
+        for (element, dropped) in self.module.elements().iter().zip(&self.dropped_elements) {
             match element.mode {
-                ElementMode::Declarative => {}
+                ElementMode::Declarative => dropped.set(true),
                 ElementMode::Passive => {}
                 ElementMode::Active {
                     table,
@@ -184,23 +379,24 @@ impl<'a, 'b> Runtime<'b, 'a> {
                 } => {
                     let offset = self.run_expr(offset_in_table, || {
                         TableElementIdx(self.stack.borrow_mut().pop_u32() as usize)
-                    });
+                    })?;
                     let refs = element
                         .init
                         .iter()
-                        .map(|init| self.run_expr(*init, || self.stack.borrow_mut().pop_ref()))
-                        .collect::<Vec<_>>();
+                        .map(|init| {
+                            self.run_expr(*init, || self.stack.borrow_mut().pop_ref(self.store.id))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
 
-                    self.tables
-                        .borrow_mut()
-                        .table_mut(table)
-                        .fill(offset, &refs);
+                    self.tables.table_mut(table).fill(offset, &refs)?;
+                    dropped.set(true);
                 }
             }
         }
+        Ok(())
     }
 
-    fn initialize_globals(&self) {
+    fn initialize_globals(&self) -> Result<(), Trap> {
         let globals = self
             .module
             .global_initializers()
@@ -209,562 +405,1014 @@ impl<'a, 'b> Runtime<'b, 'a> {
                 let value = self.run_expr(global.init, || {
                     self.stack
                         .borrow_mut()
-                        .pop_value_by_type(global.signature.valtype)
-                });
-                Global::new(value, global.signature.mutability)
+                        .pop_value(global.signature.valtype, self.store.id)
+                })?;
+                Ok(Global::new(value, global.signature.mutability))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
 
-        self.globals.borrow_mut().fill(globals);
+        self.globals.borrow_mut().extend(globals, self.store.id);
+        Ok(())
     }
 
-    fn initialize_datas(&self) {
-        for data in self.module.datas().iter() {
+    fn initialize_datas(&self) -> Result<(), Trap> {
+        for (data, dropped) in self.module.datas().iter().zip(&self.dropped_datas) {
             match data.mode {
-                crate::types::DataMode::Passive => continue,
-                crate::types::DataMode::Active { ref offset, .. } => {
-                    let offset = self.run_expr(*offset, || self.stack.borrow_mut().pop_u32());
-                    self.memory.borrow_mut().fill_data(offset, &data.init);
+                DataMode::Passive => continue,
+                DataMode::Active { ref offset, .. } => {
+                    let offset = self.run_expr(*offset, || self.stack.borrow_mut().pop_u32())?;
+                    self.memory.borrow_mut().fill_data(offset, &data.init)?;
+                    dropped.set(true);
                 }
             }
         }
+        Ok(())
     }
 
-    fn call_function(&self, func_idx: FuncIdx) {
+    fn call_function(&self, func_idx: FuncIdx) -> Result<(), Trap> {
         let next_function = self.module.get_function(func_idx).unwrap();
         match next_function {
             Function::Local(function) => {
+                self.store.check_epoch()?;
+                let code = self.code(func_idx);
+                let mut stack = self.stack.borrow_mut();
+                self.check_stack_limits(stack.len() + code.locals.len())?;
                 self.function_depth.set(self.function_depth.get() + 1);
-                let locals = Locals::new(
-                    &function.code.locals,
-                    &function.signature.params,
-                    self.stack.borrow_mut().deref_mut(),
-                );
-
-                let mut new_function_state = FunctionState::new_function(locals, func_idx);
-
-                std::mem::swap(
-                    &mut new_function_state,
-                    self.current_function_state.borrow_mut().deref_mut(),
-                );
-                self.stack
-                    .borrow_mut()
-                    .push_function_state(new_function_state);
+                stack.enter(func_idx, function.signature.params.len(), &code.locals);
             }
             Function::Imported(function) => {
-                if function.mod_name == "wasi_snapshot_preview1" {
-                    self.wasi_function(function.name);
-                } else {
-                    panic!("Unkown module import: {:?}", function.mod_name);
+                let args = self.pop_results(&function.signature.params);
+                let results = self.call_imported(func_idx, &args)?;
+
+                let mut stack = self.stack.borrow_mut();
+                for result in results {
+                    stack.push_value(result);
                 }
             }
         }
+        Ok(())
     }
 
-    fn break_from_block(&self, break_from_idx: BlockIdx, current_function: &LocalFunction) {
-        let block_type = current_function
-            .code
-            .instructions
-            .get_block_type(break_from_idx);
-        let block_type_slice = block_type_to_slice!(block_type);
+    /// Calls a function the instance imported, which runs outside of the engines
+    fn call_imported(&self, func_idx: FuncIdx, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let results = match &self.imported_functions[func_idx.0 as usize] {
+            ImportedFunc::Host(host_function) => host_function.call(
+                &mut Caller::new(&self.memory, &self.store.extern_objects, self.store.id),
+                args,
+            ),
+            ImportedFunc::Instance(function) => self
+                .store
+                .nested_call(self.function_depth.get() + 1, || function.call(args)),
+        }?;
+        // The function may be in or hold on to another store, its references can't come in
+        if !results
+            .iter()
+            .all(|result| result.belongs_to(self.store.id))
+        {
+            return Err(Trap::ForeignReference);
+        }
+        Ok(results)
+    }
 
-        self.return_from_context(block_type_slice, || {
-            self.stack
-                .borrow_mut()
-                .push_function_state(self.current_function_state.borrow().clone());
-            let mut new_function_state = self.stack.borrow_mut().break_from_block(break_from_idx);
-            if current_function
-                .code
-                .instructions
-                .is_block_loop(break_from_idx)
-            {
-                new_function_state.repeat_instruction();
-            }
-            new_function_state
-        });
+    /// Looks up the function `call_indirect` calls and checks that it has the expected type,
+    /// along with the instance it's in if that's another one
+    fn indirect_callee(
+        &self,
+        type_idx: FuncTypeIdx,
+        table_idx: TableIdx,
+        table_element_idx: TableElementIdx,
+    ) -> Result<(FuncIdx, Option<Rc<Runtime<'b, 'a>>>), Trap> {
+        let table = self.tables.table(table_idx);
+        let Ref::Func(func_ref) = table.get(table_element_idx)? else {
+            return Err(Trap::UninitializedElement);
+        };
+        drop(table);
+
+        let signature = self
+            .module
+            .function_signature(type_idx)
+            .expect("Type indices were validated");
+        // Tables only hold references of their own store, but the instance the reference
+        // points into may be gone with the store that owned it
+        let runtime = if func_ref.instance == self.instance_id {
+            None
+        } else {
+            Some(
+                self.store
+                    .instance(func_ref.instance)
+                    .ok_or(Trap::ForeignReference)?,
+            )
+        };
+        let module = runtime
+            .as_ref()
+            .map_or(self.module, |runtime| runtime.module);
+        let func = module
+            .get_function(func_ref.func_idx)
+            .expect("Function references only hold validated function indices");
+        if func.signature().deref() != signature.deref() {
+            return Err(Trap::IndirectCallTypeMismatch);
+        }
+        Ok((func_ref.func_idx, runtime))
     }
 
-    fn execute_block(&self, block_idx: BlockIdx) {
-        let mut new_function_state = self.current_function_state.borrow().new_block(block_idx);
-        std::mem::swap(
-            &mut new_function_state,
-            self.current_function_state.borrow_mut().deref_mut(),
-        );
-        self.stack
-            .borrow_mut()
-            .push_function_state(new_function_state);
+    /// Replaces any NaN with the canonical one if the store wants execution to be deterministic
+    fn canonicalize_f32(&self, value: f32) -> f32 {
+        if value.is_nan() && self.store.config().canonicalize_nans {
+            return f32::from_bits(0x7fc0_0000);
+        }
+        value
     }
 
-    fn pop_returns(&self, signature_returns: &[ValueType]) -> Vec<Value> {
-        let amount_of_returns = signature_returns.len();
-        let mut returns = Vec::with_capacity(amount_of_returns);
-        for return_type in signature_returns.iter().rev() {
-            let value = self.stack.borrow_mut().pop_value_by_type(*return_type);
-            returns.push(value);
+    fn canonicalize_f64(&self, value: f64) -> f64 {
+        if value.is_nan() && self.store.config().canonicalize_nans {
+            return f64::from_bits(0x7ff8_0000_0000_0000);
         }
-        returns
+        value
     }
 
-    fn reassemble_returns(&self, returns: &mut Vec<Value>) {
-        for _ in 0..returns.len() {
-            self.stack
-                .borrow_mut()
-                .push_value(returns.pop().expect("Pushed enough elements"));
+    /// Traps before another function is called if there are too many calls running already or
+    /// the values of the engine take up too much, `stack_size` counts them
+    fn check_stack_limits(&self, stack_size: usize) -> Result<(), Trap> {
+        let config = self.store.config();
+        // The running function and the ones waiting for it to return
+        let depth = self.store.enclosing_depth() + self.function_depth.get() + 1;
+        if depth >= config.max_call_depth || stack_size >= config.max_stack_size {
+            return Err(Trap::StackOverflow);
         }
+        Ok(())
     }
 
-    fn return_from_context(
-        &self,
-        signature_returns: &[ValueType],
-        mut get_next_function_state: impl FnMut() -> FunctionState,
-    ) {
-        let mut returns = self.pop_returns(signature_returns);
-        let function_state = get_next_function_state();
-        self.reassemble_returns(&mut returns);
-        *self.current_function_state.borrow_mut() = function_state;
-    }
-
-    fn return_function_end(&self, signature_returns: &[ValueType]) {
-        self.return_from_context(signature_returns, || {
-            self.stack.borrow_mut().pop_function_state()
+    fn pop_results(&self, signature_returns: &[ValueType]) -> Vec<Value> {
+        let mut stack = self.stack.borrow_mut();
+        let mut results = signature_returns
+            .iter()
+            .rev()
+            .map(|return_type| stack.pop_value(*return_type, self.store.id))
+            .collect::<Vec<_>>();
+        results.reverse();
+        results
+    }
+
+    /// The lowered code of a local function, it's compiled the first time it runs
+    fn code(&self, FuncIdx(idx): FuncIdx) -> &Code<'b> {
+        self.code[idx as usize].get_or_init(|| {
+            let Some(Function::Local(function)) = self.module.get_function(FuncIdx(idx)) else {
+                unreachable!("Only local functions are executed")
+            };
+            Code::compile(self.module, function)
         })
     }
 
-    fn return_immediate(&self, signature_returns: &[ValueType]) {
-        self.return_from_context(signature_returns, || {
-            if !self.current_function_state.borrow().in_block() {
-                self.stack
-                    .borrow_mut()
-                    .push_function_state(self.current_function_state.borrow().clone())
-            }
-            self.stack
-                .borrow_mut()
-                .pop_until_function_state(self.current_function_state.borrow().deref())
-        });
-        self.function_depth.set(self.function_depth.get() - 1);
+    /// Takes a branch, branching backwards repeats a loop
+    fn branch(&self, pc: &mut usize, label: &Label) -> Result<(), Trap> {
+        if label.target < *pc {
+            // Every long running execution passes a loop back-edge or a call eventually
+            self.store.check_epoch()?;
+        }
+        self.stack.borrow_mut().drop_keep(label.drop_keep);
+        *pc = label.target;
+        Ok(())
     }
 
-    pub fn execute(&self) {
+    /// Executes instructions until the function that was started last returns
+    fn run(&self) -> Result<Vec<Value>, Trap> {
+        let function_state = *self.stack.borrow().frame();
+        let mut func_idx = function_state.function_idx();
+        let mut pc = function_state.pc();
+        let mut code = self.code(func_idx);
+
         loop {
-            let Some(Function::Local(current_function)) = self
-                .module
-                .get_function(self.current_function_state.borrow().function_idx())
-            else {
-                unreachable!(
-                    "Current runing function cannot be imported and its index has to exist"
-                )
-            };
+            let op = &code.ops[pc];
+            if let Some(instruction) = op.instruction() {
+                // Charged before the instruction counts as executed, so it runs once resumed
+                if let Err(trap) = self.store.consume_fuel(instruction) {
+                    self.stack.borrow_mut().frame_mut().set_pc(pc);
+                    return Err(trap);
+                }
+            }
+            pc += 1;
 
-            let current_function_state_borrow = self.current_function_state.borrow();
-            if current_function
-                .code
-                .instructions
-                .done(current_function_state_borrow.instruction_index())
-            {
-                if self.function_depth.get() > 0 || current_function_state_borrow.in_block() {
-                    let index = current_function_state_borrow.instruction_index();
-                    drop(current_function_state_borrow);
-
-                    match index {
-                        function_state::InstructionIndex::IndexInFunction(_) => {
-                            self.return_function_end(&current_function.signature.returns);
-                            self.function_depth.set(self.function_depth.get() - 1);
-                        }
-                        function_state::InstructionIndex::IndexInBlock { block_idx, .. } => {
-                            let block_type =
-                                current_function.code.instructions.get_block_type(block_idx);
-                            let block_type_slice = block_type_to_slice!(block_type);
-                            self.return_function_end(block_type_slice)
+            match op {
+                Op::Execute(instruction) => {
+                    self.run_instruction(instruction, &mut StackOperands(&self.stack))?
+                }
+                Op::Call(instruction) => {
+                    self.stack.borrow_mut().frame_mut().set_pc(pc);
+                    self.call_instruction(instruction)?;
+
+                    let function_state = *self.stack.borrow().frame();
+                    func_idx = function_state.function_idx();
+                    pc = function_state.pc();
+                    code = self.code(func_idx);
+                }
+                Op::Jump(target) => pc = *target,
+                Op::Br { label, .. } => self.branch(&mut pc, label)?,
+                Op::BrIf { label, .. } => {
+                    if self.stack.borrow_mut().pop_bool() {
+                        self.branch(&mut pc, label)?;
+                    }
+                }
+                Op::BrUnless { target, .. } => {
+                    if !self.stack.borrow_mut().pop_bool() {
+                        pc = *target;
+                    }
+                }
+                Op::BrTable {
+                    labels, default, ..
+                } => {
+                    let index = self.stack.borrow_mut().pop_u32() as usize;
+                    self.branch(&mut pc, labels.get(index).unwrap_or(default))?;
+                }
+                Op::Return => {
+                    let amount_of_locals = code.amount_of_params + code.locals.len();
+                    if self.function_depth.get() == 0 {
+                        let results = self.pop_results(code.returns);
+                        self.stack.borrow_mut().leave(amount_of_locals);
+                        return Ok(results);
+                    }
+
+                    let mut stack = self.stack.borrow_mut();
+                    stack.leave(amount_of_locals);
+                    let function_state = *stack.frame();
+                    func_idx = function_state.function_idx();
+                    pc = function_state.pc();
+                    code = self.code(func_idx);
+                    self.function_depth.set(self.function_depth.get() - 1);
+                }
+            }
+        }
+    }
+
+    /// Runs `call` or `call_indirect` on the stack engine, a local function becomes the running
+    /// one
+    fn call_instruction(&self, instruction: &Instruction) -> Result<(), Trap> {
+        match instruction {
+            Instruction::Call(func_idx) => self.call_function(*func_idx),
+            Instruction::CallIndirect(type_idx, table_idx) => {
+                let table_element_idx = self.stack.borrow_mut().pop_table_element_idx();
+                match self.indirect_callee(*type_idx, *table_idx, table_element_idx)? {
+                    (func_idx, None) => self.call_function(func_idx),
+                    (func_idx, Some(runtime)) => {
+                        let signature = runtime.module.get_function(func_idx).unwrap().signature();
+                        let args = self.pop_results(&signature.params);
+                        let results =
+                            self.store.nested_call(self.function_depth.get() + 1, || {
+                                runtime.call(func_idx, &args)
+                            })?;
+                        let mut stack = self.stack.borrow_mut();
+                        for result in results {
+                            stack.push_value(result);
                         }
+                        Ok(())
                     }
-                    continue;
-                } else {
-                    break;
                 }
             }
+            _ => unreachable!("Only calls are lowered into call operations"),
+        }
+    }
 
-            let instruction = &current_function
-                .code
-                .instructions
-                .get_instruction(current_function_state_borrow.instruction_index());
-            drop(current_function_state_borrow);
+    fn fusions(&self) -> Fusions {
+        let mut fusions = Fusions::default();
+        for code in self.register_code.iter().filter_map(OnceCell::get) {
+            fusions.add(&code.fusions);
+        }
+        fusions
+    }
 
-            self.current_function_state.borrow_mut().next_instruction();
+    /// The translated code of a local function for the register engine, it's translated the
+    /// first time it runs
+    fn register_code(&self, FuncIdx(idx): FuncIdx) -> &RegisterCode<'b> {
+        self.register_code[idx as usize].get_or_init(|| {
+            let Some(Function::Local(function)) = self.module.get_function(FuncIdx(idx)) else {
+                unreachable!("Only local functions are executed")
+            };
+            RegisterCode::compile(self.module, function)
+        })
+    }
 
-            self.run_instruction(instruction, current_function);
+    /// Takes a branch of the register engine, its values are moved to where the label expects
+    /// them
+    fn branch_registers(
+        &self,
+        slots: &mut [u64],
+        pc: &mut usize,
+        label: &registers::Label,
+    ) -> Result<(), Trap> {
+        if label.target < *pc {
+            self.store.check_epoch()?;
         }
+        let Moves { from, to, count } = label.moves;
+        let (from, to) = (from as usize, to as usize);
+        slots.copy_within(from..from + count as usize, to);
+        *pc = label.target;
+        Ok(())
     }
 
-    fn run_instruction(&self, instruction: &Instruction, current_function: &LocalFunction) {
-        match instruction {
-            Instruction::Unreachable => {
-                panic!("Unreachable!")
+    /// Calls a function from a frame of the register engine, its arguments are in the slots
+    /// starting at `args` and so will be its results. Returns the frame of a local function,
+    /// which runs next and is pushed already
+    fn call_registers(
+        &self,
+        func_idx: FuncIdx,
+        runtime: Option<Rc<Runtime<'b, 'a>>>,
+        args: usize,
+    ) -> Result<Option<Frame>, Trap> {
+        let function = runtime
+            .as_ref()
+            .map_or(self.module, |runtime| runtime.module)
+            .get_function(func_idx)
+            .expect("Function indices were validated");
+        let call_args = |registers: &Registers| {
+            let params = &function.signature().params;
+            params
+                .iter()
+                .zip(&registers.slots[args..])
+                .map(|(param, bits)| Value::from_bits(*bits, *param, self.store.id))
+                .collect::<Vec<_>>()
+        };
+
+        let results = match (function, runtime) {
+            (Function::Local(_), None) => {
+                self.store.check_epoch()?;
+                let code = self.register_code(func_idx);
+                self.check_stack_limits(args + code.frame_size)?;
+                self.function_depth.set(self.function_depth.get() + 1);
+                let frame = Frame {
+                    func_idx,
+                    pc: 0,
+                    charged: 0,
+                    fp: args,
+                };
+                let mut registers = self.registers.borrow_mut();
+                registers.enter(args, code);
+                registers.frames.push(frame);
+                return Ok(Some(frame));
             }
-            Instruction::Nop => {}
-            Instruction::Block(block_idx) => self.execute_block(*block_idx),
-            Instruction::Loop(block_idx) => self.execute_block(*block_idx),
-            Instruction::If { if_expr, else_expr } => {
-                let condition = self.stack.borrow_mut().pop_bool();
-                self.execute_block(if condition { *if_expr } else { *else_expr });
+            (Function::Imported(_), None) => {
+                let args = call_args(&self.registers.borrow());
+                self.call_imported(func_idx, &args)?
             }
-            Instruction::Break(break_from_idx) => {
-                self.break_from_block(*break_from_idx, current_function);
+            (_, Some(runtime)) => {
+                let args = call_args(&self.registers.borrow());
+                self.store.nested_call(self.function_depth.get() + 1, || {
+                    runtime.call(func_idx, &args)
+                })?
             }
-            Instruction::BreakIf(break_from_idx) => {
-                let should_break = self.stack.borrow_mut().pop_bool();
-                if should_break {
-                    self.break_from_block(*break_from_idx, current_function);
+        };
+        let slots = &mut self.registers.borrow_mut().slots[args..];
+        for (slot, result) in slots.iter_mut().zip(results) {
+            *slot = result.to_bits();
+        }
+        Ok(None)
+    }
+
+    /// Executes operations of the register engine until the frame at `frames_base` returns
+    fn run_registers(&self, frames_base: usize) -> Result<Vec<Value>, Trap> {
+        let Frame {
+            mut func_idx,
+            mut pc,
+            mut charged,
+            mut fp,
+        } = *self.registers.borrow().frames.last().unwrap();
+        let mut code = self.register_code(func_idx);
+
+        loop {
+            if self.store.meters_fuel() {
+                // Charged before the operation counts as executed, so it runs once resumed.
+                // The instructions that were paid already aren't charged again then
+                for instruction in &code.charge(pc)[charged..] {
+                    if let Err(trap) = self.store.consume_fuel(instruction) {
+                        let mut registers = self.registers.borrow_mut();
+                        let frame = registers.frames.last_mut().unwrap();
+                        (frame.pc, frame.charged) = (pc, charged);
+                        return Err(trap);
+                    }
+                    charged += 1;
                 }
+                charged = 0;
             }
-            Instruction::BreakTable { labels, default } => {
-                let index = self.stack.borrow_mut().pop_i32() as usize;
-                let block_index = *labels.get(index).unwrap_or(default);
-                self.break_from_block(block_index, current_function);
-            }
-            Instruction::Return => {
-                if self.function_depth.get() == 0 {
-                    exit(0);
+            let op = &code.ops[pc];
+            pc += 1;
+
+            let mut borrow = self.registers.borrow_mut();
+            let registers = borrow.deref_mut();
+            let slots = &mut registers.slots[fp..];
+            match op {
+                RegisterOp::Execute {
+                    instruction,
+                    inputs,
+                    amount_of_inputs,
+                    result,
+                } => {
+                    let inputs = &inputs[..*amount_of_inputs as usize];
+                    let mut operands = SlotOperands::new(slots, inputs, *result);
+                    self.run_instruction(instruction, &mut operands)?;
                 }
-                self.return_immediate(&current_function.signature.returns[..]);
-            }
-
-            Instruction::Call(func_idx) => {
-                self.call_function(*func_idx);
-            }
-            Instruction::CallIndirect(type_idx, table_idx) => {
-                let tables_borrow = self.tables.borrow();
-                let table = tables_borrow.table(*table_idx);
-                let table_element_idx = self.stack.borrow_mut().pop_table_element_idx();
-                let Some(func_idx) = table.get(table_element_idx) else {
-                    panic!("Issued call_indirect on a null reference.")
-                };
+                RegisterOp::I32AddImmediate {
+                    a,
+                    immediate,
+                    result,
+                } => {
+                    let a = slots[*a as usize] as i32;
+                    slots[*result as usize] = a.wrapping_add(*immediate) as u32 as u64;
+                }
+                RegisterOp::I32Load {
+                    address,
+                    memarg,
+                    result,
+                } => {
+                    let address = slots[*address as usize] as u32;
+                    let value = self.memory.borrow_mut().load_i32(address, *memarg)?;
+                    slots[*result as usize] = value as u32 as u64;
+                }
+                RegisterOp::I32Store {
+                    address,
+                    value,
+                    memarg,
+                } => {
+                    let address = slots[*address as usize] as u32;
+                    let value = slots[*value as usize] as i32;
+                    self.memory
+                        .borrow_mut()
+                        .store_i32(value, address, *memarg)?;
+                }
+                RegisterOp::Copy { from, to } => slots[*to as usize] = slots[*from as usize],
+                RegisterOp::Jump(target) => pc = *target,
+                RegisterOp::Br(label) => self.branch_registers(slots, &mut pc, label)?,
+                RegisterOp::BrIf { condition, label } => {
+                    if slots[*condition as usize] as u32 != 0 {
+                        self.branch_registers(slots, &mut pc, label)?;
+                    }
+                }
+                RegisterOp::BrUnless { condition, target } => {
+                    if slots[*condition as usize] as u32 == 0 {
+                        pc = *target;
+                    }
+                }
+                RegisterOp::BrIfEqz { condition, label } => {
+                    if slots[*condition as usize] as u32 == 0 {
+                        self.branch_registers(slots, &mut pc, label)?;
+                    }
+                }
+                RegisterOp::BrIfCompare {
+                    comparison,
+                    a,
+                    b,
+                    label,
+                } => {
+                    let (a, b) = (slots[*a as usize] as i32, slots[*b as usize] as i32);
+                    if comparison.compare(a, b) {
+                        self.branch_registers(slots, &mut pc, label)?;
+                    }
+                }
+                RegisterOp::BrUnlessCompare {
+                    comparison,
+                    a,
+                    b,
+                    target,
+                } => {
+                    let (a, b) = (slots[*a as usize] as i32, slots[*b as usize] as i32);
+                    if !comparison.compare(a, b) {
+                        pc = *target;
+                    }
+                }
+                RegisterOp::BrTable {
+                    index,
+                    labels,
+                    default,
+                } => {
+                    let index = slots[*index as usize] as u32;
+                    let label = labels.get(index as usize).unwrap_or(default);
+                    self.branch_registers(slots, &mut pc, label)?;
+                }
+                RegisterOp::Call {
+                    func_idx: callee,
+                    args,
+                } => {
+                    registers.frames.last_mut().unwrap().pc = pc;
+                    drop(borrow);
+                    if let Some(frame) = self.call_registers(*callee, None, fp + *args as usize)? {
+                        (func_idx, pc, fp) = (frame.func_idx, frame.pc, frame.fp);
+                        code = self.register_code(func_idx);
+                    }
+                }
+                RegisterOp::CallIndirect {
+                    type_idx,
+                    table_idx,
+                    index,
+                    args,
+                } => {
+                    registers.frames.last_mut().unwrap().pc = pc;
+                    let index = slots[*index as usize] as u32;
+                    drop(borrow);
+                    let table_element_idx = TableElementIdx(index as usize);
+                    let (callee, runtime) =
+                        self.indirect_callee(*type_idx, *table_idx, table_element_idx)?;
+                    if let Some(frame) =
+                        self.call_registers(callee, runtime, fp + *args as usize)?
+                    {
+                        (func_idx, pc, fp) = (frame.func_idx, frame.pc, frame.fp);
+                        code = self.register_code(func_idx);
+                    }
+                }
+                RegisterOp::Return { results, amount } => {
+                    let (results, amount) = (*results as usize, *amount as usize);
+                    if registers.frames.len() - 1 == frames_base {
+                        let values = code
+                            .returns
+                            .iter()
+                            .zip(&slots[results..results + amount])
+                            .map(|(return_type, bits)| {
+                                Value::from_bits(*bits, *return_type, self.store.id)
+                            })
+                            .collect();
+                        registers.frames.pop();
+                        return Ok(values);
+                    }
 
-                let Some(func) = self.module.get_function(func_idx) else {
-                    panic!("Function index in table isn't a valid function index");
-                };
-                let Some(signature) = self.module.function_signature(*type_idx) else {
-                    panic!("call_indirect has invalid type index");
-                };
-                if func.signature().deref() != signature.deref() {
-                    panic!("call_indirect signature doesn't fit actual function signature");
+                    slots.copy_within(results..results + amount, 0);
+                    registers.frames.pop();
+                    let frame = *registers.frames.last().unwrap();
+                    (func_idx, pc, fp) = (frame.func_idx, frame.pc, frame.fp);
+                    code = self.register_code(func_idx);
+                    self.function_depth.set(self.function_depth.get() - 1);
                 }
+                RegisterOp::Fuel => {}
+            }
+        }
+    }
 
-                self.call_function(func_idx);
+    /// Runs an instruction that doesn't change where execution continues, on the operands of
+    /// either engine
+    fn run_instruction(
+        &self,
+        instruction: &Instruction,
+        operands: &mut impl Operands,
+    ) -> Result<(), Trap> {
+        match instruction {
+            Instruction::Unreachable => return Err(Trap::Unreachable),
+            Instruction::Nop => {}
+            Instruction::Block(_)
+            | Instruction::Loop(_)
+            | Instruction::If { .. }
+            | Instruction::Break(_)
+            | Instruction::BreakIf(_)
+            | Instruction::BreakTable { .. }
+            | Instruction::Return => unreachable!("Control instructions are lowered into jumps"),
+
+            Instruction::Call(_) | Instruction::CallIndirect(..) => {
+                unreachable!("Each engine calls functions its own way")
+            }
+            Instruction::PushNullRef(ref_type) => operands.push_ref(Ref::Null(*ref_type)),
+            Instruction::RefIsNull => {
+                let is_null = operands.pop_ref(self.store.id).is_null();
+                operands.push_bool(is_null);
             }
-            Instruction::PushNullRef(_) => self.stack.borrow_mut().push_ref(None),
-            Instruction::RefIsNull => self
-                .stack
-                .borrow_mut()
-                .push_bool(self.stack.borrow_mut().pop_ref().is_none()),
-            Instruction::PushFuncRef(func) => self.stack.borrow_mut().push_ref(Some(*func)),
+            Instruction::PushFuncRef(func_idx) => operands.push_ref(Ref::Func(FuncRef {
+                store: self.store.id,
+                instance: self.instance_id,
+                func_idx: *func_idx,
+            })),
 
             Instruction::Drop => {
-                self.stack.borrow_mut().drop_value();
-            }
-            Instruction::Select => {
-                let predicate = self.stack.borrow_mut().pop_bool();
-                let false_value = self.stack.borrow_mut().pop_value();
-                let true_value = self.stack.borrow_mut().pop_value();
-                self.stack.borrow_mut().push_value(if predicate {
-                    true_value
-                } else {
-                    false_value
-                });
+                operands.pop_bits();
+            }
+            Instruction::Select | Instruction::SelectTyped(_) => {
+                let predicate = operands.pop_bool();
+                let false_value = operands.pop_bits();
+                let true_value = operands.pop_bits();
+                operands.push_bits(if predicate { true_value } else { false_value });
             }
-            Instruction::SelectTyped(_) => todo!(),
 
+            // The register engine keeps locals in slots, these only run on the stack engine
             Instruction::LocalGet(idx) => {
-                let value = self.current_function_state.borrow().get_local_value(*idx);
-                self.stack.borrow_mut().push_value(value);
+                let value = self.stack.borrow().local(*idx);
+                operands.push_bits(value);
             }
             Instruction::LocalSet(idx) => {
-                let value = self.stack.borrow_mut().pop_value();
-                self.current_function_state
-                    .borrow_mut()
-                    .set_local_value(*idx, value);
+                let value = operands.pop_bits();
+                self.stack.borrow_mut().set_local(*idx, value);
             }
             Instruction::LocalTee(idx) => {
-                let value = self.stack.borrow_mut().pop_value();
-                self.current_function_state
-                    .borrow_mut()
-                    .set_local_value(*idx, value);
-                self.stack.borrow_mut().push_value(value);
+                let value = operands.pop_bits();
+                self.stack.borrow_mut().set_local(*idx, value);
+                operands.push_bits(value);
             }
             Instruction::GlobalGet(idx) => {
                 let value = self.globals.borrow().get(*idx);
-                self.stack.borrow_mut().push_value(value);
+                operands.push_value(value);
             }
             Instruction::GlobalSet(idx) => {
-                let value = self.stack.borrow_mut().pop_value();
+                let value_type = self.globals.borrow().get(*idx).value_type();
+                let value = operands.pop_value(value_type, self.store.id);
                 self.globals.borrow_mut().set(value, *idx);
             }
 
             Instruction::TableGet(table_idx) => {
-                let index_in_table = self.stack.borrow_mut().pop_table_element_idx();
-                let tables = self.tables.borrow();
-                let ref_value = tables.table(*table_idx).get(index_in_table);
+                let index_in_table = operands.pop_table_element_idx();
+                let ref_value = self.tables.table(*table_idx).get(index_in_table)?;
 
-                self.stack.borrow_mut().push_ref(ref_value);
+                operands.push_ref(ref_value);
             }
             Instruction::TableSet(table_idx) => {
-                let ref_value = self.stack.borrow_mut().pop_ref();
-                let index_in_table = self.stack.borrow_mut().pop_table_element_idx();
+                let ref_value = operands.pop_ref(self.store.id);
+                let index_in_table = operands.pop_table_element_idx();
                 self.tables
-                    .borrow_mut()
                     .table_mut(*table_idx)
-                    .set(index_in_table, ref_value);
+                    .set(index_in_table, ref_value)?;
             }
             Instruction::TableInit(element_idx, table_idx) => {
-                let elem = &self.module.elements()[element_idx.0 as usize];
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let src = self.stack.borrow_mut().pop_u32() as usize;
-                let dst = self.stack.borrow_mut().pop_u32() as usize;
-                let inits = elem.init[src..src + len]
+                let idx = element_idx.0 as usize;
+                // A dropped segment behaves like an empty one
+                let inits = if self.dropped_elements[idx].get() {
+                    &[][..]
+                } else {
+                    &self.module.elements()[idx].init[..]
+                };
+                let len = operands.pop_u32() as usize;
+                let src = operands.pop_u32() as usize;
+                let dst = operands.pop_table_element_idx();
+                let refs = src
+                    .checked_add(len)
+                    .and_then(|end| inits.get(src..end))
+                    .ok_or(Trap::TableOutOfBounds)?
                     .iter()
-                    .map(|init| self.run_expr(*init, || self.stack.borrow_mut().pop_ref()));
-                let mut tables = self.tables.borrow_mut();
-                let table = tables.table_mut(*table_idx);
-                for (i, func_ref) in inits.enumerate() {
-                    table.set(TableElementIdx(i + dst), func_ref);
-                }
+                    .map(|init| {
+                        self.run_expr(*init, || self.stack.borrow_mut().pop_ref(self.store.id))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Checks the whole range before writing, the table stays as it is on a trap
+                self.tables.table_mut(*table_idx).fill(dst, &refs)?;
+            }
+            Instruction::ElementDrop(element_idx) => {
+                self.dropped_elements[element_idx.0 as usize].set(true)
             }
-            Instruction::ElementDrop(_) => {}
             Instruction::TableCopy(dst_idx, src_idx) => {
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let src_offset = self.stack.borrow_mut().pop_table_element_idx();
-                let dst_offset = self.stack.borrow_mut().pop_table_element_idx();
+                let len = operands.pop_u32() as usize;
+                let src_offset = operands.pop_table_element_idx();
+                let dst_offset = operands.pop_table_element_idx();
                 self.tables
-                    .borrow_mut()
-                    .copy(*dst_idx, *src_idx, dst_offset, src_offset, len);
+                    .copy(*dst_idx, *src_idx, dst_offset, src_offset, len)?;
             }
             Instruction::TableGrow(table) => {
-                let mut tables = self.tables.borrow_mut();
-                let table = tables.table_mut(*table);
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let val = self.stack.borrow_mut().pop_ref();
-                let size = table.grow(len, val);
-                self.stack.borrow_mut().push_u32(size as u32);
+                let mut table = self.tables.table_mut(*table);
+                let delta = operands.pop_u32();
+                let val = operands.pop_ref(self.store.id);
+                let prev_size = table.grow(delta, val).map_or(-1, |size| size as i32);
+                operands.push_i32(prev_size);
             }
             Instruction::TableFill(table_idx) => {
-                let mut tables = self.tables.borrow_mut();
-                let table = tables.table_mut(*table_idx);
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let val = self.stack.borrow_mut().pop_ref();
-                let offset = self.stack.borrow_mut().pop_table_element_idx();
-                table.fill_value(offset, val, len);
+                let mut table = self.tables.table_mut(*table_idx);
+                let len = operands.pop_u32() as usize;
+                let val = operands.pop_ref(self.store.id);
+                let offset = operands.pop_table_element_idx();
+                table.fill_value(offset, val, len)?;
             }
             Instruction::TableSize(table_idx) => {
-                let tables = self.tables.borrow();
-                let table = tables.table(*table_idx);
-                self.stack.borrow_mut().push_u32(table.size() as u32);
-            }
-
-            Instruction::I32Load(memarg) => memory_load!(self, i32, load_i32, memarg),
-            Instruction::I64Load(memarg) => memory_load!(self, i64, load_i64, memarg),
-            Instruction::F32Load(memarg) => memory_load!(self, f32, load_f32, memarg),
-            Instruction::F64Load(memarg) => memory_load!(self, f64, load_f64, memarg),
-            Instruction::I32Load8S(memarg) => memory_load!(self, i32, load_i32_8, memarg),
-            Instruction::I32Load8U(memarg) => memory_load!(self, u32, load_u32_8, memarg),
-            Instruction::I32Load16S(memarg) => memory_load!(self, i32, load_i32_16, memarg),
-            Instruction::I32Load16U(memarg) => memory_load!(self, u32, load_u32_16, memarg),
-            Instruction::I64Load8S(memarg) => memory_load!(self, i64, load_i64_8, memarg),
-            Instruction::I64Load8U(memarg) => memory_load!(self, u64, load_u64_8, memarg),
-            Instruction::I64Load16S(memarg) => memory_load!(self, i64, load_i64_16, memarg),
-            Instruction::I64Load16U(memarg) => memory_load!(self, u64, load_u64_16, memarg),
-            Instruction::I64Load32S(memarg) => memory_load!(self, i64, load_i64_32, memarg),
-            Instruction::I64Load32U(memarg) => memory_load!(self, u64, load_u64_32, memarg),
-            Instruction::I32Store(memarg) => memory_store!(self, i32, store_i32, memarg),
-            Instruction::I64Store(memarg) => memory_store!(self, i64, store_i64, memarg),
-            Instruction::F32Store(memarg) => memory_store!(self, f32, store_f32, memarg),
-            Instruction::F64Store(memarg) => memory_store!(self, f64, store_f64, memarg),
-            Instruction::I32Store8(memarg) => memory_store!(self, i32, store_i32_8, memarg),
-            Instruction::I32Store16(memarg) => memory_store!(self, i32, store_i32_16, memarg),
-            Instruction::I64Store8(memarg) => memory_store!(self, i64, store_i64_8, memarg),
-            Instruction::I64Store16(memarg) => memory_store!(self, i64, store_i64_16, memarg),
-            Instruction::I64Store32(memarg) => memory_store!(self, i64, store_i64_32, memarg),
-            Instruction::MemorySize => self
-                .stack
-                .borrow_mut()
-                .push_u32(self.memory.borrow().size()),
+                let table = self.tables.table(*table_idx);
+                operands.push_u32(table.size() as u32);
+            }
+
+            Instruction::I32Load(memarg) => memory_load!(self, operands, i32, load_i32, memarg),
+            Instruction::I64Load(memarg) => memory_load!(self, operands, i64, load_i64, memarg),
+            Instruction::F32Load(memarg) => memory_load!(self, operands, f32, load_f32, memarg),
+            Instruction::F64Load(memarg) => memory_load!(self, operands, f64, load_f64, memarg),
+            Instruction::I32Load8S(memarg) => memory_load!(self, operands, i32, load_i32_8, memarg),
+            Instruction::I32Load8U(memarg) => memory_load!(self, operands, u32, load_u32_8, memarg),
+            Instruction::I32Load16S(memarg) => {
+                memory_load!(self, operands, i32, load_i32_16, memarg)
+            }
+            Instruction::I32Load16U(memarg) => {
+                memory_load!(self, operands, u32, load_u32_16, memarg)
+            }
+            Instruction::I64Load8S(memarg) => memory_load!(self, operands, i64, load_i64_8, memarg),
+            Instruction::I64Load8U(memarg) => memory_load!(self, operands, u64, load_u64_8, memarg),
+            Instruction::I64Load16S(memarg) => {
+                memory_load!(self, operands, i64, load_i64_16, memarg)
+            }
+            Instruction::I64Load16U(memarg) => {
+                memory_load!(self, operands, u64, load_u64_16, memarg)
+            }
+            Instruction::I64Load32S(memarg) => {
+                memory_load!(self, operands, i64, load_i64_32, memarg)
+            }
+            Instruction::I64Load32U(memarg) => {
+                memory_load!(self, operands, u64, load_u64_32, memarg)
+            }
+            Instruction::I32Store(memarg) => memory_store!(self, operands, i32, store_i32, memarg),
+            Instruction::I64Store(memarg) => memory_store!(self, operands, i64, store_i64, memarg),
+            Instruction::F32Store(memarg) => memory_store!(self, operands, f32, store_f32, memarg),
+            Instruction::F64Store(memarg) => memory_store!(self, operands, f64, store_f64, memarg),
+            Instruction::I32Store8(memarg) => {
+                memory_store!(self, operands, i32, store_i32_8, memarg)
+            }
+            Instruction::I32Store16(memarg) => {
+                memory_store!(self, operands, i32, store_i32_16, memarg)
+            }
+            Instruction::I64Store8(memarg) => {
+                memory_store!(self, operands, i64, store_i64_8, memarg)
+            }
+            Instruction::I64Store16(memarg) => {
+                memory_store!(self, operands, i64, store_i64_16, memarg)
+            }
+            Instruction::I64Store32(memarg) => {
+                memory_store!(self, operands, i64, store_i64_32, memarg)
+            }
+            Instruction::MemorySize => operands.push_u32(self.memory.borrow().size()),
             Instruction::MemoryGrow => {
-                let delta = self.stack.borrow_mut().pop_u32();
-                self.stack
-                    .borrow_mut()
-                    .push_i32(self.memory.borrow_mut().grow(delta));
+                let delta = operands.pop_u32();
+                operands.push_i32(self.memory.borrow_mut().grow(delta));
             }
             Instruction::MemoryInit(data_idx) => {
-                let data = &self.module.datas()[data_idx.0 as usize];
-                assert!(
-                    matches!(data.mode, DataMode::Passive),
-                    "Can only init passive data"
-                );
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let src = self.stack.borrow_mut().pop_u32() as usize;
-                let dst = self.stack.borrow_mut().pop_u32();
-                let data = &data.init[src..src + len];
-                self.memory.borrow_mut().fill_data(dst, data);
-            }
-            Instruction::DataDrop(_) => {}
+                let idx = data_idx.0 as usize;
+                // A dropped segment behaves like an empty one
+                let data = if self.dropped_datas[idx].get() {
+                    &[][..]
+                } else {
+                    &self.module.datas()[idx].init[..]
+                };
+                let len = operands.pop_u32() as usize;
+                let src = operands.pop_u32() as usize;
+                let dst = operands.pop_u32();
+                let data = src
+                    .checked_add(len)
+                    .and_then(|end| data.get(src..end))
+                    .ok_or(Trap::MemoryOutOfBounds)?;
+                self.memory.borrow_mut().fill_data(dst, data)?;
+            }
+            Instruction::DataDrop(data_idx) => self.dropped_datas[data_idx.0 as usize].set(true),
             Instruction::Memcpy => {
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let src = self.stack.borrow_mut().pop_u32() as usize;
-                let dst = self.stack.borrow_mut().pop_u32() as usize;
-                self.memory.borrow_mut().cpy(src, dst, len);
+                let len = operands.pop_u32() as usize;
+                let src = operands.pop_u32() as usize;
+                let dst = operands.pop_u32() as usize;
+                self.memory.borrow_mut().cpy(src, dst, len)?;
             }
             Instruction::Memfill => {
-                let len = self.stack.borrow_mut().pop_u32() as usize;
-                let value = self.stack.borrow_mut().pop_u32() as u8;
-                let addr = self.stack.borrow_mut().pop_u32() as usize;
-                self.memory.borrow_mut().fill_value(len, addr, value);
-            }
-
-            Instruction::I32Const(value) => self.stack.borrow_mut().push_i32(*value),
-            Instruction::I64Const(value) => self.stack.borrow_mut().push_i64(*value),
-            Instruction::F32Const(value) => self.stack.borrow_mut().push_f32(*value),
-            Instruction::F64Const(value) => self.stack.borrow_mut().push_f64(*value),
-
-            Instruction::I32Eqz => op!(self, { a: i32, }, bool => a == 0),
-            Instruction::I32Eq => op!(self, { b: i32, a: i32 }, bool => a == b),
-            Instruction::I32Ne => op!(self, { b: i32, a: i32 }, bool => a != b),
-            Instruction::I32LtS => op!(self, { b: i32, a: i32 }, bool => a < b),
-            Instruction::I32LtU => op!(self, { b: u32, a: u32 }, bool => a < b),
-            Instruction::I32GtS => op!(self, { b: i32, a: i32 }, bool => a > b),
-            Instruction::I32GtU => op!(self, { b: u32, a: u32 }, bool => a > b),
-            Instruction::I32LeS => op!(self, { b: i32, a: i32 }, bool => a <= b),
-            Instruction::I32LeU => op!(self, { b: u32, a: u32 }, bool => a <= b),
-            Instruction::I32GeS => op!(self, { b: i32, a: i32 }, bool => a >= b),
-            Instruction::I32GeU => op!(self, { b: u32, a: u32 }, bool => a >= b),
-            Instruction::I64Eqz => op!(self, { a: i64 }, bool => a == 0),
-            Instruction::I64Eq => op!(self, { b:i64, a: i64 }, bool => a == b),
-            Instruction::I64Ne => op!(self, { b: i64, a: i64 }, bool => a != b),
-            Instruction::I64LtS => op!(self, { b: i64, a: i64 }, bool => a < b),
-            Instruction::I64LtU => op!(self, { b: u64, a: u64 }, bool => a < b),
-            Instruction::I64GtS => op!(self, { b: i64, a: i64 }, bool => a > b),
-            Instruction::I64GtU => op!(self, { b: u64, a: u64 }, bool => a > b),
-            Instruction::I64LeS => op!(self, { b: i64, a: i64 }, bool => a <= b),
-            Instruction::I64LeU => op!(self, { b: u64, a: u64 }, bool => a <= b),
-            Instruction::I64GeS => op!(self, { b: i64, a: i64 }, bool => a >= b),
-            Instruction::I64GeU => op!(self, { b: u64, a: u64 }, bool => a >= b),
-            Instruction::F32Eq => op!(self, { b: f32, a: f32 }, bool => a == b),
-            Instruction::F32Ne => op!(self, { b: f32, a: f32 }, bool => a != b),
-            Instruction::F32Lt => op!(self, { b: f32, a: f32 }, bool => a < b),
-            Instruction::F32Gt => op!(self, { b: f32, a: f32 }, bool => a > b),
-            Instruction::F32Le => op!(self, { b: f32, a: f32 }, bool => a <= b),
-            Instruction::F32Ge => op!(self, { b: f32, a: f32 }, bool => a >= b),
-
-            Instruction::F64Eq => op!(self, { b: f64, a: f64 }, bool => a == b),
-            Instruction::F64Ne => op!(self, { b: f64, a: f64 }, bool => a != b),
-            Instruction::F64Lt => op!(self, { b: f64, a: f64 }, bool => a < b),
-            Instruction::F64Gt => op!(self, { b: f64, a: f64 }, bool => a > b),
-            Instruction::F64Le => op!(self, { b: f64, a: f64 }, bool => a <= b),
-            Instruction::F64Ge => op!(self, { b: f64, a: f64 }, bool => a >= b),
-
-            Instruction::I32Clz => op!(self, { a: u32 }, u32 => a.leading_zeros()),
-            Instruction::I32Ctz => op!(self, { a: u32 }, u32 => a.trailing_zeros()),
-            Instruction::I32Popcnt => op!(self, { a: u32 }, u32 => a.count_ones()),
-
-            Instruction::I32Add => op!(self, { b: i32, a: i32 }, i32 => a.wrapping_add(b)),
-            Instruction::I32Sub => op!(self, { b: i32, a: i32 }, i32 => a.wrapping_sub(b)),
-            Instruction::I32Mul => op!(self, { b: i32, a: i32 }, i32 => a.wrapping_mul(b)),
-            Instruction::I32DivS => op!(self, { b: i32, a: i32 }, i32 => a / b),
-            Instruction::I32DivU => op!(self, { b: u32, a: u32 }, u32 => a / b),
-            Instruction::I32RemS => op!(self, { b: i32, a: i32 }, i32 => a % b),
-            Instruction::I32RemU => op!(self, { b: u32, a: u32 }, u32 => a % b),
-            Instruction::I32And => op!(self, { b: i32, a: i32 }, i32 => a & b),
-            Instruction::I32Or => op!(self, { b: i32, a: i32 }, i32 => a | b),
-            Instruction::I32Xor => op!(self, { b: i32, a: i32 }, i32 => a ^ b),
-            Instruction::I32Shl => op!(self, { b: i32, a: i32 }, i32 => a << (b % 32)),
-            Instruction::I32ShrS => op!(self, { b: i32, a: i32 }, i32 => a >> (b % 32)),
-            Instruction::I32ShrU => op!(self, { b: u32, a: u32 }, u32 => a >> (b % 32)),
-            Instruction::I32Rotr => op!(self, { b: u32, a: u32 }, u32 => a.rotate_right(b)),
-            Instruction::I32Rotl => op!(self, { b: u32, a: u32 }, u32 => a.rotate_left(b)),
-
-            Instruction::I64Clz => op!(self, { a: u64 }, u64 => a.leading_zeros() as u64),
-            Instruction::I64Ctz => op!(self, { a: u64 }, u64 => a.trailing_zeros() as u64),
-            Instruction::I64Popcnt => op!(self, { a: u64 }, u64 => a.count_ones() as u64),
-            Instruction::I64Add => op!(self, { b: i64, a: i64 }, i64 => a.wrapping_add(b)),
-            Instruction::I64Sub => op!(self, { b: i64, a: i64 }, i64 => a.wrapping_sub(b)),
-            Instruction::I64Mul => op!(self, { b: i64, a: i64 }, i64 => a.wrapping_mul(b)),
-            Instruction::I64DivS => op!(self, { b: i64, a: i64 }, i64 => a / b),
-            Instruction::I64DivU => op!(self, { b: u64, a: u64 }, u64 => a / b),
-            Instruction::I64RemS => op!(self, { b: i64, a: i64 }, i64 => a % b),
-            Instruction::I64RemU => op!(self, { b: u64, a: u64 }, u64 => a % b),
-            Instruction::I64And => op!(self, { b: i64, a: i64 }, i64 => a & b),
-            Instruction::I64Or => op!(self, { b: i64, a: i64 }, i64 => a | b),
-            Instruction::I64Xor => op!(self, { b: i64, a: i64 }, i64 => a ^ b),
-            Instruction::I64Shl => op!(self, { b: i64, a: i64 }, i64 => a << (b % 64)),
-            Instruction::I64ShrS => op!(self, { b: i64, a: i64 }, i64 => a >> (b % 64)),
-            Instruction::I64ShrU => op!(self, { b: u64, a: u64 }, u64 => a >> (b % 64)),
-            Instruction::I64Rotl => op!(self, { b: u64, a: u64 }, u64 => a.rotate_left(b as u32)),
-            Instruction::I64Rotr => op!(self, { b: u64, a: u64 }, u64 => a.rotate_right(b as u32)),
-
-            Instruction::F32Abs => op!(self, { a: f32 }, f32 => a.abs()),
-            Instruction::F32Neg => op!(self, { a: f32 }, f32 => -a),
-            Instruction::F32Ceil => op!(self, { a: f32 }, f32 => a.ceil()),
-            Instruction::F32Floor => op!(self, { a: f32 }, f32 => a.floor()),
-            Instruction::F32Trunc => op!(self, { a: f32 }, f32 => a.trunc()),
-            Instruction::F32Nearest => op!(self, { a: f32 }, f32 => a.round()),
-            Instruction::F32Sqrt => op!(self, { a: f32 }, f32 => a.sqrt()),
-            Instruction::F32Add => op!(self, { b: f32, a: f32 }, f32 => a + b),
-            Instruction::F32Sub => op!(self, { b: f32, a: f32 }, f32 => a - b),
-            Instruction::F32Mul => op!(self, { b: f32, a: f32 }, f32 => a * b),
-            Instruction::F32Div => op!(self, { b: f32, a: f32 }, f32 => a / b),
-            Instruction::F32Min => op!(self, { b: f32, a: f32 }, f32 => a.min(b)),
-            Instruction::F32Max => op!(self, { b: f32, a: f32 }, f32 => a.max(b)),
-            Instruction::F32Copysign => op!(self, { b: f32, a: f32 }, f32 => a .copysign(b)),
-            Instruction::F64Abs => op!(self, { a: f64 }, f64 => a.abs()),
-            Instruction::F64Neg => op!(self, { a: f64 }, f64 => -a),
-            Instruction::F64Ceil => op!(self, { a: f64 }, f64 => a.ceil()),
-            Instruction::F64Floor => op!(self, { a: f64 }, f64 => a.floor()),
-            Instruction::F64Trunc => op!(self, { a: f64 }, f64 => a.trunc()),
-            Instruction::F64Nearest => op!(self, { a: f64 }, f64 => a.round()),
-            Instruction::F64Sqrt => op!(self, { a: f64 }, f64 => a.sqrt()),
-            Instruction::F64Add => op!(self, { b: f64, a: f64 }, f64 => a + b),
-            Instruction::F64Sub => op!(self, { b: f64, a: f64 }, f64 => a - b),
-            Instruction::F64Mul => op!(self, { b: f64, a: f64 }, f64 => a * b),
-            Instruction::F64Div => op!(self, { b: f64, a: f64 }, f64 => a / b),
-            Instruction::F64Min => op!(self, { b: f64, a: f64 }, f64 => a.min(b)),
-            Instruction::F64Max => op!(self, { b: f64, a: f64 }, f64 => a.max(b)),
-            Instruction::F64Copysign => op!(self, { b: f64, a: f64 }, f64 => a .copysign(b)),
-            Instruction::I32WrapI64 => op!(self, { a: i64 }, i32 => a as i32),
-            Instruction::I32TruncF32S => op!(self, { a: f32 }, i32 => a as i32),
-            Instruction::I32TruncF32U => op!(self, { a: f32 }, u32 => a as u32),
-            Instruction::I32TruncF64S => op!(self, { a: f64 }, i32 => a as i32),
-            Instruction::I32TruncF64U => op!(self, { a: f64 }, u32 => a as u32),
-            Instruction::I64ExtendI32S => op!(self, { a: i32 }, i64 => a as i64),
-            Instruction::I64ExtendI32U => op!(self, { a: u32 }, u64 => a as u64),
-            Instruction::I64TruncF32S => op!(self, { a: f32 }, i64 => a as i64),
-            Instruction::I64TruncF32U => op!(self, { a: f32 }, u64 => a as u64),
-            Instruction::I64TruncF64S => op!(self, { a: f64 }, i64 => a as i64),
-            Instruction::I64TruncF64U => op!(self, { a: f64 }, u64 => a as u64),
-            Instruction::F32ConvertI32S => op!(self, { a: i32 }, f32 => a as f32),
-            Instruction::F32ConvertI32U => op!(self, { a: u32 }, f32 => a as f32),
-            Instruction::F32ConvertI64S => op!(self, { a: i64 }, f32 => a as f32),
-            Instruction::F32ConvertI64U => op!(self, { a: u64 }, f32 => a as f32),
-            Instruction::F32DemoteF64 => op!(self, { a: f64 }, f32 => a as f32),
-            Instruction::F64ConvertI32S => op!(self, { a: i32 }, f64 => a as f64),
-            Instruction::F64ConvertI32U => op!(self, { a: u32 }, f64 => a as f64),
-            Instruction::F64ConvertI64S => op!(self, { a: i64 }, f64 => a as f64),
-            Instruction::F64ConvertI64U => op!(self, { a: u64 }, f64 => a as f64),
-            Instruction::F64PromoteF32 => op!(self, { a: f32 }, f64 => a as f64),
+                let len = operands.pop_u32() as usize;
+                let value = operands.pop_u32() as u8;
+                let addr = operands.pop_u32() as usize;
+                self.memory.borrow_mut().fill_value(len, addr, value)?;
+            }
+
+            Instruction::I32Const(value) => operands.push_i32(*value),
+            Instruction::I64Const(value) => operands.push_i64(*value),
+            Instruction::F32Const(value) => operands.push_f32(*value),
+            Instruction::F64Const(value) => operands.push_f64(*value),
+
+            Instruction::I32Eqz => op!(self, operands, { a: i32, }, bool => a == 0),
+            Instruction::I32Eq => op!(self, operands, { b: i32, a: i32 }, bool => a == b),
+            Instruction::I32Ne => op!(self, operands, { b: i32, a: i32 }, bool => a != b),
+            Instruction::I32LtS => op!(self, operands, { b: i32, a: i32 }, bool => a < b),
+            Instruction::I32LtU => op!(self, operands, { b: u32, a: u32 }, bool => a < b),
+            Instruction::I32GtS => op!(self, operands, { b: i32, a: i32 }, bool => a > b),
+            Instruction::I32GtU => op!(self, operands, { b: u32, a: u32 }, bool => a > b),
+            Instruction::I32LeS => op!(self, operands, { b: i32, a: i32 }, bool => a <= b),
+            Instruction::I32LeU => op!(self, operands, { b: u32, a: u32 }, bool => a <= b),
+            Instruction::I32GeS => op!(self, operands, { b: i32, a: i32 }, bool => a >= b),
+            Instruction::I32GeU => op!(self, operands, { b: u32, a: u32 }, bool => a >= b),
+            Instruction::I64Eqz => op!(self, operands, { a: i64 }, bool => a == 0),
+            Instruction::I64Eq => op!(self, operands, { b:i64, a: i64 }, bool => a == b),
+            Instruction::I64Ne => op!(self, operands, { b: i64, a: i64 }, bool => a != b),
+            Instruction::I64LtS => op!(self, operands, { b: i64, a: i64 }, bool => a < b),
+            Instruction::I64LtU => op!(self, operands, { b: u64, a: u64 }, bool => a < b),
+            Instruction::I64GtS => op!(self, operands, { b: i64, a: i64 }, bool => a > b),
+            Instruction::I64GtU => op!(self, operands, { b: u64, a: u64 }, bool => a > b),
+            Instruction::I64LeS => op!(self, operands, { b: i64, a: i64 }, bool => a <= b),
+            Instruction::I64LeU => op!(self, operands, { b: u64, a: u64 }, bool => a <= b),
+            Instruction::I64GeS => op!(self, operands, { b: i64, a: i64 }, bool => a >= b),
+            Instruction::I64GeU => op!(self, operands, { b: u64, a: u64 }, bool => a >= b),
+            Instruction::F32Eq => op!(self, operands, { b: f32, a: f32 }, bool => a == b),
+            Instruction::F32Ne => op!(self, operands, { b: f32, a: f32 }, bool => a != b),
+            Instruction::F32Lt => op!(self, operands, { b: f32, a: f32 }, bool => a < b),
+            Instruction::F32Gt => op!(self, operands, { b: f32, a: f32 }, bool => a > b),
+            Instruction::F32Le => op!(self, operands, { b: f32, a: f32 }, bool => a <= b),
+            Instruction::F32Ge => op!(self, operands, { b: f32, a: f32 }, bool => a >= b),
+
+            Instruction::F64Eq => op!(self, operands, { b: f64, a: f64 }, bool => a == b),
+            Instruction::F64Ne => op!(self, operands, { b: f64, a: f64 }, bool => a != b),
+            Instruction::F64Lt => op!(self, operands, { b: f64, a: f64 }, bool => a < b),
+            Instruction::F64Gt => op!(self, operands, { b: f64, a: f64 }, bool => a > b),
+            Instruction::F64Le => op!(self, operands, { b: f64, a: f64 }, bool => a <= b),
+            Instruction::F64Ge => op!(self, operands, { b: f64, a: f64 }, bool => a >= b),
+
+            Instruction::I32Clz => op!(self, operands, { a: u32 }, u32 => a.leading_zeros()),
+            Instruction::I32Ctz => op!(self, operands, { a: u32 }, u32 => a.trailing_zeros()),
+            Instruction::I32Popcnt => op!(self, operands, { a: u32 }, u32 => a.count_ones()),
+
+            Instruction::I32Add => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => a.wrapping_add(b))
+            }
+            Instruction::I32Sub => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => a.wrapping_sub(b))
+            }
+            Instruction::I32Mul => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => a.wrapping_mul(b))
+            }
+            Instruction::I32DivS => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => div_s_i32(a, b)?)
+            }
+            Instruction::I32DivU => {
+                op!(self, operands, { b: u32, a: u32 }, u32 => div_u_u32(a, b)?)
+            }
+            Instruction::I32RemS => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => rem_s_i32(a, b)?)
+            }
+            Instruction::I32RemU => {
+                op!(self, operands, { b: u32, a: u32 }, u32 => rem_u_u32(a, b)?)
+            }
+            Instruction::I32And => op!(self, operands, { b: i32, a: i32 }, i32 => a & b),
+            Instruction::I32Or => op!(self, operands, { b: i32, a: i32 }, i32 => a | b),
+            Instruction::I32Xor => op!(self, operands, { b: i32, a: i32 }, i32 => a ^ b),
+            Instruction::I32Shl => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => a.wrapping_shl(b as u32))
+            }
+            Instruction::I32ShrS => {
+                op!(self, operands, { b: i32, a: i32 }, i32 => a.wrapping_shr(b as u32))
+            }
+            Instruction::I32ShrU => {
+                op!(self, operands, { b: u32, a: u32 }, u32 => a.wrapping_shr(b))
+            }
+            Instruction::I32Rotr => {
+                op!(self, operands, { b: u32, a: u32 }, u32 => a.rotate_right(b))
+            }
+            Instruction::I32Rotl => {
+                op!(self, operands, { b: u32, a: u32 }, u32 => a.rotate_left(b))
+            }
+
+            Instruction::I64Clz => op!(self, operands, { a: u64 }, u64 => a.leading_zeros() as u64),
+            Instruction::I64Ctz => {
+                op!(self, operands, { a: u64 }, u64 => a.trailing_zeros() as u64)
+            }
+            Instruction::I64Popcnt => op!(self, operands, { a: u64 }, u64 => a.count_ones() as u64),
+            Instruction::I64Add => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => a.wrapping_add(b))
+            }
+            Instruction::I64Sub => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => a.wrapping_sub(b))
+            }
+            Instruction::I64Mul => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => a.wrapping_mul(b))
+            }
+            Instruction::I64DivS => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => div_s_i64(a, b)?)
+            }
+            Instruction::I64DivU => {
+                op!(self, operands, { b: u64, a: u64 }, u64 => div_u_u64(a, b)?)
+            }
+            Instruction::I64RemS => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => rem_s_i64(a, b)?)
+            }
+            Instruction::I64RemU => {
+                op!(self, operands, { b: u64, a: u64 }, u64 => rem_u_u64(a, b)?)
+            }
+            Instruction::I64And => op!(self, operands, { b: i64, a: i64 }, i64 => a & b),
+            Instruction::I64Or => op!(self, operands, { b: i64, a: i64 }, i64 => a | b),
+            Instruction::I64Xor => op!(self, operands, { b: i64, a: i64 }, i64 => a ^ b),
+            Instruction::I64Shl => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => a.wrapping_shl(b as u32))
+            }
+            Instruction::I64ShrS => {
+                op!(self, operands, { b: i64, a: i64 }, i64 => a.wrapping_shr(b as u32))
+            }
+            Instruction::I64ShrU => {
+                op!(self, operands, { b: u64, a: u64 }, u64 => a.wrapping_shr(b as u32))
+            }
+            Instruction::I64Rotl => {
+                op!(self, operands, { b: u64, a: u64 }, u64 => a.rotate_left(b as u32))
+            }
+            Instruction::I64Rotr => {
+                op!(self, operands, { b: u64, a: u64 }, u64 => a.rotate_right(b as u32))
+            }
+
+            Instruction::F32Abs => op!(self, operands, { a: f32 }, f32 => a.abs()),
+            Instruction::F32Neg => op!(self, operands, { a: f32 }, f32 => -a),
+            Instruction::F32Ceil => {
+                op!(self, operands, { a: f32 }, f32 => self.canonicalize_f32(a.ceil()))
+            }
+            Instruction::F32Floor => {
+                op!(self, operands, { a: f32 }, f32 => self.canonicalize_f32(a.floor()))
+            }
+            Instruction::F32Trunc => {
+                op!(self, operands, { a: f32 }, f32 => self.canonicalize_f32(a.trunc()))
+            }
+            Instruction::F32Nearest => {
+                op!(self, operands, { a: f32 }, f32 => self.canonicalize_f32(a.round_ties_even()))
+            }
+            Instruction::F32Sqrt => {
+                op!(self, operands, { a: f32 }, f32 => self.canonicalize_f32(a.sqrt()))
+            }
+            Instruction::F32Add => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(a + b))
+            }
+            Instruction::F32Sub => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(a - b))
+            }
+            Instruction::F32Mul => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(a * b))
+            }
+            Instruction::F32Div => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(a / b))
+            }
+            Instruction::F32Min => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(min_f32(a, b)))
+            }
+            Instruction::F32Max => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => self.canonicalize_f32(max_f32(a, b)))
+            }
+            Instruction::F32Copysign => {
+                op!(self, operands, { b: f32, a: f32 }, f32 => a.copysign(b))
+            }
+            Instruction::F64Abs => op!(self, operands, { a: f64 }, f64 => a.abs()),
+            Instruction::F64Neg => op!(self, operands, { a: f64 }, f64 => -a),
+            Instruction::F64Ceil => {
+                op!(self, operands, { a: f64 }, f64 => self.canonicalize_f64(a.ceil()))
+            }
+            Instruction::F64Floor => {
+                op!(self, operands, { a: f64 }, f64 => self.canonicalize_f64(a.floor()))
+            }
+            Instruction::F64Trunc => {
+                op!(self, operands, { a: f64 }, f64 => self.canonicalize_f64(a.trunc()))
+            }
+            Instruction::F64Nearest => {
+                op!(self, operands, { a: f64 }, f64 => self.canonicalize_f64(a.round_ties_even()))
+            }
+            Instruction::F64Sqrt => {
+                op!(self, operands, { a: f64 }, f64 => self.canonicalize_f64(a.sqrt()))
+            }
+            Instruction::F64Add => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(a + b))
+            }
+            Instruction::F64Sub => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(a - b))
+            }
+            Instruction::F64Mul => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(a * b))
+            }
+            Instruction::F64Div => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(a / b))
+            }
+            Instruction::F64Min => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(min_f64(a, b)))
+            }
+            Instruction::F64Max => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => self.canonicalize_f64(max_f64(a, b)))
+            }
+            Instruction::F64Copysign => {
+                op!(self, operands, { b: f64, a: f64 }, f64 => a.copysign(b))
+            }
+            Instruction::I32WrapI64 => op!(self, operands, { a: i64 }, i32 => a as i32),
+            Instruction::I32TruncF32S => op!(self, operands, { a: f32 }, i32 => trunc_f32_i32(a)?),
+            Instruction::I32TruncF32U => op!(self, operands, { a: f32 }, u32 => trunc_f32_u32(a)?),
+            Instruction::I32TruncF64S => op!(self, operands, { a: f64 }, i32 => trunc_f64_i32(a)?),
+            Instruction::I32TruncF64U => op!(self, operands, { a: f64 }, u32 => trunc_f64_u32(a)?),
+            Instruction::I64ExtendI32S => op!(self, operands, { a: i32 }, i64 => a as i64),
+            Instruction::I64ExtendI32U => op!(self, operands, { a: u32 }, u64 => a as u64),
+            Instruction::I64TruncF32S => op!(self, operands, { a: f32 }, i64 => trunc_f32_i64(a)?),
+            Instruction::I64TruncF32U => op!(self, operands, { a: f32 }, u64 => trunc_f32_u64(a)?),
+            Instruction::I64TruncF64S => op!(self, operands, { a: f64 }, i64 => trunc_f64_i64(a)?),
+            Instruction::I64TruncF64U => op!(self, operands, { a: f64 }, u64 => trunc_f64_u64(a)?),
+            Instruction::F32ConvertI32S => op!(self, operands, { a: i32 }, f32 => a as f32),
+            Instruction::F32ConvertI32U => op!(self, operands, { a: u32 }, f32 => a as f32),
+            Instruction::F32ConvertI64S => op!(self, operands, { a: i64 }, f32 => a as f32),
+            Instruction::F32ConvertI64U => op!(self, operands, { a: u64 }, f32 => a as f32),
+            Instruction::F32DemoteF64 => {
+                op!(self, operands, { a: f64 }, f32 => self.canonicalize_f32(a as f32))
+            }
+            Instruction::F64ConvertI32S => op!(self, operands, { a: i32 }, f64 => a as f64),
+            Instruction::F64ConvertI32U => op!(self, operands, { a: u32 }, f64 => a as f64),
+            Instruction::F64ConvertI64S => op!(self, operands, { a: i64 }, f64 => a as f64),
+            Instruction::F64ConvertI64U => op!(self, operands, { a: u64 }, f64 => a as f64),
+            Instruction::F64PromoteF32 => {
+                op!(self, operands, { a: f32 }, f64 => self.canonicalize_f64(a as f64))
+            }
             Instruction::I32ReinterpretF32 => {
-                op!(self, { a: f32 }, i32 => i32::from_le_bytes(a.to_le_bytes()))
+                op!(self, operands, { a: f32 }, i32 => i32::from_le_bytes(a.to_le_bytes()))
             }
             Instruction::I64ReinterpretF64 => {
-                op!(self, { a: f64 }, i64 => i64::from_le_bytes(a.to_le_bytes()))
+                op!(self, operands, { a: f64 }, i64 => i64::from_le_bytes(a.to_le_bytes()))
             }
             Instruction::F32ReinterpretI32 => {
-                op!(self, { a: i32 }, f32 => f32::from_le_bytes(a.to_le_bytes()))
+                op!(self, operands, { a: i32 }, f32 => f32::from_le_bytes(a.to_le_bytes()))
             }
             Instruction::F64ReinterpretI64 => {
-                op!(self, { a: i64 }, f64 => f64::from_le_bytes(a.to_le_bytes()))
+                op!(self, operands, { a: i64 }, f64 => f64::from_le_bytes(a.to_le_bytes()))
             }
-            Instruction::I32Extend8S => op!(self, { a: i32 }, i32 => extend_i32(a, 8)),
-            Instruction::I32Extend16S => op!(self, { a: i32 }, i32 => extend_i32(a, 16)),
-            Instruction::I64Extend8S => op!(self, { a: i64 }, i64 => extend_i64(a, 8)),
-            Instruction::I64Extend16S => op!(self, { a: i64 }, i64 => extend_i64(a, 16)),
-            Instruction::I64Extend32S => op!(self, { a: i64 }, i64 => extend_i64(a, 32)),
+            Instruction::I32Extend8S => op!(self, operands, { a: i32 }, i32 => extend_i32(a, 8)),
+            Instruction::I32Extend16S => op!(self, operands, { a: i32 }, i32 => extend_i32(a, 16)),
+            Instruction::I64Extend8S => op!(self, operands, { a: i64 }, i64 => extend_i64(a, 8)),
+            Instruction::I64Extend16S => op!(self, operands, { a: i64 }, i64 => extend_i64(a, 16)),
+            Instruction::I64Extend32S => op!(self, operands, { a: i64 }, i64 => extend_i64(a, 32)),
+            // `as` saturates and turns NaN into 0, just like the `trunc_sat` instructions
+            Instruction::I32TruncSatF32S => op!(self, operands, { a: f32 }, i32 => a as i32),
+            Instruction::I32TruncSatF32U => op!(self, operands, { a: f32 }, u32 => a as u32),
+            Instruction::I32TruncSatF64S => op!(self, operands, { a: f64 }, i32 => a as i32),
+            Instruction::I32TruncSatF64U => op!(self, operands, { a: f64 }, u32 => a as u32),
+            Instruction::I64TruncSatF32S => op!(self, operands, { a: f32 }, i64 => a as i64),
+            Instruction::I64TruncSatF32U => op!(self, operands, { a: f32 }, u64 => a as u64),
+            Instruction::I64TruncSatF64S => op!(self, operands, { a: f64 }, i64 => a as i64),
+            Instruction::I64TruncSatF64U => op!(self, operands, { a: f64 }, u64 => a as u64),
         }
+        Ok(())
     }
 }
 
@@ -787,3 +1435,96 @@ macro_rules! extend_int {
 
 extend_int!(i32);
 extend_int!(i64);
+
+macro_rules! min_max_float {
+    ($float:ty) => {
+        paste! {
+            /// Unlike `f32::min` a NaN operand makes the result NaN, and -0 is less than +0
+            fn [<min_ $float>](a: $float, b: $float) -> $float {
+                if a.is_nan() || b.is_nan() {
+                    return a + b;
+                }
+                if a == b {
+                    // Only differ in the sign if they are zeros, negative wins
+                    return <$float>::from_bits(a.to_bits() | b.to_bits());
+                }
+                a.min(b)
+            }
+
+            fn [<max_ $float>](a: $float, b: $float) -> $float {
+                if a.is_nan() || b.is_nan() {
+                    return a + b;
+                }
+                if a == b {
+                    return <$float>::from_bits(a.to_bits() & b.to_bits());
+                }
+                a.max(b)
+            }
+        }
+    };
+}
+
+min_max_float!(f32);
+min_max_float!(f64);
+
+macro_rules! div_rem_int {
+    ($signed:ty, $unsigned:ty) => {
+        paste! {
+            fn [<div_s_ $signed>](a: $signed, b: $signed) -> Result<$signed, Trap> {
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero);
+                }
+                a.checked_div(b).ok_or(Trap::IntegerOverflow)
+            }
+
+            /// The remainder of `MIN / -1` is 0, only dividing it overflows
+            fn [<rem_s_ $signed>](a: $signed, b: $signed) -> Result<$signed, Trap> {
+                if b == 0 {
+                    return Err(Trap::IntegerDivideByZero);
+                }
+                Ok(a.wrapping_rem(b))
+            }
+
+            fn [<div_u_ $unsigned>](a: $unsigned, b: $unsigned) -> Result<$unsigned, Trap> {
+                a.checked_div(b).ok_or(Trap::IntegerDivideByZero)
+            }
+
+            fn [<rem_u_ $unsigned>](a: $unsigned, b: $unsigned) -> Result<$unsigned, Trap> {
+                a.checked_rem(b).ok_or(Trap::IntegerDivideByZero)
+            }
+        }
+    };
+}
+
+div_rem_int!(i32, u32);
+div_rem_int!(i64, u64);
+
+macro_rules! trunc_float {
+    ($float:ty, $int:ty) => {
+        paste! {
+            fn [<trunc_ $float _ $int>](value: $float) -> Result<$int, Trap> {
+                if value.is_nan() {
+                    return Err(Trap::InvalidConversion);
+                }
+                let value = value.trunc();
+                // MAX + 1 is a power of two, so the exclusive upper bound is exact even where
+                // `MAX as float` already rounded up to it
+                let lower = <$int>::MIN as $float;
+                let upper = <$int>::MAX as $float + 1.0;
+                if value < lower || value >= upper {
+                    return Err(Trap::IntegerOverflow);
+                }
+                Ok(value as $int)
+            }
+        }
+    };
+}
+
+trunc_float!(f32, i32);
+trunc_float!(f32, u32);
+trunc_float!(f32, i64);
+trunc_float!(f32, u64);
+trunc_float!(f64, i32);
+trunc_float!(f64, u32);
+trunc_float!(f64, i64);
+trunc_float!(f64, u64);
@@ -0,0 +1,457 @@
+use crate::{
+    module::{functions::LocalFunction, Module},
+    types::{BlockIdx, Expr, Instruction, ValueType},
+};
+
+use super::value::Value;
+
+/// What a branch does to the stack: the top `keep` values are moved down over the `drop`
+/// values below them, which belong to the blocks that are left
+#[derive(Debug, Clone, Copy)]
+pub struct DropKeep {
+    pub drop: usize,
+    pub keep: usize,
+}
+
+/// Where a branch continues and how it cleans up the stack on the way
+#[derive(Debug, Clone, Copy)]
+pub struct Label {
+    pub target: usize,
+    pub drop_keep: DropKeep,
+}
+
+/// An instruction of a function body lowered into a flat sequence. Blocks don't exist
+/// anymore, branches jump straight to where their label continues
+#[derive(Debug)]
+pub enum Op<'b> {
+    /// Runs an instruction that doesn't change where execution continues
+    Execute(&'b Instruction),
+    /// Runs `call` or `call_indirect`, which may switch to another function
+    Call(&'b Instruction),
+    /// Continues at the target, placed at the end of the first arm of an `if` with an `else`
+    Jump(usize),
+    Br {
+        label: Label,
+        instruction: &'b Instruction,
+    },
+    BrIf {
+        label: Label,
+        instruction: &'b Instruction,
+    },
+    /// Skips the first arm of an `if` when the condition is zero
+    BrUnless {
+        target: usize,
+        instruction: &'b Instruction,
+    },
+    BrTable {
+        labels: Box<[Label]>,
+        default: Label,
+        instruction: &'b Instruction,
+    },
+    /// The end of the function, its results are on top of the stack
+    Return,
+}
+
+impl<'b> Op<'b> {
+    /// The instruction fuel is charged for, jumps that only replace the end of a block are
+    /// free like the blocks themselves
+    pub fn instruction(&self) -> Option<&'b Instruction> {
+        match self {
+            Op::Execute(instruction)
+            | Op::Call(instruction)
+            | Op::Br { instruction, .. }
+            | Op::BrIf { instruction, .. }
+            | Op::BrUnless { instruction, .. }
+            | Op::BrTable { instruction, .. } => Some(instruction),
+            Op::Jump(_) | Op::Return => None,
+        }
+    }
+}
+
+/// The body of a local function, ready to be executed
+#[derive(Debug)]
+pub struct Code<'b> {
+    pub ops: Vec<Op<'b>>,
+    pub returns: &'b [ValueType],
+    pub amount_of_params: usize,
+    /// Initial values of the locals that aren't params
+    pub locals: Vec<u64>,
+}
+
+impl<'b> Code<'b> {
+    /// Lowers the body of a validated function
+    pub fn compile(module: &'b Module<'b>, function: &'b LocalFunction) -> Code<'b> {
+        let expr = &function.code.instructions;
+        let returns = &function.signature.returns[..];
+        let function_label = expr.amount_of_blocks();
+        let mut compiler = Compiler {
+            module,
+            expr,
+            ops: vec![],
+            labels: vec![None; expr.amount_of_blocks()],
+            targets: vec![usize::MAX; function_label + 1],
+            function_label: LabelInfo {
+                id: function_label,
+                base: 0,
+                arity: returns.len(),
+            },
+            height: 0,
+        };
+        compiler.compile_instructions(expr.instructions());
+        compiler.targets[function_label] = compiler.ops.len();
+        compiler.ops.push(Op::Return);
+
+        Code {
+            ops: compiler.resolve_targets(),
+            returns,
+            amount_of_params: function.signature.params.len(),
+            locals: function
+                .code
+                .locals
+                .0
+                .iter()
+                .map(|value_type| Value::default(*value_type).to_bits())
+                .collect(),
+        }
+    }
+}
+
+/// A label while the body is compiled. Its id stands in for the target until the whole body
+/// is compiled, forward branches don't know it before
+#[derive(Debug, Clone, Copy)]
+struct LabelInfo {
+    /// The index of the block, one past the last block for the function body itself
+    id: usize,
+    /// Height of the stack below the params of the block
+    base: usize,
+    /// Amount of values a branch to the label takes along
+    arity: usize,
+}
+
+struct Compiler<'b> {
+    module: &'b Module<'b>,
+    expr: &'b Expr,
+    ops: Vec<Op<'b>>,
+    /// Labels of the blocks entered so far, by block index
+    labels: Vec<Option<LabelInfo>>,
+    /// Where execution continues for each label id
+    targets: Vec<usize>,
+    function_label: LabelInfo,
+    /// Values on the stack above the locals of the function, known statically for valid code
+    height: usize,
+}
+
+impl<'b> Compiler<'b> {
+    /// Instructions after one that never falls through are dead and left out
+    fn compile_instructions(&mut self, instructions: &'b [Instruction]) {
+        for instruction in instructions {
+            if !self.compile_instruction(instruction) {
+                return;
+            }
+        }
+    }
+
+    /// Returns whether execution can continue after the instruction
+    fn compile_instruction(&mut self, instruction: &'b Instruction) -> bool {
+        match instruction {
+            Instruction::Block(block) | Instruction::Loop(block) => {
+                let is_loop = matches!(instruction, Instruction::Loop(_));
+                let (params, results) = self.block_signature(*block);
+                let base = self.height - params;
+                self.labels[block.0] = Some(LabelInfo {
+                    id: block.0,
+                    base,
+                    arity: if is_loop { params } else { results },
+                });
+
+                if is_loop {
+                    self.targets[block.0] = self.ops.len();
+                }
+                self.compile_instructions(self.expr.block_instructions(*block));
+                if !is_loop {
+                    self.targets[block.0] = self.ops.len();
+                }
+                self.height = base + results;
+            }
+            Instruction::If { if_expr, else_expr } => {
+                self.height -= 1;
+                let (params, results) = self.block_signature(*if_expr);
+                let base = self.height - params;
+                // Both arms end the `if`, but the id of the second arm is where it starts
+                let label = LabelInfo {
+                    id: if_expr.0,
+                    base,
+                    arity: results,
+                };
+                self.labels[if_expr.0] = Some(label);
+                self.labels[else_expr.0] = Some(label);
+
+                self.ops.push(Op::BrUnless {
+                    target: else_expr.0,
+                    instruction,
+                });
+                self.compile_instructions(self.expr.block_instructions(*if_expr));
+
+                let else_instructions = self.expr.block_instructions(*else_expr);
+                if !else_instructions.is_empty() {
+                    self.ops.push(Op::Jump(if_expr.0));
+                    self.targets[else_expr.0] = self.ops.len();
+                    self.height = base + params;
+                    self.compile_instructions(else_instructions);
+                } else {
+                    self.targets[else_expr.0] = self.ops.len();
+                }
+                self.targets[if_expr.0] = self.ops.len();
+                self.height = base + results;
+            }
+            Instruction::Break(block) => {
+                let label = self.label(self.block_label(*block));
+                self.ops.push(Op::Br { label, instruction });
+                return false;
+            }
+            Instruction::BreakIf(block) => {
+                self.height -= 1;
+                let label = self.label(self.block_label(*block));
+                self.ops.push(Op::BrIf { label, instruction });
+            }
+            Instruction::BreakTable { labels, default } => {
+                self.height -= 1;
+                let labels = labels
+                    .iter()
+                    .map(|block| self.label(self.block_label(*block)))
+                    .collect();
+                let default = self.label(self.block_label(*default));
+                self.ops.push(Op::BrTable {
+                    labels,
+                    default,
+                    instruction,
+                });
+                return false;
+            }
+            Instruction::Return => {
+                let label = self.label(self.function_label);
+                self.ops.push(Op::Br { label, instruction });
+                return false;
+            }
+            Instruction::Unreachable => {
+                self.ops.push(Op::Execute(instruction));
+                return false;
+            }
+            Instruction::Call(_) | Instruction::CallIndirect(..) => {
+                self.apply_stack_effect(instruction);
+                self.ops.push(Op::Call(instruction));
+            }
+            _ => {
+                self.apply_stack_effect(instruction);
+                self.ops.push(Op::Execute(instruction));
+            }
+        }
+        true
+    }
+
+    fn block_label(&self, block: BlockIdx) -> LabelInfo {
+        if block == BlockIdx::FUNCTION {
+            return self.function_label;
+        }
+        self.labels[block.0].expect("Branches only target blocks they are in")
+    }
+
+    /// A branch from the current height to the label
+    fn label(&self, label: LabelInfo) -> Label {
+        Label {
+            target: label.id,
+            drop_keep: DropKeep {
+                drop: self.height - label.base - label.arity,
+                keep: label.arity,
+            },
+        }
+    }
+
+    /// Amount of params and results of the block
+    fn block_signature(&self, block: BlockIdx) -> (usize, usize) {
+        let block_type = self.expr.get_block_type(block);
+        let (params, results) = self
+            .module
+            .function_types()
+            .block_signature(&block_type)
+            .expect("Block types were validated");
+        (params.len(), results.len())
+    }
+
+    fn apply_stack_effect(&mut self, instruction: &Instruction) {
+        let (pops, pushes) = stack_effect(self.module, instruction);
+        self.height = self.height - pops + pushes;
+    }
+
+    /// Replaces the label ids of the branches with the positions they continue at
+    fn resolve_targets(self) -> Vec<Op<'b>> {
+        let targets = self.targets;
+        let resolve = |label: &mut Label| label.target = targets[label.target];
+
+        let mut ops = self.ops;
+        for op in &mut ops {
+            match op {
+                Op::Jump(target) | Op::BrUnless { target, .. } => *target = targets[*target],
+                Op::Br { label, .. } | Op::BrIf { label, .. } => resolve(label),
+                Op::BrTable {
+                    labels, default, ..
+                } => {
+                    labels.iter_mut().for_each(&resolve);
+                    resolve(default);
+                }
+                Op::Execute(_) | Op::Call(_) | Op::Return => {}
+            }
+        }
+        ops
+    }
+}
+
+/// Amount of values an instruction that doesn't branch pops and pushes
+pub fn stack_effect(module: &Module<'_>, instruction: &Instruction) -> (usize, usize) {
+    use Instruction::*;
+
+    match instruction {
+        Call(func_idx) => {
+            let signature = module
+                .get_function(*func_idx)
+                .expect("Function indices were validated")
+                .signature();
+            (signature.params.len(), signature.returns.len())
+        }
+        CallIndirect(type_idx, _) => {
+            let signature = module
+                .function_signature(*type_idx)
+                .expect("Type indices were validated");
+            (signature.params.len() + 1, signature.returns.len())
+        }
+
+        Nop | DataDrop(_) | ElementDrop(_) => (0, 0),
+        Drop | LocalSet(_) | GlobalSet(_) => (1, 0),
+        Select | SelectTyped(_) => (3, 1),
+        LocalGet(_) | GlobalGet(_) | MemorySize | TableSize(_) => (0, 1),
+        I32Const(_) | I64Const(_) | F32Const(_) | F64Const(_) => (0, 1),
+        PushNullRef(_) | PushFuncRef(_) => (0, 1),
+        TableSet(_) => (2, 0),
+        TableGrow(_) => (2, 1),
+        MemoryInit(_) | Memcpy | Memfill => (3, 0),
+        TableInit(..) | TableCopy(..) | TableFill(_) => (3, 0),
+
+        I32Store(_) | I64Store(_) | F32Store(_) | F64Store(_) | I32Store8(_) | I32Store16(_)
+        | I64Store8(_) | I64Store16(_) | I64Store32(_) => (2, 0),
+
+        I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU | I32GeS | I32GeU
+        | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU | I64LeS | I64LeU | I64GeS | I64GeU
+        | F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt | F64Le
+        | F64Ge => (2, 1),
+
+        I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU | I32And | I32Or
+        | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Add | I64Sub | I64Mul
+        | I64DivS | I64DivU | I64RemS | I64RemU | I64And | I64Or | I64Xor | I64Shl | I64ShrS
+        | I64ShrU | I64Rotl | I64Rotr | F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max
+        | F32Copysign | F64Add | F64Sub | F64Mul | F64Div | F64Min | F64Max | F64Copysign => (2, 1),
+
+        Unreachable
+        | Block(_)
+        | Loop(_)
+        | If { .. }
+        | Break(_)
+        | BreakIf(_)
+        | BreakTable { .. }
+        | Return => unreachable!("Control instructions are lowered"),
+
+        // Everything left takes one operand and replaces it: tests, unary operations,
+        // conversions, loads and the like
+        _ => (1, 1),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        module::Module,
+        runtime::test::{compile, local_function},
+        types::FuncIdx,
+    };
+
+    use super::{Code, DropKeep, Label, Op};
+
+    #[test]
+    fn branches_are_resolved() {
+        let binary = compile(
+            r#"(module
+                (func (result i32)
+                    (block (result i32)
+                        (loop
+                            (br_if 0 (i32.const 0)))
+                        (i32.const 1)
+                        (i32.const 2)
+                        (br 0)
+                        (drop))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let function = local_function(&module, FuncIdx(0));
+        let code = Code::compile(&module, function);
+
+        assert!(matches!(
+            code.ops[..],
+            [
+                Op::Execute(_),
+                Op::BrIf {
+                    label: Label {
+                        target: 0,
+                        drop_keep: DropKeep { drop: 0, keep: 0 }
+                    },
+                    ..
+                },
+                Op::Execute(_),
+                Op::Execute(_),
+                Op::Br {
+                    label: Label {
+                        target: 5,
+                        drop_keep: DropKeep { drop: 1, keep: 1 }
+                    },
+                    ..
+                },
+                Op::Return,
+            ]
+        ));
+    }
+
+    #[test]
+    fn function_branches_are_resolved() {
+        let binary = compile(
+            r#"(module
+                (func (param i32) (result i32)
+                    (i32.const 1)
+                    (block
+                        (br_if 1 (i32.const 7) (local.get 0))
+                        (drop))
+                    (drop)
+                    (i32.const 8)))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let function = local_function(&module, FuncIdx(0));
+        let code = Code::compile(&module, function);
+
+        // The branch returns with its value, dropping the one below
+        assert!(matches!(
+            code.ops[..],
+            [
+                Op::Execute(_),
+                Op::Execute(_),
+                Op::Execute(_),
+                Op::BrIf {
+                    label: Label {
+                        target: 7,
+                        drop_keep: DropKeep { drop: 1, keep: 1 }
+                    },
+                    ..
+                },
+                Op::Execute(_),
+                Op::Execute(_),
+                Op::Execute(_),
+                Op::Return,
+            ]
+        ));
+    }
+}
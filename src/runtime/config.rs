@@ -0,0 +1,50 @@
+/// How the instances of a [`Store`](super::Store) run, set with
+/// [`Store::with_config`](super::Store::with_config). Exceeding one of the limits traps with
+/// [`Trap::StackOverflow`](super::Trap::StackOverflow) instead of exhausting the host's memory
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// How many function calls can be running at once, calls between instances of the store
+    /// count together
+    pub max_call_depth: usize,
+    /// How many values the stack of an instance can hold, locals included, or how many slots
+    /// the frames of the register engine may occupy. Checked whenever a function is called, a
+    /// single function can't grow the stack by much
+    pub max_stack_size: usize,
+    /// How many bytes of the host's stack calls between instances may use. Each of them runs
+    /// the interpreter recursively, unlike calls within an instance
+    pub max_native_stack: usize,
+    /// Makes every float operation that results in NaN return the canonical NaN with a
+    /// positive sign and no payload, which the spec otherwise leaves open. Executions then
+    /// give the same results on any host
+    pub canonicalize_nans: bool,
+    /// How functions are executed, the engines only differ in speed
+    pub engine: Engine,
+}
+
+/// The interpreters a [`Store`](super::Store) can run functions with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Pushes and pops every operand on the value stack, like the instructions describe it
+    Stack,
+    /// Translates functions so that instructions read their operands from slots of the
+    /// function and write their result to one, which saves most of the moving around.
+    ///
+    /// Functions are translated the first time they are called. `local.get` and `local.set`
+    /// mostly disappear in the process, constants become operands, integer operations on
+    /// constants are computed right away and common sequences are fused into single
+    /// operations, see [`Instance::fusions`](super::Instance::fusions)
+    #[default]
+    Registers,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 100_000,
+            max_stack_size: 1 << 20,
+            max_native_stack: 1 << 20,
+            canonicalize_nans: false,
+            engine: Engine::default(),
+        }
+    }
+}
@@ -1,90 +1,38 @@
-use std::{cell::RefCell, ops::Deref, rc::Rc};
-
-use crate::types::{BlockIdx, FuncIdx, LocalIdx};
-
-use super::{locals::Locals, value::Value};
-
-#[derive(Debug, Clone, Copy)]
-pub enum InstructionIndex {
-    IndexInFunction(usize),
-    IndexInBlock {
-        block_idx: BlockIdx,
-        index_in_block: usize,
-    },
-}
+use crate::types::FuncIdx;
 
+/// A function running in the stack engine, where its locals start in the stack and where it
+/// continues once the function it called returns
 #[derive(Debug, Clone, Copy)]
-pub struct InstructionPosition(FuncIdx, InstructionIndex);
-
-#[derive(Debug, Clone)]
 pub struct FunctionState {
-    locals: Rc<RefCell<Locals>>,
-    instruction_position: InstructionPosition,
+    function_idx: FuncIdx,
+    /// Position of the next operation in the lowered code of the function
+    pc: usize,
+    /// Where the params and locals of the function start, its operands follow them
+    fp: usize,
 }
 
 impl FunctionState {
-    pub fn new_function(locals: Locals, index: FuncIdx) -> Self {
-        Self {
-            locals: Rc::new(RefCell::new(locals)),
-            instruction_position: InstructionPosition(index, InstructionIndex::IndexInFunction(0)),
-        }
-    }
-
-    pub fn new_block(&self, block_idx: BlockIdx) -> Self {
+    pub fn new_function(index: FuncIdx, fp: usize) -> Self {
         Self {
-            locals: self.locals.clone(),
-            instruction_position: InstructionPosition(
-                self.instruction_position.0,
-                InstructionIndex::IndexInBlock {
-                    block_idx,
-                    index_in_block: 0,
-                },
-            ),
+            function_idx: index,
+            pc: 0,
+            fp,
         }
     }
 
-    pub fn get_local_value(&self, idx: LocalIdx) -> Value {
-        self.locals.deref().borrow().get_value(idx)
-    }
-
-    pub fn set_local_value(&mut self, idx: LocalIdx, value: Value) {
-        self.locals.deref().borrow_mut().set_value(idx, value);
-    }
-
     pub fn function_idx(&self) -> FuncIdx {
-        self.instruction_position.0
-    }
-
-    pub fn instruction_index(&self) -> InstructionIndex {
-        self.instruction_position.1
+        self.function_idx
     }
 
-    pub fn in_block(&self) -> bool {
-        matches!(
-            self.instruction_position.1,
-            InstructionIndex::IndexInBlock { .. }
-        )
+    pub fn pc(&self) -> usize {
+        self.pc
     }
 
-    pub fn repeat_instruction(&mut self) {
-        let i = match &mut self.instruction_position.1 {
-            InstructionIndex::IndexInFunction(ref mut i) => i,
-            InstructionIndex::IndexInBlock {
-                index_in_block: ref mut i,
-                ..
-            } => i,
-        };
-        *i -= 1;
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc;
     }
 
-    pub fn next_instruction(&mut self) {
-        let i = match &mut self.instruction_position.1 {
-            InstructionIndex::IndexInFunction(ref mut i) => i,
-            InstructionIndex::IndexInBlock {
-                index_in_block: ref mut i,
-                ..
-            } => i,
-        };
-        *i += 1;
+    pub fn fp(&self) -> usize {
+        self.fp
     }
 }
@@ -1,24 +1,52 @@
 use core::panic;
-use std::cell::OnceCell;
+use std::{
+    cell::RefCell,
+    fmt::{self, Display},
+    rc::Rc,
+};
 
-use crate::types::{GlobalIdx, Mutability};
+use crate::types::{GlobalIdx, GlobalType, Mutability, ValueType};
 
-use super::{value::Value, variable::Variable};
+use super::{store::StoreId, value::Value, variable::Variable};
 
 #[derive(Debug)]
 pub struct Global {
     variable: Variable,
     mutability: Mutability,
+    /// The store whose references a reference typed global holds, see [`Table`]
+    ///
+    /// [`Table`]: super::table::Table
+    store: Option<StoreId>,
 }
 
 impl Global {
     pub fn new(value: Value, mutability: Mutability) -> Self {
         Self {
+            store: match value {
+                Value::Ref(value) => value.store(),
+                _ => None,
+            },
             variable: Variable::from_value(value),
             mutability,
         }
     }
 
+    /// Binds a reference typed global to `store` unless it's `None`, `false` if it belongs to
+    /// another one. Other globals can be shared between stores
+    fn bind(&mut self, store: Option<StoreId>) -> bool {
+        if !matches!(self.get_value().value_type(), ValueType::Ref(_)) {
+            return true;
+        }
+        match (self.store, store) {
+            (_, None) => true,
+            (None, Some(_)) => {
+                self.store = store;
+                true
+            }
+            (Some(own), Some(store)) => own == store,
+        }
+    }
+
     pub fn get_value(&self) -> Value {
         self.variable.value()
     }
@@ -33,24 +61,98 @@ impl Global {
     }
 }
 
-pub struct Globals(OnceCell<Vec<Global>>);
+/// A global that can be shared between the host and instances importing it
+#[derive(Debug, Clone)]
+pub struct GlobalHandle(Rc<RefCell<Global>>);
+
+impl GlobalHandle {
+    pub fn new(value: Value, mutability: Mutability) -> Self {
+        Self(Rc::new(RefCell::new(Global::new(value, mutability))))
+    }
+
+    pub fn get(&self) -> Value {
+        self.0.borrow().get_value()
+    }
+
+    pub fn set(&self, value: Value) -> Result<(), SetGlobalError> {
+        let mut global = self.0.borrow_mut();
+        if global.mutability == Mutability::Const {
+            return Err(SetGlobalError::Immutable);
+        }
+        if value.value_type() != global.get_value().value_type() {
+            return Err(SetGlobalError::TypeMismatch);
+        }
+        let store = match value {
+            Value::Ref(value) => value.store(),
+            _ => None,
+        };
+        if !global.bind(store) {
+            return Err(SetGlobalError::ForeignReference);
+        }
+        global.set_value(value);
+        Ok(())
+    }
+
+    /// Binds the global to the store of an instance importing it, `false` if it holds
+    /// references and already belongs to another one
+    pub(super) fn bind(&self, store: StoreId) -> bool {
+        self.0.borrow_mut().bind(Some(store))
+    }
+
+    pub fn global_type(&self) -> GlobalType {
+        let global = self.0.borrow();
+        GlobalType {
+            valtype: global.get_value().value_type(),
+            mutability: global.mutability,
+        }
+    }
+}
+
+/// Imported globals followed by the ones defined by the module
+pub struct Globals(Vec<GlobalHandle>);
 impl Globals {
-    pub fn new() -> Globals {
-        Globals(OnceCell::new())
+    pub fn new(imported: Vec<GlobalHandle>) -> Globals {
+        Globals(imported)
     }
 
-    pub fn fill(&mut self, values: Vec<Global>) {
-        assert!(self.0.get().is_none(), "Can't fill, already has globals");
+    /// Adds the globals defined by the module, they belong to the store of the instance
+    pub fn extend(&mut self, globals: Vec<Global>, store: StoreId) {
+        self.0.extend(globals.into_iter().map(|mut global| {
+            global.bind(Some(store));
+            GlobalHandle(Rc::new(RefCell::new(global)))
+        }));
+    }
 
-        self.0
-            .set(values)
-            .expect("Already checked in asseryt above");
+    pub fn handle(&self, GlobalIdx(global_idx): GlobalIdx) -> GlobalHandle {
+        self.0[global_idx as usize].clone()
     }
 
     pub fn set(&mut self, value: Value, GlobalIdx(global_idx): GlobalIdx) {
-        self.0.get_mut().expect("Globals to be initialized")[global_idx as usize].set_value(value);
+        self.0[global_idx as usize].0.borrow_mut().set_value(value);
     }
     pub fn get(&self, GlobalIdx(global_idx): GlobalIdx) -> Value {
-        self.0.get().expect("Globals to be initialized")[global_idx as usize].get_value()
+        self.0[global_idx as usize].0.borrow().get_value()
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetGlobalError {
+    Immutable,
+    TypeMismatch,
+    /// The value is a reference of another store than the global
+    ForeignReference,
+}
+
+impl Display for SetGlobalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SetGlobalError::Immutable => write!(f, "global is immutable"),
+            SetGlobalError::TypeMismatch => write!(f, "value doesn't match the type of the global"),
+            SetGlobalError::ForeignReference => {
+                write!(f, "reference belongs to another store than the global")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SetGlobalError {}
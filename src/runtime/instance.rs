@@ -0,0 +1,246 @@
+use std::rc::Rc;
+
+use crate::{
+    module::Module,
+    types::{ExportDesc, FuncIdx, FuncType},
+};
+
+use super::{
+    globals::GlobalHandle,
+    linker::Imports,
+    memory::MemoryHandle,
+    peephole::Fusions,
+    store::StoreData,
+    table::TableHandle,
+    typed_func::{TypedFunc, WasmParams, WasmResults},
+    value::FuncRef,
+    InstantiationError, Linker, Runtime, Trap, Value,
+};
+
+/// An instantiated module whose exports can be used by the embedder
+#[derive(Clone)]
+pub struct Instance<'b, 'a> {
+    runtime: Rc<Runtime<'b, 'a>>,
+    module: &'b Module<'a>,
+}
+
+impl<'b, 'a> Instance<'b, 'a> {
+    /// Instantiates a module without imports, see [`Linker::instantiate`] for modules that
+    /// import host functions
+    pub fn new(module: &'b Module<'a>) -> Result<Self, InstantiationError> {
+        Linker::new().instantiate(module)
+    }
+
+    /// Adds an instance to the store without initializing it yet
+    pub(super) fn uninitialized(
+        module: &'b Module<'a>,
+        imports: Imports<'b, 'a>,
+        store: Rc<StoreData<'b, 'a>>,
+    ) -> Self {
+        let instance_id = store.reserve_instance();
+        let runtime = Rc::new(Runtime::new(module, imports, instance_id, store.clone()));
+        store.set_instance(instance_id, &runtime);
+        Self { runtime, module }
+    }
+
+    /// Initializes the tables, globals and memory of the module and runs its start function
+    pub(super) fn initialize(&self) -> Result<(), Trap> {
+        self.runtime.initialize()
+    }
+
+    pub fn module(&self) -> &'b Module<'a> {
+        self.module
+    }
+
+    pub fn get_func(&self, name: &str) -> Option<Func<'b, 'a>> {
+        let ExportDesc::Func(func_idx) = self.module.get_export(name)? else {
+            return None;
+        };
+        Some(Func {
+            runtime: self.runtime.clone(),
+            func_idx: *func_idx,
+            signature: self.module.get_function(*func_idx)?.signature(),
+        })
+    }
+
+    /// Returns `None` if there's no such function or its signature doesn't match `Params` and
+    /// `Results`
+    pub fn get_typed_func<Params: WasmParams, Results: WasmResults>(
+        &self,
+        name: &str,
+    ) -> Option<TypedFunc<'b, 'a, Params, Results>> {
+        self.get_func(name)?.typed()
+    }
+
+    pub fn get_memory(&self, name: &str) -> Option<MemoryHandle> {
+        let ExportDesc::Memory(_) = self.module.get_export(name)? else {
+            return None;
+        };
+        Some(self.runtime.memory())
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<GlobalHandle> {
+        let ExportDesc::Global(global_idx) = self.module.get_export(name)? else {
+            return None;
+        };
+        Some(self.runtime.global(*global_idx))
+    }
+
+    pub fn get_table(&self, name: &str) -> Option<TableHandle> {
+        let ExportDesc::Table(table_idx) = self.module.get_export(name)? else {
+            return None;
+        };
+        Some(self.runtime.table(*table_idx))
+    }
+
+    /// How often each fusion fired in the functions of the instance that were translated for
+    /// the register engine. Functions are translated when they are first called, so this
+    /// grows while the instance runs
+    pub fn fusions(&self) -> Fusions {
+        self.runtime.fusions()
+    }
+}
+
+/// An exported function, keeps the instance it belongs to alive
+#[derive(Clone)]
+pub struct Func<'b, 'a> {
+    runtime: Rc<Runtime<'b, 'a>>,
+    func_idx: FuncIdx,
+    signature: Rc<FuncType>,
+}
+
+impl<'b, 'a> Func<'b, 'a> {
+    pub fn signature(&self) -> &FuncType {
+        &self.signature
+    }
+
+    /// A reference to the function that can be stored in tables of instances in the same store
+    pub fn func_ref(&self) -> FuncRef {
+        FuncRef {
+            store: self.runtime.store_id(),
+            instance: self.runtime.instance_id(),
+            func_idx: self.func_idx,
+        }
+    }
+
+    /// Runs the function to completion, a trap leaves the instance usable for further calls
+    pub fn call(&self, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let arguments_match = args.len() == self.signature.params.len()
+            && args
+                .iter()
+                .zip(&self.signature.params)
+                .all(|(arg, param)| arg.value_type() == *param);
+        if !arguments_match {
+            return Err(Trap::ArgumentMismatch);
+        }
+        self.runtime.call(self.func_idx, args)
+    }
+
+    /// Returns `None` if the signature doesn't match `Params` and `Results`
+    pub fn typed<Params: WasmParams, Results: WasmResults>(
+        &self,
+    ) -> Option<TypedFunc<'b, 'a, Params, Results>> {
+        TypedFunc::new(self.runtime.clone(), self.func_idx, &self.signature)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        module::Module,
+        runtime::{globals::SetGlobalError, test::compile, Instance, Ref, Trap, Value},
+        types::RefType,
+    };
+
+    #[test]
+    fn exported_memory() {
+        let binary = compile(
+            r#"(module
+                (memory (export "memory") 1 2)
+                (data (i32.const 0) "abc")
+                (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let instance = Instance::new(&module).unwrap();
+        let memory = instance.get_memory("memory").unwrap();
+        let load = instance.get_typed_func::<i32, i32>("load").unwrap();
+
+        let mut buffer = [0; 3];
+        memory.read(0, &mut buffer).unwrap();
+        assert_eq!(&buffer, b"abc");
+
+        memory.write(10, &[42]).unwrap();
+        assert_eq!(load.call(10), Ok(42));
+        assert_eq!(memory.write(65535, &[1, 2]), Err(Trap::MemoryOutOfBounds));
+
+        memory.data_mut()[11] = 7;
+        assert_eq!(load.call(11), Ok(7));
+
+        assert_eq!(memory.grow(1), Some(1));
+        assert_eq!(memory.data().len(), 2 * 65536);
+        assert_eq!(memory.grow(1), None);
+        assert!(instance.get_memory("load").is_none());
+    }
+
+    #[test]
+    fn exported_globals() {
+        let binary = compile(
+            r#"(module
+                (global (export "counter") (mut i32) (i32.const 1))
+                (global (export "constant") f64 (f64.const 2.5))
+                (func (export "increment") (global.set 0 (i32.add (global.get 0) (i32.const 1)))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let instance = Instance::new(&module).unwrap();
+        let counter = instance.get_global("counter").unwrap();
+        let constant = instance.get_global("constant").unwrap();
+
+        instance.get_func("increment").unwrap().call(&[]).unwrap();
+        assert!(matches!(counter.get(), Value::I32(2)));
+
+        counter.set(Value::I32(10)).unwrap();
+        instance.get_func("increment").unwrap().call(&[]).unwrap();
+        assert!(matches!(counter.get(), Value::I32(11)));
+
+        assert_eq!(
+            counter.set(Value::I64(0)),
+            Err(SetGlobalError::TypeMismatch)
+        );
+        assert_eq!(
+            constant.set(Value::F64(1.0)),
+            Err(SetGlobalError::Immutable)
+        );
+        assert!(matches!(constant.get(), Value::F64(2.5)));
+    }
+
+    #[test]
+    fn exported_table() {
+        let binary = compile(
+            r#"(module
+                (table (export "table") 2 4 funcref)
+                (func (export "answer") (result i32) (i32.const 42))
+                (func (export "call") (param i32) (result i32)
+                    (call_indirect (result i32) (local.get 0))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let instance = Instance::new(&module).unwrap();
+        let table = instance.get_table("table").unwrap();
+        let call = instance.get_typed_func::<i32, i32>("call").unwrap();
+        let answer = instance.get_func("answer").unwrap().func_ref();
+
+        assert_eq!(call.call(1), Err(Trap::UninitializedElement));
+        table.set(1, Ref::Func(answer)).unwrap();
+        assert_eq!(table.get(1), Some(Ref::Func(answer)));
+        assert_eq!(call.call(1), Ok(42));
+
+        assert_eq!(table.grow(2, Ref::Func(answer)), Some(2));
+        assert_eq!(table.size(), 4);
+        assert_eq!(call.call(3), Ok(42));
+        assert_eq!(table.grow(1, Ref::Null(RefType::FuncRef)), None);
+        assert_eq!(table.get(4), None);
+        assert_eq!(
+            table.set(4, Ref::Null(RefType::FuncRef)),
+            Err(Trap::TableOutOfBounds)
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::{
+    any::Any,
+    cell::RefMut,
+    collections::HashMap,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+use crate::{
+    module::Module,
+    types::{ExportDesc, FuncType, ImportDesc, Limit, MemoryType, TableType},
+    validate::ValidationError,
+};
+
+use super::{
+    globals::GlobalHandle,
+    memory::{Memory, MemoryHandle},
+    store::{ExternObjects, StoreId},
+    table::TableHandle,
+    value::ExternRef,
+    Func, Instance, Store, Trap, Value,
+};
+
+type HostCallback = dyn Fn(&mut Caller<'_>, &[Value]) -> Result<Vec<Value>, Trap>;
+
+/// A function implemented by the embedder that modules can import
+#[derive(Clone)]
+pub struct HostFunc {
+    signature: Rc<FuncType>,
+    callback: Rc<HostCallback>,
+}
+
+impl HostFunc {
+    pub fn signature(&self) -> &FuncType {
+        &self.signature
+    }
+
+    /// Calls the host function with arguments already checked against its signature
+    pub(super) fn call(&self, caller: &mut Caller<'_>, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        let results = (self.callback)(caller, args)?;
+        let results_match = results.len() == self.signature.returns.len()
+            && results
+                .iter()
+                .zip(&self.signature.returns)
+                .all(|(result, return_type)| result.value_type() == *return_type);
+        if !results_match {
+            return Err(Trap::HostResultMismatch);
+        }
+        Ok(results)
+    }
+}
+
+/// What a host function can access of the instance that called it
+pub struct Caller<'r> {
+    memory: &'r MemoryHandle,
+    /// Only borrowed once the host function asks for it, the memory may be shared with
+    /// instances the host function calls
+    borrowed_memory: Option<RefMut<'r, Memory>>,
+    extern_objects: &'r ExternObjects,
+    store: StoreId,
+}
+
+impl<'r> Caller<'r> {
+    pub(super) fn new(
+        memory: &'r MemoryHandle,
+        extern_objects: &'r ExternObjects,
+        store: StoreId,
+    ) -> Self {
+        Self {
+            memory,
+            borrowed_memory: None,
+            extern_objects,
+            store,
+        }
+    }
+
+    pub fn memory(&mut self) -> &mut Memory {
+        self.borrowed_memory
+            .get_or_insert_with(|| self.memory.borrow_mut())
+    }
+
+    /// Wraps a host object into a reference the host function can return to the guest, see
+    /// [`Store::extern_ref`]
+    pub fn extern_ref<T: Any>(&self, object: T) -> ExternRef {
+        self.extern_objects.insert(self.store, object)
+    }
+
+    /// The object behind a reference the guest passed in, `None` if it isn't a `T` or was
+    /// dropped
+    pub fn extern_object<T: Any>(&self, extern_ref: ExternRef) -> Option<Rc<T>> {
+        self.extern_objects.get(self.store, extern_ref)
+    }
+
+    /// Drops the object behind a reference, like a file the guest closed, see
+    /// [`Store::drop_extern`]
+    pub fn drop_extern(&self, extern_ref: ExternRef) -> bool {
+        self.extern_objects.remove(self.store, extern_ref)
+    }
+}
+
+/// Something provided by the host or exported by an instance that a module can import
+#[derive(Clone)]
+pub enum Extern<'b, 'a> {
+    HostFunc(HostFunc),
+    Func(Func<'b, 'a>),
+    Memory(MemoryHandle),
+    Table(TableHandle),
+    Global(GlobalHandle),
+}
+
+/// A function import, either implemented by the host or by another instance
+#[derive(Clone)]
+pub(super) enum ImportedFunc<'b, 'a> {
+    Host(HostFunc),
+    Instance(Func<'b, 'a>),
+}
+
+/// The externals an instance was instantiated with, in the order of the imports of each kind
+pub(super) struct Imports<'b, 'a> {
+    pub functions: Vec<ImportedFunc<'b, 'a>>,
+    pub memory: Option<MemoryHandle>,
+    pub tables: Vec<TableHandle>,
+    pub globals: Vec<GlobalHandle>,
+}
+
+/// Externals keyed by the module and name they are imported with
+///
+/// Host functions are closures that get the arguments and the caller, to access its memory,
+/// and return the results or a [`Trap`]. Memories, tables and globals are shared as handles,
+/// and [`Linker::instance`] makes the exports of another instance importable. Instantiating
+/// fails with an [`InstantiationError`] if an import is missing, or if its signature, limits,
+/// element type or mutability don't match what the module declares
+#[derive(Clone, Default)]
+pub struct Linker<'b, 'a> {
+    externs: HashMap<(String, String), Extern<'b, 'a>>,
+}
+
+impl<'b, 'a> Linker<'b, 'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an external, replacing a previous one with the same module and name
+    pub fn define(&mut self, module: &str, name: &str, external: Extern<'b, 'a>) -> &mut Self {
+        self.externs
+            .insert((module.to_string(), name.to_string()), external);
+        self
+    }
+
+    /// Registers a host function, replacing a previous one with the same module and name.
+    /// The callback receives arguments matching `signature` and has to return values matching it
+    pub fn func(
+        &mut self,
+        module: &str,
+        name: &str,
+        signature: FuncType,
+        callback: impl Fn(&mut Caller<'_>, &[Value]) -> Result<Vec<Value>, Trap> + 'static,
+    ) -> &mut Self {
+        let function = HostFunc {
+            signature: Rc::new(signature),
+            callback: Rc::new(callback),
+        };
+        self.define(module, name, Extern::HostFunc(function))
+    }
+
+    /// Makes every export of the instance importable under the given module name
+    pub fn instance(&mut self, module: &str, instance: &Instance<'b, 'a>) -> &mut Self {
+        for export in instance.module().exports() {
+            let external = match export.desc {
+                ExportDesc::Func(_) => instance.get_func(export.name).map(Extern::Func),
+                ExportDesc::Memory(_) => instance.get_memory(export.name).map(Extern::Memory),
+                ExportDesc::Table(_) => instance.get_table(export.name).map(Extern::Table),
+                ExportDesc::Global(_) => instance.get_global(export.name).map(Extern::Global),
+            };
+            let external = external.expect("Exports of an instance are valid");
+            self.define(module, export.name, external);
+        }
+        self
+    }
+
+    pub fn memory(&mut self, module: &str, name: &str, memory: MemoryHandle) -> &mut Self {
+        self.define(module, name, Extern::Memory(memory))
+    }
+
+    pub fn table(&mut self, module: &str, name: &str, table: TableHandle) -> &mut Self {
+        self.define(module, name, Extern::Table(table))
+    }
+
+    pub fn global(&mut self, module: &str, name: &str, global: GlobalHandle) -> &mut Self {
+        self.define(module, name, Extern::Global(global))
+    }
+
+    pub fn get(&self, module: &str, name: &str) -> Option<&Extern<'b, 'a>> {
+        self.externs.get(&(module.to_string(), name.to_string()))
+    }
+
+    /// Resolves the imports of the module and instantiates it in a store of its own, use
+    /// [`Store::instantiate`] for instances that share function references
+    pub fn instantiate(
+        &self,
+        module: &'b Module<'a>,
+    ) -> Result<Instance<'b, 'a>, InstantiationError> {
+        Store::new().instantiate(self, module)
+    }
+
+    /// Looks up every import and checks that it is compatible with what the module expects
+    /// The imports of the module, tables and reference typed globals are bound to `store`
+    pub(super) fn resolve(
+        &self,
+        module: &Module<'_>,
+        store: StoreId,
+    ) -> Result<Imports<'b, 'a>, InstantiationError> {
+        let mut imports = Imports {
+            functions: vec![],
+            memory: None,
+            tables: vec![],
+            globals: vec![],
+        };
+        for import in module.imports() {
+            let external = self.get(import.mod_name, import.name).ok_or_else(|| {
+                InstantiationError::UnknownImport {
+                    module: import.mod_name.to_string(),
+                    name: import.name.to_string(),
+                }
+            })?;
+            let incompatible = || InstantiationError::IncompatibleImport {
+                module: import.mod_name.to_string(),
+                name: import.name.to_string(),
+            };
+            let foreign = || InstantiationError::ForeignImport {
+                module: import.mod_name.to_string(),
+                name: import.name.to_string(),
+            };
+
+            match (&import.desc, external) {
+                (ImportDesc::Func(type_idx), Extern::HostFunc(function)) => {
+                    let signature = module
+                        .function_signature(*type_idx)
+                        .expect("Type of imported functions was checked while parsing");
+                    if *function.signature != *signature {
+                        return Err(incompatible());
+                    }
+                    imports.functions.push(ImportedFunc::Host(function.clone()));
+                }
+                (ImportDesc::Func(type_idx), Extern::Func(function)) => {
+                    let signature = module
+                        .function_signature(*type_idx)
+                        .expect("Type of imported functions was checked while parsing");
+                    if *function.signature() != *signature {
+                        return Err(incompatible());
+                    }
+                    imports
+                        .functions
+                        .push(ImportedFunc::Instance(function.clone()));
+                }
+                (ImportDesc::Memory(MemoryType(limit)), Extern::Memory(memory)) => {
+                    if !limit_matches(memory.limit(), *limit) {
+                        return Err(incompatible());
+                    }
+                    imports.memory = Some(memory.clone());
+                }
+                (ImportDesc::Table(TableType(ref_type, limit)), Extern::Table(table)) => {
+                    let TableType(actual_ref_type, actual_limit) = table.table_type();
+                    if actual_ref_type != *ref_type || !limit_matches(actual_limit, *limit) {
+                        return Err(incompatible());
+                    }
+                    if !table.bind(store) {
+                        return Err(foreign());
+                    }
+                    imports.tables.push(table.clone());
+                }
+                (ImportDesc::Global(global_type), Extern::Global(global)) => {
+                    if global.global_type() != *global_type {
+                        return Err(incompatible());
+                    }
+                    if !global.bind(store) {
+                        return Err(foreign());
+                    }
+                    imports.globals.push(global.clone());
+                }
+                _ => return Err(incompatible()),
+            }
+        }
+        Ok(imports)
+    }
+}
+
+/// Whether something of the actual size and maximum can be used where `expected` is imported
+fn limit_matches(actual: Limit, expected: Limit) -> bool {
+    actual.min >= expected.min
+        && match (actual.max, expected.max) {
+            (_, None) => true,
+            (Some(actual_max), Some(expected_max)) => actual_max <= expected_max,
+            (None, Some(_)) => false,
+        }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstantiationError {
+    /// The module doesn't pass validation, the engines only run valid code
+    Invalid(ValidationError),
+    UnknownImport {
+        module: String,
+        name: String,
+    },
+    IncompatibleImport {
+        module: String,
+        name: String,
+    },
+    /// The imported table or global holds references of another store
+    ForeignImport {
+        module: String,
+        name: String,
+    },
+    /// Initializing the module or running its start function trapped
+    Trap(Trap),
+}
+
+impl Display for InstantiationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstantiationError::Invalid(err) => write!(f, "invalid module: {}", err),
+            InstantiationError::UnknownImport { module, name } => {
+                write!(f, "unknown import {}::{}", module, name)
+            }
+            InstantiationError::IncompatibleImport { module, name } => {
+                write!(f, "incompatible import type for {}::{}", module, name)
+            }
+            InstantiationError::ForeignImport { module, name } => {
+                write!(f, "import {}::{} belongs to another store", module, name)
+            }
+            InstantiationError::Trap(trap) => write!(f, "{}", trap),
+        }
+    }
+}
+
+impl std::error::Error for InstantiationError {}
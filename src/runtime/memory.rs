@@ -1,21 +1,91 @@
-use std::ops::Range;
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    ops::Range,
+    rc::Rc,
+};
 
 use crate::types::{Limit, MemoryArgument};
 use paste::paste;
 
+use super::trap::Trap;
+
+#[cfg(feature = "mmap-memory")]
+mod mmap;
+#[cfg(feature = "mmap-memory")]
+pub use mmap::MmapMemory;
+
 const PAGE_SIZE: usize = 65536;
+/// Largest amount of pages a 32 bit memory can address
+const MAX_PAGES: u32 = 65536;
 
 pub struct Memory {
+    #[cfg(not(feature = "mmap-memory"))]
     data: Vec<u8>,
+    #[cfg(feature = "mmap-memory")]
+    data: MmapMemory,
     limits: Limit,
 }
 
+/// A memory that can be shared between the host and instances importing it
+#[derive(Clone)]
+pub struct MemoryHandle(Rc<RefCell<Memory>>);
+
+impl MemoryHandle {
+    pub fn new(limit: Limit) -> Self {
+        Self(Rc::new(RefCell::new(Memory::new(limit))))
+    }
+
+    /// Current size in pages and the maximum it can grow to
+    pub fn limit(&self) -> Limit {
+        self.0.borrow().limit()
+    }
+
+    /// Size in pages
+    pub fn size(&self) -> u32 {
+        self.0.borrow().size()
+    }
+
+    /// Returns the previous size in pages, or `None` if the memory can't grow that much
+    pub fn grow(&self, amount_of_pages: u32) -> Option<u32> {
+        let prev_size = self.0.borrow_mut().grow(amount_of_pages);
+        u32::try_from(prev_size).ok()
+    }
+
+    pub fn read(&self, address: u32, buffer: &mut [u8]) -> Result<(), Trap> {
+        buffer.copy_from_slice(self.0.borrow().bytes(address as usize, buffer.len())?);
+        Ok(())
+    }
+
+    pub fn write(&self, address: u32, data: &[u8]) -> Result<(), Trap> {
+        self.0.borrow_mut().fill_data(address, data)
+    }
+
+    /// The whole memory, has to be released before the guest runs again
+    pub fn data(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.0.borrow(), |memory| &memory.data[..])
+    }
+
+    /// The whole memory, has to be released before the guest runs again
+    pub fn data_mut(&self) -> RefMut<'_, [u8]> {
+        RefMut::map(self.0.borrow_mut(), |memory| &mut memory.data[..])
+    }
+
+    pub(crate) fn borrow(&self) -> Ref<'_, Memory> {
+        self.0.borrow()
+    }
+
+    pub(crate) fn borrow_mut(&self) -> RefMut<'_, Memory> {
+        self.0.borrow_mut()
+    }
+}
+
 macro_rules! define_load_function {
     ($func_name:ident, $ty:ty) => {
-        pub fn $func_name(&self, address_raw: u32, memarg: MemoryArgument) -> $ty {
-            let address = Memory::apply_memarg(address_raw, memarg) as usize;
-            let bytes = &self.data[address..address + std::mem::size_of::<$ty>()];
-            <$ty>::from_le_bytes(bytes.try_into().unwrap())
+        pub fn $func_name(&self, address_raw: u32, memarg: MemoryArgument) -> Result<$ty, Trap> {
+            let address = Memory::apply_memarg(address_raw, memarg)?;
+            Ok(<$ty>::from_le_bytes(
+                self.load::<{ std::mem::size_of::<$ty>() }>(address)?,
+            ))
         }
     };
 }
@@ -23,10 +93,14 @@ macro_rules! define_load_function {
 // Basic store functions
 macro_rules! define_store_function {
     ($func_name:ident, $ty:ty) => {
-        pub fn $func_name(&mut self, value: $ty, address_raw: u32, memarg: MemoryArgument) {
-            let address = Memory::apply_memarg(address_raw, memarg) as usize;
-            let bytes = value.to_le_bytes();
-            self.data[address..address + bytes.len()].copy_from_slice(&bytes);
+        pub fn $func_name(
+            &mut self,
+            value: $ty,
+            address_raw: u32,
+            memarg: MemoryArgument,
+        ) -> Result<(), Trap> {
+            let address = Memory::apply_memarg(address_raw, memarg)?;
+            self.store(address, value.to_le_bytes())
         }
     };
 }
@@ -37,11 +111,11 @@ macro_rules! define_load_ext_function_signed {
             &mut self,
             address_raw: u32,
             memarg: MemoryArgument,
-        ) -> $ty {
-            let address = Memory::apply_memarg(address_raw, memarg);
+        ) -> Result<$ty, Trap> {
+            let address = Memory::apply_memarg(address_raw, memarg)?;
             const NUM_BYTES: usize = $num_bits / 8;
             paste! {
-                [<i $num_bits>]::from_le_bytes(self.data[address..address + NUM_BYTES].try_into().unwrap()) as $ty
+                Ok([<i $num_bits>]::from_le_bytes(self.load::<NUM_BYTES>(address)?) as $ty)
             }
         }
     };
@@ -53,11 +127,11 @@ macro_rules! define_load_ext_function_unsigned {
             &mut self,
             address_raw: u32,
             memarg: MemoryArgument,
-        ) -> $ty {
-            let address = Memory::apply_memarg(address_raw, memarg);
+        ) -> Result<$ty, Trap> {
+            let address = Memory::apply_memarg(address_raw, memarg)?;
             const NUM_BYTES: usize = $num_bits / 8;
             paste! {
-                [<u $num_bits>]::from_le_bytes(self.data[address..address + NUM_BYTES].try_into().unwrap()) as $ty
+                Ok([<u $num_bits>]::from_le_bytes(self.load::<NUM_BYTES>(address)?) as $ty)
             }
         }
     };
@@ -66,10 +140,15 @@ macro_rules! define_load_ext_function_unsigned {
 // Extended store functions
 macro_rules! define_store_ext_function {
     ($func_name:ident, $value_ty:ty, $num_bytes:expr) => {
-        pub fn $func_name(&mut self, value: $value_ty, address_raw: u32, memarg: MemoryArgument) {
-            let address = Memory::apply_memarg(address_raw, memarg) as usize;
-            let bytes = value.to_le_bytes();
-            self.data[address..address + $num_bytes].copy_from_slice(&bytes[..$num_bytes]);
+        pub fn $func_name(
+            &mut self,
+            value: $value_ty,
+            address_raw: u32,
+            memarg: MemoryArgument,
+        ) -> Result<(), Trap> {
+            let address = Memory::apply_memarg(address_raw, memarg)?;
+            let bytes: [u8; $num_bytes] = value.to_le_bytes()[..$num_bytes].try_into().unwrap();
+            self.store(address, bytes)
         }
     };
 }
@@ -79,37 +158,90 @@ macro_rules! define_store_ext_function {
 impl Memory {
     pub fn new(limit: Limit) -> Memory {
         Memory {
+            #[cfg(not(feature = "mmap-memory"))]
             data: vec![0; limit.min as usize * PAGE_SIZE],
+            #[cfg(feature = "mmap-memory")]
+            data: MmapMemory::new(limit.min as usize * PAGE_SIZE),
             limits: limit,
         }
     }
 
     pub fn grow(&mut self, amount_of_pages: u32) -> i32 {
         let prev_size = (self.data.len() / PAGE_SIZE) as u32;
-        let new_size = prev_size + amount_of_pages;
-        if self.limits.max.is_some_and(|max| new_size > max) {
+        let max = self.limits.max.unwrap_or(MAX_PAGES).min(MAX_PAGES);
+        let Some(new_size) = prev_size.checked_add(amount_of_pages) else {
+            return -1;
+        };
+        if new_size > max {
             return -1;
         }
 
+        #[cfg(not(feature = "mmap-memory"))]
         self.data
-            .extend(std::iter::repeat(0).take(amount_of_pages as usize * PAGE_SIZE));
+            .extend(std::iter::repeat_n(0, amount_of_pages as usize * PAGE_SIZE));
+        #[cfg(feature = "mmap-memory")]
+        if !self.data.grow(amount_of_pages as usize * PAGE_SIZE) {
+            return -1;
+        }
 
         prev_size as i32
     }
 
-    fn apply_memarg(address_raw: u32, memarg: MemoryArgument) -> usize {
-        let address_raw: usize = address_raw as usize;
+    /// The effective address of an access, which may lie beyond what 32 bits can address
+    fn apply_memarg(address_raw: u32, memarg: MemoryArgument) -> Result<usize, Trap> {
+        (address_raw as usize)
+            .checked_add(memarg.offset as usize)
+            .ok_or(Trap::MemoryOutOfBounds)
+    }
+
+    /// The range of `len` bytes starting at `address`, if all of them are in bounds
+    fn range(&self, address: usize, len: usize) -> Result<Range<usize>, Trap> {
+        match address.checked_add(len) {
+            Some(end) if end <= self.data.len() => Ok(address..end),
+            _ => Err(Trap::MemoryOutOfBounds),
+        }
+    }
+
+    fn bytes(&self, address: usize, len: usize) -> Result<&[u8], Trap> {
+        let range = self.range(address, len)?;
+        Ok(&self.data[range])
+    }
+
+    fn bytes_mut(&mut self, address: usize, len: usize) -> Result<&mut [u8], Trap> {
+        let range = self.range(address, len)?;
+        Ok(&mut self.data[range])
+    }
+
+    #[cfg(not(feature = "mmap-memory"))]
+    fn load<const N: usize>(&self, address: usize) -> Result<[u8; N], Trap> {
+        Ok(self.bytes(address, N)?.try_into().unwrap())
+    }
 
-        address_raw + memarg.offset as usize
+    #[cfg(not(feature = "mmap-memory"))]
+    fn store<const N: usize>(&mut self, address: usize, bytes: [u8; N]) -> Result<(), Trap> {
+        self.bytes_mut(address, N)?.copy_from_slice(&bytes);
+        Ok(())
     }
 
-    pub fn fill_data(&mut self, address: u32, data: &[u8]) {
-        let address = address as usize;
-        self.data[address..address + data.len()].copy_from_slice(data);
+    /// Faults in the guard region are turned into traps, so there is nothing to compare
+    #[cfg(feature = "mmap-memory")]
+    fn load<const N: usize>(&self, address: usize) -> Result<[u8; N], Trap> {
+        self.data.load(address)
     }
 
-    pub fn get_range(&self, range: Range<usize>) -> &[u8] {
-        &self.data[range]
+    #[cfg(feature = "mmap-memory")]
+    fn store<const N: usize>(&mut self, address: usize, bytes: [u8; N]) -> Result<(), Trap> {
+        self.data.store(address, bytes)
+    }
+
+    pub fn fill_data(&mut self, address: u32, data: &[u8]) -> Result<(), Trap> {
+        self.bytes_mut(address as usize, data.len())?
+            .copy_from_slice(data);
+        Ok(())
+    }
+
+    pub fn get_bytes(&self, address: u32, len: u32) -> Result<&[u8], Trap> {
+        self.bytes(address as usize, len as usize)
     }
 
     define_load_function!(load_i32, i32);
@@ -133,20 +265,20 @@ impl Memory {
 
     define_store_function!(store_i32, i32);
 
-    pub fn store_u32(&mut self, value: u32, addr: u32) {
+    pub fn store_u32(&mut self, value: u32, addr: u32) -> Result<(), Trap> {
         self.store_i32(
             i32::from_le_bytes(value.to_le_bytes()),
             addr,
             MemoryArgument::default(),
-        );
+        )
     }
-    pub fn store_u16(&mut self, value: u16, addr: u32) {
+    pub fn store_u16(&mut self, value: u16, addr: u32) -> Result<(), Trap> {
         let value = value as u32;
         self.store_i32_16(
             i32::from_le_bytes(value.to_le_bytes()),
             addr,
             MemoryArgument::default(),
-        );
+        )
     }
 
     define_store_function!(store_i64, i64);
@@ -159,19 +291,28 @@ impl Memory {
     define_store_ext_function!(store_i64_16, i64, 2);
     define_store_ext_function!(store_i64_32, i64, 4);
 
-    pub fn cpy(&mut self, src: usize, dst: usize, len: usize) {
-        let mut buf = vec![0; len];
-        buf.copy_from_slice(&self.data[src..src + len]);
-        self.data[dst..dst + len].copy_from_slice(&buf);
+    /// Both ranges are checked before anything is copied, they may overlap
+    pub fn cpy(&mut self, src: usize, dst: usize, len: usize) -> Result<(), Trap> {
+        let src = self.range(src, len)?;
+        let dst = self.range(dst, len)?;
+        self.data.copy_within(src, dst.start);
+        Ok(())
     }
 
     pub fn size(&self) -> u32 {
         (self.data.len() / PAGE_SIZE) as u32
     }
 
-    pub fn fill_value(&mut self, len: usize, addr: usize, value: u8) {
-        for i in addr..addr + len {
-            self.data[i] = value;
+    /// Current size in pages and the maximum it can grow to
+    pub fn limit(&self) -> Limit {
+        Limit {
+            min: self.size(),
+            max: self.limits.max,
         }
     }
+
+    pub fn fill_value(&mut self, len: usize, addr: usize, value: u8) -> Result<(), Trap> {
+        self.bytes_mut(addr, len)?.fill(value);
+        Ok(())
+    }
 }
@@ -0,0 +1,331 @@
+use std::{
+    arch::global_asm,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::{Once, OnceLock},
+};
+
+use super::super::trap::Trap;
+
+#[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+compile_error!("the mmap-memory feature is only supported on x86_64 Linux");
+
+/// Addresses a 32 bit memory can use
+const ADDRESS_SPACE: usize = 1 << 32;
+/// An effective address is a 32 bit address plus a 32 bit offset, so it can reach another
+/// 4 GiB past the memory. The extra page covers the last bytes of an access starting right
+/// before the end of the guard region.
+const GUARD_SIZE: usize = (1 << 32) + 65536;
+const RESERVED_SIZE: usize = ADDRESS_SPACE + GUARD_SIZE;
+
+/// Linear memory in an address space reserved up front, so growing never moves or copies it.
+///
+/// Only the pages the memory currently has are readable and writable, everything up to the
+/// end of the guard region faults. Loads and stores don't compare against the size, instead
+/// the access is done by a small assembly routine and a fault in it becomes a `Trap`.
+pub struct MmapMemory {
+    base: NonNull<u8>,
+    len: usize,
+}
+
+impl MmapMemory {
+    pub fn new(len: usize) -> MmapMemory {
+        install_fault_handler();
+
+        let base = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                RESERVED_SIZE,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            panic!(
+                "Failed to reserve address space for a linear memory: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+
+        let mut memory = MmapMemory {
+            base: NonNull::new(base as *mut u8).unwrap(),
+            len: 0,
+        };
+        if !memory.grow(len) {
+            panic!(
+                "Failed to commit {len} bytes of linear memory: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        memory
+    }
+
+    /// Makes `additional` more bytes accessible, they are zero as they were never touched
+    pub fn grow(&mut self, additional: usize) -> bool {
+        let Some(new_len) = self.len.checked_add(additional) else {
+            return false;
+        };
+        if new_len > ADDRESS_SPACE {
+            return false;
+        }
+        if additional == 0 {
+            return true;
+        }
+
+        let start = unsafe { self.base.as_ptr().add(self.len) };
+        let result = unsafe {
+            libc::mprotect(
+                start as *mut libc::c_void,
+                additional,
+                libc::PROT_READ | libc::PROT_WRITE,
+            )
+        };
+        if result != 0 {
+            return false;
+        }
+        self.len = new_len;
+        true
+    }
+
+    /// Reads `N` bytes at an effective address, which may be anywhere a memory argument can reach
+    pub fn load<const N: usize>(&self, address: usize) -> Result<[u8; N], Trap> {
+        debug_assert!(address < ADDRESS_SPACE * 2);
+        let pointer = unsafe { self.base.as_ptr().add(address) };
+        let result = unsafe {
+            match N {
+                1 => reactor_guarded_load_1(pointer),
+                2 => reactor_guarded_load_2(pointer),
+                4 => reactor_guarded_load_4(pointer),
+                8 => reactor_guarded_load_8(pointer),
+                _ => unreachable!("loads are 1, 2, 4 or 8 bytes wide"),
+            }
+        };
+        if result.trapped != 0 {
+            return Err(Trap::MemoryOutOfBounds);
+        }
+        Ok(result.value.to_le_bytes()[..N].try_into().unwrap())
+    }
+
+    /// Writes `N` bytes at an effective address, nothing is written if any of them is out of bounds
+    pub fn store<const N: usize>(&mut self, address: usize, bytes: [u8; N]) -> Result<(), Trap> {
+        debug_assert!(address < ADDRESS_SPACE * 2);
+        let pointer = unsafe { self.base.as_ptr().add(address) };
+        let mut value = [0; 8];
+        value[..N].copy_from_slice(&bytes);
+        let value = u64::from_le_bytes(value);
+        let result = unsafe {
+            match N {
+                1 => reactor_guarded_store_1(pointer, value),
+                2 => reactor_guarded_store_2(pointer, value),
+                4 => reactor_guarded_store_4(pointer, value),
+                8 => reactor_guarded_store_8(pointer, value),
+                _ => unreachable!("stores are 1, 2, 4 or 8 bytes wide"),
+            }
+        };
+        if result.trapped != 0 {
+            return Err(Trap::MemoryOutOfBounds);
+        }
+        Ok(())
+    }
+}
+
+impl Deref for MmapMemory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.base.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for MmapMemory {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.base.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for MmapMemory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base.as_ptr() as *mut libc::c_void, RESERVED_SIZE);
+        }
+    }
+}
+
+/// What a guarded access returns, in `rax` and `rdx`
+#[repr(C)]
+struct Guarded {
+    value: u64,
+    trapped: u64,
+}
+
+// Each access is a single instruction, so when it faults nothing has been read or written
+// and the handler can resume at `reactor_guarded_trap`, which returns from the routine with
+// `trapped` set. Nothing here touches the stack before the access.
+global_asm!(
+    ".text",
+    ".p2align 4",
+    ".globl reactor_guarded_start",
+    ".globl reactor_guarded_end",
+    ".globl reactor_guarded_trap",
+    ".globl reactor_guarded_load_1",
+    ".globl reactor_guarded_load_2",
+    ".globl reactor_guarded_load_4",
+    ".globl reactor_guarded_load_8",
+    ".globl reactor_guarded_store_1",
+    ".globl reactor_guarded_store_2",
+    ".globl reactor_guarded_store_4",
+    ".globl reactor_guarded_store_8",
+    "reactor_guarded_start:",
+    "reactor_guarded_load_1:",
+    "    xor edx, edx",
+    "    movzx eax, byte ptr [rdi]",
+    "    ret",
+    "reactor_guarded_load_2:",
+    "    xor edx, edx",
+    "    movzx eax, word ptr [rdi]",
+    "    ret",
+    "reactor_guarded_load_4:",
+    "    xor edx, edx",
+    "    mov eax, dword ptr [rdi]",
+    "    ret",
+    "reactor_guarded_load_8:",
+    "    xor edx, edx",
+    "    mov rax, qword ptr [rdi]",
+    "    ret",
+    "reactor_guarded_store_1:",
+    "    xor edx, edx",
+    "    mov byte ptr [rdi], sil",
+    "    ret",
+    "reactor_guarded_store_2:",
+    "    xor edx, edx",
+    "    mov word ptr [rdi], si",
+    "    ret",
+    "reactor_guarded_store_4:",
+    "    xor edx, edx",
+    "    mov dword ptr [rdi], esi",
+    "    ret",
+    "reactor_guarded_store_8:",
+    "    xor edx, edx",
+    "    mov qword ptr [rdi], rsi",
+    "    ret",
+    "reactor_guarded_end:",
+    "reactor_guarded_trap:",
+    "    mov edx, 1",
+    "    ret",
+);
+
+extern "C" {
+    fn reactor_guarded_start();
+    fn reactor_guarded_end();
+    fn reactor_guarded_trap();
+    fn reactor_guarded_load_1(address: *const u8) -> Guarded;
+    fn reactor_guarded_load_2(address: *const u8) -> Guarded;
+    fn reactor_guarded_load_4(address: *const u8) -> Guarded;
+    fn reactor_guarded_load_8(address: *const u8) -> Guarded;
+    fn reactor_guarded_store_1(address: *mut u8, value: u64) -> Guarded;
+    fn reactor_guarded_store_2(address: *mut u8, value: u64) -> Guarded;
+    fn reactor_guarded_store_4(address: *mut u8, value: u64) -> Guarded;
+    fn reactor_guarded_store_8(address: *mut u8, value: u64) -> Guarded;
+}
+
+/// The handler that was installed before ours, faults outside the guarded routines go there
+static PREVIOUS_HANDLER: OnceLock<libc::sigaction> = OnceLock::new();
+
+fn install_fault_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| unsafe {
+        let mut handler: libc::sigaction = std::mem::zeroed();
+        handler.sa_sigaction = handle_fault as *const () as usize;
+        // Keep running on the alternate stack, std uses it to report stack overflows
+        handler.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+        libc::sigemptyset(&mut handler.sa_mask);
+
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(libc::SIGSEGV, &handler, &mut previous) != 0 {
+            panic!(
+                "Failed to install the memory fault handler: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        PREVIOUS_HANDLER.set(previous).unwrap();
+    });
+}
+
+unsafe extern "C" fn handle_fault(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let context = &mut *(context as *mut libc::ucontext_t);
+    let pc = &mut context.uc_mcontext.gregs[libc::REG_RIP as usize];
+    let guarded =
+        reactor_guarded_start as *const () as usize..reactor_guarded_end as *const () as usize;
+    if guarded.contains(&(*pc as usize)) {
+        *pc = reactor_guarded_trap as *const () as i64;
+        return;
+    }
+
+    // Not one of ours, let whoever handled it before take over
+    let Some(previous) = PREVIOUS_HANDLER.get() else {
+        libc::signal(signal, libc::SIG_DFL);
+        return;
+    };
+    match previous.sa_sigaction {
+        libc::SIG_DFL | libc::SIG_IGN => {
+            // Returning faults again, this time with the previous disposition
+            libc::sigaction(signal, previous, std::ptr::null_mut());
+        }
+        action if previous.sa_flags & libc::SA_SIGINFO != 0 => {
+            let action: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                std::mem::transmute(action);
+            action(
+                signal,
+                info,
+                context as *mut libc::ucontext_t as *mut libc::c_void,
+            );
+        }
+        action => {
+            let action: extern "C" fn(libc::c_int) = std::mem::transmute(action);
+            action(signal);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MmapMemory;
+
+    const PAGE_SIZE: usize = 65536;
+
+    #[test]
+    fn grows_in_place() {
+        let mut memory = MmapMemory::new(PAGE_SIZE);
+        memory
+            .store(PAGE_SIZE - 4, 0x01020304u32.to_le_bytes())
+            .unwrap();
+        let base = memory.as_ptr();
+
+        assert!(memory.grow(PAGE_SIZE));
+        assert_eq!(memory.as_ptr(), base);
+        assert_eq!(memory.len(), 2 * PAGE_SIZE);
+        assert_eq!(
+            memory.load::<8>(PAGE_SIZE - 4).unwrap(),
+            [4, 3, 2, 1, 0, 0, 0, 0]
+        );
+        assert!(!memory.grow(usize::MAX));
+    }
+
+    #[test]
+    fn faults_become_traps() {
+        let mut memory = MmapMemory::new(PAGE_SIZE);
+        assert!(memory.load::<1>(PAGE_SIZE).is_err());
+        assert!(memory.load::<4>(PAGE_SIZE - 2).is_err());
+        assert!(memory.load::<8>(u32::MAX as usize * 2).is_err());
+        assert!(memory.store(PAGE_SIZE - 1, [1u8; 2]).is_err());
+        assert_eq!(memory[PAGE_SIZE - 1], 0);
+        memory.store(PAGE_SIZE - 1, [1u8]).unwrap();
+        assert_eq!(memory.load::<1>(PAGE_SIZE - 1).unwrap(), [1]);
+    }
+}
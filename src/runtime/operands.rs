@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+
+use crate::types::ValueType;
+
+use super::{
+    stack::Stack,
+    store::StoreId,
+    table::TableElementIdx,
+    value::{Ref, Value},
+};
+
+/// Where an instruction takes its operands from and puts its result. The stack engine pops
+/// and pushes, the register engine reads and writes the slots an operation names. Operands
+/// are taken from the last one to the first, like popping them. Both keep only the bits of a
+/// value, the instruction knows which type it takes
+pub trait Operands {
+    fn pop_bits(&mut self) -> u64;
+    fn push_bits(&mut self, bits: u64);
+
+    fn pop_value(&mut self, value_type: ValueType, store: StoreId) -> Value {
+        Value::from_bits(self.pop_bits(), value_type, store)
+    }
+
+    fn push_value(&mut self, value: Value) {
+        self.push_bits(value.to_bits())
+    }
+
+    fn pop_i32(&mut self) -> i32 {
+        self.pop_bits() as i32
+    }
+
+    fn push_i32(&mut self, value: i32) {
+        self.push_bits(value as u32 as u64)
+    }
+
+    fn pop_i64(&mut self) -> i64 {
+        self.pop_bits() as i64
+    }
+
+    fn push_i64(&mut self, value: i64) {
+        self.push_bits(value as u64)
+    }
+
+    fn pop_f32(&mut self) -> f32 {
+        f32::from_bits(self.pop_bits() as u32)
+    }
+
+    fn push_f32(&mut self, value: f32) {
+        self.push_bits(value.to_bits() as u64)
+    }
+
+    fn pop_f64(&mut self) -> f64 {
+        f64::from_bits(self.pop_bits())
+    }
+
+    fn push_f64(&mut self, value: f64) {
+        self.push_bits(value.to_bits())
+    }
+
+    fn pop_ref(&mut self, store: StoreId) -> Ref {
+        Ref::from_bits(self.pop_bits(), store)
+    }
+
+    fn push_ref(&mut self, value: Ref) {
+        self.push_bits(value.to_bits())
+    }
+
+    fn pop_u32(&mut self) -> u32 {
+        self.pop_i32() as u32
+    }
+
+    fn push_u32(&mut self, value: u32) {
+        self.push_i32(value as i32)
+    }
+
+    fn pop_u64(&mut self) -> u64 {
+        self.pop_bits()
+    }
+
+    fn push_u64(&mut self, value: u64) {
+        self.push_bits(value)
+    }
+
+    fn pop_bool(&mut self) -> bool {
+        self.pop_i32() != 0
+    }
+
+    fn push_bool(&mut self, value: bool) {
+        self.push_i32(value as i32)
+    }
+
+    fn pop_table_element_idx(&mut self) -> TableElementIdx {
+        TableElementIdx(self.pop_u32() as usize)
+    }
+}
+
+/// The operands of the stack engine, the stack is borrowed for each of them as instructions
+/// may call functions that use it as well
+pub struct StackOperands<'s>(pub &'s RefCell<Stack>);
+
+impl Operands for StackOperands<'_> {
+    fn pop_bits(&mut self) -> u64 {
+        self.0.borrow_mut().pop()
+    }
+
+    fn push_bits(&mut self, bits: u64) {
+        self.0.borrow_mut().push(bits)
+    }
+}
+
+/// The operands of an operation of the register engine, `slots` are the ones of the running
+/// function
+pub struct SlotOperands<'r> {
+    slots: &'r mut [u64],
+    inputs: &'r [u32],
+    result: u32,
+}
+
+impl<'r> SlotOperands<'r> {
+    pub fn new(slots: &'r mut [u64], inputs: &'r [u32], result: u32) -> Self {
+        Self {
+            slots,
+            inputs,
+            result,
+        }
+    }
+}
+
+impl Operands for SlotOperands<'_> {
+    fn pop_bits(&mut self) -> u64 {
+        let (last, inputs) = self
+            .inputs
+            .split_last()
+            .expect("Operations name all of their operands");
+        self.inputs = inputs;
+        self.slots[*last as usize]
+    }
+
+    fn push_bits(&mut self, bits: u64) {
+        self.slots[self.result as usize] = bits;
+    }
+}
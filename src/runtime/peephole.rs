@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::types::Instruction;
+
+use super::value::Value;
+
+/// A pattern the register engine replaces with fewer or cheaper operations while it
+/// translates a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Fusion {
+    /// An operation on constants computed during translation, its result is a constant too
+    ConstantFold,
+    /// `i32.add` or `i32.sub` with a constant operand, like `local.get; i32.const; i32.add`
+    AddImmediate,
+    /// `i32.load` straight from the slot of its address, like `local.get; i32.load offset`
+    Load,
+    /// `i32.store` straight from the slots of its address and value
+    Store,
+    /// `i32.eqz; br_if`
+    BranchIfZero,
+    /// An `i32` comparison followed by `br_if` or `if`
+    CompareBranch,
+}
+
+/// How often each fusion fired in the functions translated so far
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Fusions(BTreeMap<Fusion, usize>);
+
+impl Fusions {
+    pub fn count(&self, fusion: Fusion) -> usize {
+        self.0.get(&fusion).copied().unwrap_or(0)
+    }
+
+    /// The fusions that fired at least once, with how often
+    pub fn iter(&self) -> impl Iterator<Item = (Fusion, usize)> + '_ {
+        self.0.iter().map(|(fusion, count)| (*fusion, *count))
+    }
+
+    pub(super) fn record(&mut self, fusion: Fusion) {
+        *self.0.entry(fusion).or_default() += 1;
+    }
+
+    pub(super) fn add(&mut self, other: &Fusions) {
+        for (fusion, count) in other.iter() {
+            *self.0.entry(fusion).or_default() += count;
+        }
+    }
+}
+
+impl Display for Fusions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (fusion, count) in self.iter() {
+            writeln!(f, "{:?}: {}", fusion, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// An `i32` comparison that a branch can test directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    LtS,
+    LtU,
+    GtS,
+    GtU,
+    LeS,
+    LeU,
+    GeS,
+    GeU,
+}
+
+impl Comparison {
+    pub fn new(instruction: &Instruction) -> Option<Comparison> {
+        Some(match instruction {
+            Instruction::I32Eq => Comparison::Eq,
+            Instruction::I32Ne => Comparison::Ne,
+            Instruction::I32LtS => Comparison::LtS,
+            Instruction::I32LtU => Comparison::LtU,
+            Instruction::I32GtS => Comparison::GtS,
+            Instruction::I32GtU => Comparison::GtU,
+            Instruction::I32LeS => Comparison::LeS,
+            Instruction::I32LeU => Comparison::LeU,
+            Instruction::I32GeS => Comparison::GeS,
+            Instruction::I32GeU => Comparison::GeU,
+            _ => return None,
+        })
+    }
+
+    pub fn compare(self, a: i32, b: i32) -> bool {
+        let (ua, ub) = (a as u32, b as u32);
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::LtS => a < b,
+            Comparison::LtU => ua < ub,
+            Comparison::GtS => a > b,
+            Comparison::GtU => ua > ub,
+            Comparison::LeS => a <= b,
+            Comparison::LeU => ua <= ub,
+            Comparison::GeS => a >= b,
+            Comparison::GeU => ua >= ub,
+        }
+    }
+}
+
+/// Computes an integer instruction whose operands are all constants. Instructions that may
+/// trap or work on floats are left to run, so their results can't differ from the engines
+pub fn fold(instruction: &Instruction, operands: &[Value]) -> Option<Value> {
+    use Instruction::*;
+
+    let value = match (instruction, operands) {
+        (I32Eqz, [Value::I32(a)]) => Value::I32((*a == 0) as i32),
+        (I64Eqz, [Value::I64(a)]) => Value::I32((*a == 0) as i32),
+        (I32WrapI64, [Value::I64(a)]) => Value::I32(*a as i32),
+        (I64ExtendI32S, [Value::I32(a)]) => Value::I64(*a as i64),
+        (I64ExtendI32U, [Value::I32(a)]) => Value::I64(*a as u32 as i64),
+        (instruction, [Value::I32(a), Value::I32(b)]) => {
+            if let Some(comparison) = Comparison::new(instruction) {
+                return Some(Value::I32(comparison.compare(*a, *b) as i32));
+            }
+            Value::I32(match instruction {
+                I32Add => a.wrapping_add(*b),
+                I32Sub => a.wrapping_sub(*b),
+                I32Mul => a.wrapping_mul(*b),
+                I32And => a & b,
+                I32Or => a | b,
+                I32Xor => a ^ b,
+                I32Shl => a.wrapping_shl(*b as u32),
+                I32ShrS => a.wrapping_shr(*b as u32),
+                I32ShrU => (*a as u32).wrapping_shr(*b as u32) as i32,
+                _ => return None,
+            })
+        }
+        (instruction, [Value::I64(a), Value::I64(b)]) => Value::I64(match instruction {
+            I64Add => a.wrapping_add(*b),
+            I64Sub => a.wrapping_sub(*b),
+            I64Mul => a.wrapping_mul(*b),
+            I64And => a & b,
+            I64Or => a | b,
+            I64Xor => a ^ b,
+            I64Shl => a.wrapping_shl(*b as u32),
+            I64ShrS => a.wrapping_shr(*b as u32),
+            I64ShrU => (*a as u64).wrapping_shr(*b as u32) as i64,
+            _ => return None,
+        }),
+        _ => return None,
+    };
+    Some(value)
+}
@@ -0,0 +1,938 @@
+use std::{collections::HashMap, mem::Discriminant};
+
+use crate::{
+    module::{functions::LocalFunction, Module},
+    types::{
+        BlockIdx, Expr, FuncIdx, FuncTypeIdx, Instruction, MemoryArgument, TableIdx, ValueType,
+    },
+};
+
+use super::{
+    bytecode::stack_effect,
+    peephole::{fold, Comparison, Fusion, Fusions},
+    value::Value,
+};
+
+/// A slot of the running function: its locals come first, then the constants it uses, then
+/// one slot for each height the operand stack of the function can reach. Slots above the
+/// arguments of a call belong to the callee, constants can't be there
+pub type Slot = u32;
+
+/// Temporaries and constants get slots from these on while the function is translated, they
+/// are moved behind the locals once it's known how many constants there are
+const TEMPORARIES: Slot = 1 << 30;
+const CONSTANTS: Slot = 1 << 31;
+
+/// Copies `count` slots starting at `from` to the ones starting at `to`, how a branch takes
+/// the values of its label along
+#[derive(Debug, Clone, Copy)]
+pub struct Moves {
+    pub from: Slot,
+    pub to: Slot,
+    pub count: u32,
+}
+
+impl Moves {
+    fn slots_mut(&mut self) -> Vec<&mut Slot> {
+        vec![&mut self.from, &mut self.to]
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Label {
+    pub target: usize,
+    pub moves: Moves,
+}
+
+/// An operation of the register engine. Instead of pushing and popping, operations name the
+/// slots they read and write
+#[derive(Debug)]
+pub enum Op<'b> {
+    /// Runs an instruction on `inputs`, its result goes to `result` if it has one
+    Execute {
+        instruction: &'b Instruction,
+        inputs: [Slot; 3],
+        amount_of_inputs: u8,
+        result: Slot,
+    },
+    /// `i32.add` of a slot and a constant
+    I32AddImmediate {
+        a: Slot,
+        immediate: i32,
+        result: Slot,
+    },
+    I32Load {
+        address: Slot,
+        memarg: MemoryArgument,
+        result: Slot,
+    },
+    I32Store {
+        address: Slot,
+        value: Slot,
+        memarg: MemoryArgument,
+    },
+    Copy {
+        from: Slot,
+        to: Slot,
+    },
+    Jump(usize),
+    Br(Label),
+    BrIf {
+        condition: Slot,
+        label: Label,
+    },
+    BrUnless {
+        condition: Slot,
+        target: usize,
+    },
+    /// Branches if the condition is zero, which saves computing `i32.eqz` first
+    BrIfEqz {
+        condition: Slot,
+        label: Label,
+    },
+    /// Branches if the comparison of `a` and `b` holds
+    BrIfCompare {
+        comparison: Comparison,
+        a: Slot,
+        b: Slot,
+        label: Label,
+    },
+    /// Skips the first arm of an `if` unless the comparison of `a` and `b` holds
+    BrUnlessCompare {
+        comparison: Comparison,
+        a: Slot,
+        b: Slot,
+        target: usize,
+    },
+    BrTable {
+        index: Slot,
+        labels: Box<[Label]>,
+        default: Label,
+    },
+    /// The callee's frame starts at `args`, where its params already are and its results
+    /// end up
+    Call {
+        func_idx: FuncIdx,
+        args: Slot,
+    },
+    CallIndirect {
+        type_idx: FuncTypeIdx,
+        table_idx: TableIdx,
+        index: Slot,
+        args: Slot,
+    },
+    /// Moves the results to the first slots of the frame and returns to the caller
+    Return {
+        results: Slot,
+        amount: u32,
+    },
+    /// Only charges fuel, for instructions that didn't need an operation like a `drop` at
+    /// the end of a block
+    Fuel,
+}
+
+impl Op<'_> {
+    /// The slot the operation writes its result to, if it has one
+    fn result_mut(&mut self) -> Option<&mut Slot> {
+        match self {
+            Op::Execute { result, .. }
+            | Op::I32AddImmediate { result, .. }
+            | Op::I32Load { result, .. } => Some(result),
+            _ => None,
+        }
+    }
+
+    /// All slots the operation names
+    fn slots_mut(&mut self) -> Vec<&mut Slot> {
+        match self {
+            Op::Execute { inputs, result, .. } => inputs.iter_mut().chain([result]).collect(),
+            Op::I32AddImmediate { a, result, .. } => vec![a, result],
+            Op::I32Load {
+                address, result, ..
+            } => vec![address, result],
+            Op::I32Store { address, value, .. } => vec![address, value],
+            Op::Copy { from, to } => vec![from, to],
+            Op::Br(label) => label.moves.slots_mut(),
+            Op::BrIf { condition, label } | Op::BrIfEqz { condition, label } => {
+                let mut slots = label.moves.slots_mut();
+                slots.push(condition);
+                slots
+            }
+            Op::BrIfCompare { a, b, label, .. } => {
+                let mut slots = label.moves.slots_mut();
+                slots.extend([a, b]);
+                slots
+            }
+            Op::BrUnless { condition, .. } => vec![condition],
+            Op::BrUnlessCompare { a, b, .. } => vec![a, b],
+            Op::BrTable {
+                index,
+                labels,
+                default,
+            } => labels
+                .iter_mut()
+                .chain([default])
+                .flat_map(|label| label.moves.slots_mut())
+                .chain([index])
+                .collect(),
+            Op::Call { args, .. } => vec![args],
+            Op::CallIndirect { index, args, .. } => vec![index, args],
+            Op::Return { results, .. } => vec![results],
+            Op::Jump(_) | Op::Fuel => vec![],
+        }
+    }
+}
+
+/// The body of a local function translated for the register engine
+#[derive(Debug)]
+pub struct RegisterCode<'b> {
+    pub ops: Vec<Op<'b>>,
+    /// The instructions each operation is charged fuel for, by operation
+    charges: Vec<(u32, u32)>,
+    sources: Vec<&'b Instruction>,
+    pub amount_of_params: usize,
+    pub returns: &'b [ValueType],
+    /// Initial values of the locals that aren't params, followed by the constants the
+    /// operations use
+    pub locals: Vec<u64>,
+    pub frame_size: usize,
+    /// The fusions that fired while translating
+    pub fusions: Fusions,
+}
+
+impl<'b> RegisterCode<'b> {
+    /// Translates the body of a validated function
+    pub fn compile(module: &'b Module<'b>, function: &'b LocalFunction) -> RegisterCode<'b> {
+        let expr = &function.code.instructions;
+        let mut locals = function
+            .code
+            .locals
+            .0
+            .iter()
+            .map(|value_type| Value::default(*value_type).to_bits())
+            .collect::<Vec<_>>();
+        let amount_of_params = function.signature.params.len();
+        let mut compiler = Compiler {
+            module,
+            expr,
+            amount_of_locals: amount_of_params + locals.len(),
+            amount_of_results: function.signature.returns.len(),
+            stack: vec![],
+            max_height: 0,
+            constants: vec![],
+            constant_slots: HashMap::new(),
+            fusions: Fusions::default(),
+            ops: vec![],
+            charges: vec![],
+            sources: vec![],
+            pending_sources: 0,
+            last_result: None,
+            labels: vec![None; expr.amount_of_blocks()],
+            targets: vec![usize::MAX; expr.amount_of_blocks() + 1],
+            function_label: LabelInfo {
+                id: expr.amount_of_blocks(),
+                base: 0,
+                arity: function.signature.returns.len(),
+            },
+            branches_to_function: false,
+        };
+        if compiler.compile_instructions(expr.instructions()) {
+            compiler.compile_return();
+        }
+        if compiler.branches_to_function {
+            // Branches to the function body leave its results at the bottom of the stack
+            compiler.place_label(compiler.function_label.id);
+            compiler.reset_stack(0, compiler.amount_of_results);
+            compiler.compile_return();
+        }
+
+        locals.extend(compiler.constants.iter().map(|value| value.to_bits()));
+        RegisterCode {
+            frame_size: compiler.amount_of_locals + compiler.constants.len() + compiler.max_height,
+            charges: compiler.charges.clone(),
+            sources: compiler.sources.clone(),
+            fusions: compiler.fusions.clone(),
+            ops: compiler.resolve_slots(),
+            amount_of_params,
+            returns: &function.signature.returns,
+            locals,
+        }
+    }
+
+    /// The instructions the operation at `pc` was translated from, which it is charged fuel for
+    pub fn charge(&self, pc: usize) -> &[&'b Instruction] {
+        let (start, end) = self.charges[pc];
+        &self.sources[start as usize..end as usize]
+    }
+}
+
+/// A function running in the register engine
+#[derive(Debug, Clone, Copy)]
+pub struct Frame {
+    pub func_idx: FuncIdx,
+    /// Where the function continues once the function it called returns
+    pub pc: usize,
+    /// How many of the instructions the operation at `pc` is charged for were paid already,
+    /// if the fuel ran out in between
+    pub charged: usize,
+    /// Where the slots of the function start
+    pub fp: usize,
+}
+
+/// The slots of all functions running in the register engine, and the functions themselves
+#[derive(Debug, Default)]
+pub struct Registers {
+    pub slots: Vec<u64>,
+    pub frames: Vec<Frame>,
+}
+
+impl Registers {
+    /// Makes room for the frame of a function starting at `fp` and initializes its locals,
+    /// the params have to be in place already
+    pub fn enter(&mut self, fp: usize, code: &RegisterCode) {
+        let end = fp + code.frame_size;
+        if self.slots.len() < end {
+            self.slots.resize(end, 0);
+        }
+        let locals = fp + code.amount_of_params;
+        self.slots[locals..locals + code.locals.len()].copy_from_slice(&code.locals);
+    }
+}
+
+/// Where a branch to a block continues while the body is compiled. The id stands in for the
+/// target until it is known, like in [`bytecode`](super::bytecode)
+#[derive(Debug, Clone, Copy)]
+struct LabelInfo {
+    id: usize,
+    /// Height of the operand stack below the params of the block
+    base: usize,
+    arity: usize,
+}
+
+struct Compiler<'b> {
+    module: &'b Module<'b>,
+    expr: &'b Expr,
+    amount_of_locals: usize,
+    amount_of_results: usize,
+    /// The operand stack while compiling, with the slot each value is in. Values are in the
+    /// slot for their height, except for locals and constants that weren't copied yet
+    stack: Vec<Slot>,
+    max_height: usize,
+    constants: Vec<Value>,
+    /// The slot of each constant, by its type and bits
+    constant_slots: HashMap<(Discriminant<Value>, u64), Slot>,
+    fusions: Fusions,
+    ops: Vec<Op<'b>>,
+    charges: Vec<(u32, u32)>,
+    sources: Vec<&'b Instruction>,
+    /// Sources from here on aren't charged by any operation yet
+    pending_sources: usize,
+    /// The last operation, if it computed the value on top of the stack. Its result can go
+    /// straight into a local instead
+    last_result: Option<usize>,
+    labels: Vec<Option<LabelInfo>>,
+    targets: Vec<usize>,
+    /// The label of the function body, one past the last block
+    function_label: LabelInfo,
+    /// Whether a branch targets the function body, which then needs a return of its own
+    branches_to_function: bool,
+}
+
+impl<'b> Compiler<'b> {
+    /// Returns whether execution can continue after the instructions, the ones after an
+    /// instruction that never falls through are dead and left out
+    fn compile_instructions(&mut self, instructions: &'b [Instruction]) -> bool {
+        instructions
+            .iter()
+            .all(|instruction| self.compile_instruction(instruction))
+    }
+
+    fn compile_instruction(&mut self, instruction: &'b Instruction) -> bool {
+        // Blocks and loops are free, only the instructions in them are charged
+        if !matches!(instruction, Instruction::Block(_) | Instruction::Loop(_)) {
+            self.sources.push(instruction);
+        }
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::Block(block) | Instruction::Loop(block) => {
+                let is_loop = matches!(instruction, Instruction::Loop(_));
+                let (params, results) = self.block_signature(*block);
+                self.canonicalize();
+                let base = self.stack.len() - params;
+                self.labels[block.0] = Some(LabelInfo {
+                    id: block.0,
+                    base,
+                    arity: if is_loop { params } else { results },
+                });
+
+                if is_loop {
+                    self.place_label(block.0);
+                }
+                if self.compile_instructions(self.expr.block_instructions(*block)) {
+                    self.canonicalize();
+                }
+                if !is_loop {
+                    self.place_label(block.0);
+                }
+                self.reset_stack(base, results);
+            }
+            Instruction::If { if_expr, else_expr } => {
+                let condition = self.pop();
+                let tested = self.take_test(condition);
+                let (params, results) = self.block_signature(*if_expr);
+                self.canonicalize();
+                let base = self.stack.len() - params;
+                let label = LabelInfo {
+                    id: if_expr.0,
+                    base,
+                    arity: results,
+                };
+                self.labels[if_expr.0] = Some(label);
+                self.labels[else_expr.0] = Some(label);
+
+                let target = else_expr.0;
+                let op = match tested {
+                    Some((Instruction::I32Eqz, [value, ..])) => {
+                        self.fusions.record(Fusion::BranchIfZero);
+                        let moves = Moves {
+                            from: 0,
+                            to: 0,
+                            count: 0,
+                        };
+                        Op::BrIf {
+                            condition: value,
+                            label: Label { target, moves },
+                        }
+                    }
+                    Some((instruction, [a, b, _])) => {
+                        self.fusions.record(Fusion::CompareBranch);
+                        let comparison = Comparison::new(instruction).unwrap();
+                        Op::BrUnlessCompare {
+                            comparison,
+                            a,
+                            b,
+                            target,
+                        }
+                    }
+                    None => Op::BrUnless { condition, target },
+                };
+                self.emit(op);
+                if self.compile_instructions(self.expr.block_instructions(*if_expr)) {
+                    self.canonicalize();
+                    if !self.expr.block_instructions(*else_expr).is_empty() {
+                        self.emit(Op::Jump(if_expr.0));
+                    }
+                }
+                self.place_label(else_expr.0);
+                self.reset_stack(base, params);
+                if self.compile_instructions(self.expr.block_instructions(*else_expr)) {
+                    self.canonicalize();
+                }
+                self.place_label(if_expr.0);
+                self.reset_stack(base, results);
+            }
+            Instruction::Break(block) => {
+                self.canonicalize();
+                let label = self.label(*block);
+                self.emit(Op::Br(label));
+                return false;
+            }
+            Instruction::BreakIf(block) => {
+                let condition = self.pop();
+                let tested = self.take_test(condition);
+                self.canonicalize();
+                let label = self.label(*block);
+                let op = match tested {
+                    Some((Instruction::I32Eqz, [value, ..])) => {
+                        self.fusions.record(Fusion::BranchIfZero);
+                        Op::BrIfEqz {
+                            condition: value,
+                            label,
+                        }
+                    }
+                    Some((instruction, [a, b, _])) => {
+                        self.fusions.record(Fusion::CompareBranch);
+                        let comparison = Comparison::new(instruction).unwrap();
+                        Op::BrIfCompare {
+                            comparison,
+                            a,
+                            b,
+                            label,
+                        }
+                    }
+                    None => Op::BrIf { condition, label },
+                };
+                self.emit(op);
+            }
+            Instruction::BreakTable { labels, default } => {
+                let index = self.pop();
+                self.canonicalize();
+                let labels = labels.iter().map(|block| self.label(*block)).collect();
+                let default = self.label(*default);
+                self.emit(Op::BrTable {
+                    index,
+                    labels,
+                    default,
+                });
+                return false;
+            }
+            Instruction::Return => {
+                self.compile_return();
+                return false;
+            }
+            Instruction::Unreachable => {
+                self.execute(instruction, 0, 0);
+                return false;
+            }
+
+            Instruction::Call(func_idx) => {
+                let (params, results) = stack_effect(self.module, instruction);
+                self.canonicalize();
+                let args = self.temp(self.stack.len() - params);
+                self.emit(Op::Call {
+                    func_idx: *func_idx,
+                    args,
+                });
+                self.reset_stack(self.stack.len() - params, results);
+            }
+            Instruction::CallIndirect(type_idx, table_idx) => {
+                let (params, results) = stack_effect(self.module, instruction);
+                let index = self.pop();
+                self.canonicalize();
+                let base = self.stack.len() - (params - 1);
+                self.emit(Op::CallIndirect {
+                    type_idx: *type_idx,
+                    table_idx: *table_idx,
+                    index,
+                    args: self.temp(base),
+                });
+                self.reset_stack(base, results);
+            }
+
+            Instruction::Drop => {
+                self.pop();
+            }
+            Instruction::LocalGet(idx) => {
+                self.push(idx.0);
+            }
+            Instruction::I32Const(value) => self.push_constant(Value::I32(*value)),
+            Instruction::I64Const(value) => self.push_constant(Value::I64(*value)),
+            Instruction::F32Const(value) => self.push_constant(Value::F32(*value)),
+            Instruction::F64Const(value) => self.push_constant(Value::F64(*value)),
+            Instruction::LocalSet(idx) => {
+                let value = self.pop();
+                self.set_local(idx.0, value);
+            }
+            Instruction::LocalTee(idx) => {
+                let value = self.pop();
+                self.set_local(idx.0, value);
+                self.push(idx.0);
+            }
+
+            _ => {
+                let (pops, pushes) = stack_effect(self.module, instruction);
+                self.execute(instruction, pops, pushes);
+            }
+        }
+        true
+    }
+
+    fn temp(&self, height: usize) -> Slot {
+        TEMPORARIES + height as Slot
+    }
+
+    fn push(&mut self, slot: Slot) {
+        self.stack.push(slot);
+        self.max_height = self.max_height.max(self.stack.len());
+    }
+
+    fn push_temp(&mut self) -> Slot {
+        let slot = self.temp(self.stack.len());
+        self.push(slot);
+        slot
+    }
+
+    fn pop(&mut self) -> Slot {
+        self.stack.pop().expect("Operands were validated")
+    }
+
+    /// Leaves `amount` values in their slots above `base`, which is where the values of a
+    /// block are at its end
+    fn reset_stack(&mut self, base: usize, amount: usize) {
+        self.stack.truncate(base);
+        for _ in 0..amount {
+            self.push_temp();
+        }
+    }
+
+    fn emit(&mut self, op: Op<'b>) {
+        self.ops.push(op);
+        self.charges
+            .push((self.pending_sources as u32, self.sources.len() as u32));
+        self.pending_sources = self.sources.len();
+        self.last_result = None;
+    }
+
+    fn execute(&mut self, instruction: &'b Instruction, pops: usize, pushes: usize) {
+        let mut inputs = [0; 3];
+        let first_input = self.stack.len() - pops;
+        inputs[..pops].copy_from_slice(&self.stack[first_input..]);
+        self.stack.truncate(first_input);
+
+        let constants = inputs[..pops]
+            .iter()
+            .map(|input| self.constant_value(*input))
+            .collect::<Option<Vec<_>>>();
+        if let Some(value) = constants.and_then(|constants| fold(instruction, &constants)) {
+            // The instruction is still charged, along with the next operation
+            self.fusions.record(Fusion::ConstantFold);
+            self.push_constant(value);
+            return;
+        }
+
+        let result = if pushes == 1 { self.push_temp() } else { 0 };
+        let op = match (instruction, inputs) {
+            (Instruction::I32Add | Instruction::I32Sub, [a, b, _]) => {
+                let immediate = match (instruction, self.constant_value(a), self.constant_value(b))
+                {
+                    (Instruction::I32Sub, _, Some(Value::I32(b))) => Some((a, b.wrapping_neg())),
+                    (Instruction::I32Add, _, Some(Value::I32(b))) => Some((a, b)),
+                    (Instruction::I32Add, Some(Value::I32(a)), _) => Some((b, a)),
+                    _ => None,
+                };
+                match immediate {
+                    Some((a, immediate)) => {
+                        self.fusions.record(Fusion::AddImmediate);
+                        Op::I32AddImmediate {
+                            a,
+                            immediate,
+                            result,
+                        }
+                    }
+                    None => Op::Execute {
+                        instruction,
+                        inputs,
+                        amount_of_inputs: 2,
+                        result,
+                    },
+                }
+            }
+            (Instruction::I32Load(memarg), [address, ..]) => {
+                self.fusions.record(Fusion::Load);
+                Op::I32Load {
+                    address,
+                    memarg: *memarg,
+                    result,
+                }
+            }
+            (Instruction::I32Store(memarg), [address, value, _]) => {
+                self.fusions.record(Fusion::Store);
+                Op::I32Store {
+                    address,
+                    value,
+                    memarg: *memarg,
+                }
+            }
+            _ => Op::Execute {
+                instruction,
+                inputs,
+                amount_of_inputs: pops as u8,
+                result,
+            },
+        };
+
+        self.emit(op);
+        if pushes == 1 {
+            self.last_result = Some(self.ops.len() - 1);
+        }
+    }
+
+    fn push_constant(&mut self, value: Value) {
+        let slot = *self
+            .constant_slots
+            .entry((std::mem::discriminant(&value), value.to_bits()))
+            .or_insert_with(|| {
+                self.constants.push(value);
+                CONSTANTS + self.constants.len() as Slot - 1
+            });
+        self.push(slot);
+    }
+
+    fn constant_value(&self, slot: Slot) -> Option<Value> {
+        let idx = slot.checked_sub(CONSTANTS)?;
+        Some(self.constants[idx as usize])
+    }
+
+    /// Takes back the operation that computed the condition of a branch if the branch can
+    /// test it directly: `i32.eqz` or an `i32` comparison. Returns the instruction and its
+    /// inputs
+    fn take_test(&mut self, condition: Slot) -> Option<(&'b Instruction, [Slot; 3])> {
+        let last_result = self.last_result?;
+        let Some(&Op::Execute {
+            instruction,
+            inputs,
+            result,
+            ..
+        }) = self.ops.get(last_result)
+        else {
+            return None;
+        };
+        if result != condition
+            || !(matches!(instruction, Instruction::I32Eqz)
+                || Comparison::new(instruction).is_some())
+        {
+            return None;
+        }
+
+        self.ops.pop();
+        let (start, _) = self.charges.pop().unwrap();
+        // Charged by the branch instead
+        self.pending_sources = start as usize;
+        self.last_result = None;
+        Some((instruction, inputs))
+    }
+
+    /// Copies every value that is still in a local into the slot for its height. Where
+    /// control flow meets, all values have to be where the other paths put them
+    fn canonicalize(&mut self) {
+        for height in 0..self.stack.len() {
+            let temp = self.temp(height);
+            if self.stack[height] != temp {
+                self.emit(Op::Copy {
+                    from: self.stack[height],
+                    to: temp,
+                });
+                self.stack[height] = temp;
+            }
+        }
+    }
+
+    fn set_local(&mut self, local: Slot, value: Slot) {
+        // Values pushed from the local before have to keep the value it has now
+        for height in 0..self.stack.len() {
+            if self.stack[height] == local {
+                let temp = self.temp(height);
+                self.emit(Op::Copy {
+                    from: local,
+                    to: temp,
+                });
+                self.stack[height] = temp;
+            }
+        }
+
+        if value == local {
+            return;
+        }
+        if let Some(result) = self
+            .last_result
+            .and_then(|last_result| self.ops.get_mut(last_result))
+            .and_then(Op::result_mut)
+        {
+            if *result == value {
+                *result = local;
+                self.last_result = None;
+                return;
+            }
+        }
+        self.emit(Op::Copy {
+            from: value,
+            to: local,
+        });
+    }
+
+    fn compile_return(&mut self) {
+        self.canonicalize();
+        let results = self.stack.len() - self.amount_of_results;
+        self.emit(Op::Return {
+            results: self.temp(results),
+            amount: self.amount_of_results as u32,
+        });
+    }
+
+    /// Execution continues here for the label, the sources that aren't charged yet are
+    /// charged before as they must not be charged again by a loop
+    fn place_label(&mut self, id: usize) {
+        if self.pending_sources < self.sources.len() {
+            self.emit(Op::Fuel);
+        }
+        self.targets[id] = self.ops.len();
+        self.last_result = None;
+    }
+
+    /// A branch from the current height to the label of the block, all values have to be
+    /// in the slots for their height
+    fn label(&mut self, block: BlockIdx) -> Label {
+        let label = if block == BlockIdx::FUNCTION {
+            self.branches_to_function = true;
+            self.function_label
+        } else {
+            self.labels[block.0].expect("Branches only target blocks they are in")
+        };
+        let from = self.stack.len() - label.arity;
+        let count = if from == label.base { 0 } else { label.arity };
+        Label {
+            target: label.id,
+            moves: Moves {
+                from: self.temp(from),
+                to: self.temp(label.base),
+                count: count as u32,
+            },
+        }
+    }
+
+    fn block_signature(&self, block: BlockIdx) -> (usize, usize) {
+        let block_type = self.expr.get_block_type(block);
+        let (params, results) = self
+            .module
+            .function_types()
+            .block_signature(&block_type)
+            .expect("Block types were validated");
+        (params.len(), results.len())
+    }
+
+    /// Replaces the label ids of the branches with the positions they continue at, and the
+    /// slots of constants and temporaries with their place in the frame
+    fn resolve_slots(self) -> Vec<Op<'b>> {
+        let targets = self.targets;
+        let resolve = |label: &mut Label| label.target = targets[label.target];
+        let constants = self.amount_of_locals as Slot;
+        let temporaries = constants + self.constants.len() as Slot;
+
+        let mut ops = self.ops;
+        for op in &mut ops {
+            for slot in op.slots_mut() {
+                if *slot >= CONSTANTS {
+                    *slot = constants + (*slot - CONSTANTS);
+                } else if *slot >= TEMPORARIES {
+                    *slot = temporaries + (*slot - TEMPORARIES);
+                }
+            }
+
+            match op {
+                Op::Jump(target)
+                | Op::BrUnless { target, .. }
+                | Op::BrUnlessCompare { target, .. } => *target = targets[*target],
+                Op::Br(label)
+                | Op::BrIf { label, .. }
+                | Op::BrIfEqz { label, .. }
+                | Op::BrIfCompare { label, .. } => resolve(label),
+                Op::BrTable {
+                    labels, default, ..
+                } => {
+                    labels.iter_mut().for_each(&resolve);
+                    resolve(default);
+                }
+                Op::Execute { .. }
+                | Op::I32AddImmediate { .. }
+                | Op::I32Load { .. }
+                | Op::I32Store { .. }
+                | Op::Copy { .. }
+                | Op::Call { .. }
+                | Op::CallIndirect { .. }
+                | Op::Return { .. }
+                | Op::Fuel => {}
+            }
+        }
+        ops
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        module::Module,
+        runtime::test::{compile, local_function},
+        types::FuncIdx,
+    };
+
+    use super::{Comparison, Fusion, Op, RegisterCode};
+
+    #[test]
+    fn locals_are_operands() {
+        let binary = compile(
+            r#"(module
+                (func (param i32 i32) (result i32) (local i32)
+                    (local.set 2 (i32.add (local.get 0) (local.get 1)))
+                    (local.get 2)))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let function = local_function(&module, FuncIdx(0));
+        let code = RegisterCode::compile(&module, function);
+
+        assert!(matches!(
+            code.ops[..],
+            [
+                Op::Execute {
+                    inputs: [0, 1, _],
+                    amount_of_inputs: 2,
+                    result: 2,
+                    ..
+                },
+                Op::Copy { from: 2, to: 3 },
+                Op::Return {
+                    results: 3,
+                    amount: 1
+                },
+            ]
+        ));
+        assert_eq!(code.frame_size, 5);
+        assert_eq!(code.charge(0).len(), 3);
+    }
+
+    #[test]
+    fn patterns_are_fused() {
+        let binary = compile(
+            r#"(module
+                (memory 1)
+                (func (param i32)
+                    (loop
+                        (i32.store (local.get 0) (i32.load offset=4 (local.get 0)))
+                        (local.set 0 (i32.add (local.get 0) (i32.add (i32.const 2) (i32.const 2))))
+                        (br_if 0 (i32.eqz (local.get 0)))
+                        (br_if 0 (i32.lt_u (local.get 0) (i32.const 64))))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let function = local_function(&module, FuncIdx(0));
+        let code = RegisterCode::compile(&module, function);
+
+        // The constants 2, 4 and 64 come after the param, then the temporaries
+        assert!(matches!(
+            code.ops[..],
+            [
+                Op::I32Load {
+                    address: 0,
+                    result: 5,
+                    ..
+                },
+                Op::I32Store {
+                    address: 0,
+                    value: 5,
+                    ..
+                },
+                Op::I32AddImmediate {
+                    a: 0,
+                    immediate: 4,
+                    result: 0
+                },
+                Op::BrIfEqz { condition: 0, .. },
+                Op::BrIfCompare {
+                    comparison: Comparison::LtU,
+                    a: 0,
+                    b: 3,
+                    ..
+                },
+                Op::Return { amount: 0, .. },
+            ]
+        ));
+        for (fusion, count) in [
+            (Fusion::ConstantFold, 1),
+            (Fusion::AddImmediate, 1),
+            (Fusion::Load, 1),
+            (Fusion::Store, 1),
+            (Fusion::BranchIfZero, 1),
+            (Fusion::CompareBranch, 1),
+        ] {
+            assert_eq!(code.fusions.count(fusion), count, "{:?}", fusion);
+        }
+    }
+}
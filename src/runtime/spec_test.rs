@@ -0,0 +1,636 @@
+//! Runs `.wast` scripts in the format of the WebAssembly spec testsuite against the library API
+
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+};
+
+use wast::{
+    core::{AbstractHeapType, HeapType, NanPattern, WastArgCore, WastRetCore},
+    lexer::Lexer,
+    parser::{self, ParseBuffer},
+    token::{Id, Index},
+    Wast, WastArg, WastDirective, WastExecute, WastInvoke, WastRet,
+};
+
+use crate::{
+    module::Module,
+    parse_error::{ParseError, ParseErrorKind},
+    types::{
+        FuncIdx, FuncType, Limit, Mutability, NumericValueType, RefType, TableType, ValueType,
+    },
+    validate::validate,
+};
+
+use super::{
+    value::{FuncRef, Ref},
+    Config, Engine, GlobalHandle, Instance, InstantiationError, Linker, MemoryHandle, Store,
+    TableHandle, Trap, Value,
+};
+
+/// A module of the script after going through the text and the binary parser
+enum ScriptModule<'b> {
+    MalformedText(String),
+    Malformed(ParseError),
+    Parsed(Module<'b>),
+}
+
+impl<'b> ScriptModule<'b> {
+    fn parsed(&self) -> Result<&Module<'b>, String> {
+        match self {
+            ScriptModule::MalformedText(err) => Err(format!("malformed text: {}", err)),
+            ScriptModule::Malformed(err) => Err(format!("malformed binary: {}", err)),
+            ScriptModule::Parsed(module) => Ok(module),
+        }
+    }
+}
+
+/// Encodes the module each directive carries, has to happen before any module is parsed since
+/// `Module` borrows its binary
+fn encode_modules(directives: &mut [WastDirective]) -> Vec<Option<Result<Vec<u8>, String>>> {
+    directives
+        .iter_mut()
+        .map(|directive| {
+            let binary = match directive {
+                WastDirective::Module(module)
+                | WastDirective::ModuleDefinition(module)
+                | WastDirective::AssertMalformed { module, .. }
+                | WastDirective::AssertInvalid { module, .. } => module.encode(),
+                WastDirective::AssertUnlinkable { module, .. }
+                | WastDirective::AssertReturn {
+                    exec: WastExecute::Wat(module),
+                    ..
+                }
+                | WastDirective::AssertTrap {
+                    exec: WastExecute::Wat(module),
+                    ..
+                } => module.encode(),
+                _ => return None,
+            };
+            Some(binary.map_err(|err| err.message()))
+        })
+        .collect()
+}
+
+fn directive_name(directive: &WastDirective) -> &'static str {
+    match directive {
+        WastDirective::Module(_) => "module",
+        WastDirective::ModuleDefinition(_) => "module definition",
+        WastDirective::ModuleInstance { .. } => "module instance",
+        WastDirective::AssertMalformed { .. } => "assert_malformed",
+        WastDirective::AssertInvalid { .. } => "assert_invalid",
+        WastDirective::Register { .. } => "register",
+        WastDirective::Invoke(_) => "invoke",
+        WastDirective::AssertTrap { .. } => "assert_trap",
+        WastDirective::AssertReturn { .. } => "assert_return",
+        WastDirective::AssertExhaustion { .. } => "assert_exhaustion",
+        WastDirective::AssertUnlinkable { .. } => "assert_unlinkable",
+        WastDirective::AssertException { .. } => "assert_exception",
+        WastDirective::AssertSuspension { .. } => "assert_suspension",
+        WastDirective::Thread(_) => "thread",
+        WastDirective::Wait { .. } => "wait",
+    }
+}
+
+fn ref_type(heap_type: &HeapType) -> Option<RefType> {
+    match heap_type {
+        HeapType::Abstract {
+            shared: false,
+            ty: AbstractHeapType::Func,
+        } => Some(RefType::FuncRef),
+        HeapType::Abstract {
+            shared: false,
+            ty: AbstractHeapType::Extern,
+        } => Some(RefType::ExternRef),
+        _ => None,
+    }
+}
+
+/// `(ref.extern n)` arguments become host objects holding `n`
+fn arg_value(arg: &WastArg, store: &Store) -> Result<Value, String> {
+    match arg {
+        WastArg::Core(WastArgCore::I32(value)) => Ok(Value::I32(*value)),
+        WastArg::Core(WastArgCore::I64(value)) => Ok(Value::I64(*value)),
+        WastArg::Core(WastArgCore::F32(value)) => Ok(Value::F32(f32::from_bits(value.bits))),
+        WastArg::Core(WastArgCore::F64(value)) => Ok(Value::F64(f64::from_bits(value.bits))),
+        WastArg::Core(WastArgCore::RefNull(heap_type)) => ref_type(heap_type)
+            .map(|ref_type| Value::Ref(Ref::Null(ref_type)))
+            .ok_or_else(|| format!("unsupported heap type {:?}", heap_type)),
+        WastArg::Core(WastArgCore::RefExtern(value)) => {
+            Ok(Value::Ref(Ref::Extern(store.extern_ref(*value))))
+        }
+        arg => Err(format!("unsupported argument {:?}", arg)),
+    }
+}
+
+fn nan_pattern_matches<T>(
+    pattern: &NanPattern<T>,
+    bits: u64,
+    exponent_and_quiet_bit: u64,
+    value_bits: impl Fn(&T) -> u64,
+) -> bool {
+    let sign_bit = (exponent_and_quiet_bit << 1) & !exponent_and_quiet_bit;
+    match pattern {
+        NanPattern::CanonicalNan => bits & !sign_bit == exponent_and_quiet_bit,
+        NanPattern::ArithmeticNan => bits & exponent_and_quiet_bit == exponent_and_quiet_bit,
+        NanPattern::Value(value) => value_bits(value) == bits,
+    }
+}
+
+fn result_matches(expected: &WastRet, actual: &Value, store: &Store) -> bool {
+    let WastRet::Core(expected) = expected else {
+        return false;
+    };
+    match (expected, actual) {
+        (WastRetCore::I32(expected), Value::I32(actual)) => expected == actual,
+        (WastRetCore::I64(expected), Value::I64(actual)) => expected == actual,
+        (WastRetCore::F32(expected), Value::F32(actual)) => {
+            nan_pattern_matches(expected, actual.to_bits() as u64, 0x7fc0_0000, |value| {
+                value.bits as u64
+            })
+        }
+        (WastRetCore::F64(expected), Value::F64(actual)) => {
+            nan_pattern_matches(expected, actual.to_bits(), 0x7ff8_0000_0000_0000, |value| {
+                value.bits
+            })
+        }
+        (WastRetCore::RefNull(None), Value::Ref(Ref::Null(_))) => true,
+        (WastRetCore::RefNull(Some(heap_type)), Value::Ref(Ref::Null(actual))) => {
+            ref_type(heap_type) == Some(*actual)
+        }
+        (WastRetCore::RefFunc(None), Value::Ref(Ref::Func(_))) => true,
+        (
+            WastRetCore::RefFunc(Some(Index::Num(expected, _))),
+            Value::Ref(Ref::Func(FuncRef {
+                func_idx: FuncIdx(actual),
+                ..
+            })),
+        ) => expected == actual,
+        (WastRetCore::RefExtern(None), Value::Ref(Ref::Extern(_))) => true,
+        (WastRetCore::RefExtern(Some(expected)), Value::Ref(Ref::Extern(actual))) => store
+            .extern_object::<u32>(*actual)
+            .is_some_and(|actual| *actual == *expected),
+        _ => false,
+    }
+}
+
+fn expect_trap(result: Result<Vec<Value>, Trap>, message: &str) -> Result<(), String> {
+    match result {
+        Ok(values) => Err(format!("expected trap {:?}, got {:?}", message, values)),
+        Err(trap) if trap.to_string().contains(message) => Ok(()),
+        Err(trap) => Err(format!(
+            "expected trap {:?}, got {:?}",
+            message,
+            trap.to_string()
+        )),
+    }
+}
+
+/// The text format is handled by the `wast` crate, a module it can't encode never reaches the
+/// runtime so nothing about it was checked
+fn unchecked_text(err: &str) -> String {
+    format!("not checked, the text parser rejected the module: {}", err)
+}
+
+/// The message the spec scripts expect for a module the binary parser rejects
+fn malformed_message(kind: &ParseErrorKind) -> String {
+    let message = match kind {
+        ParseErrorKind::UnexpectedEnd => "unexpected end",
+        ParseErrorKind::MagicHeader => "magic header not detected",
+        ParseErrorKind::UnknownVersion => "unknown binary version",
+        ParseErrorKind::MalformedSectionId(_) => "malformed section id",
+        ParseErrorKind::SectionSizeMismatch => "section size mismatch",
+        ParseErrorKind::DuplicateSection | ParseErrorKind::SectionOutOfOrder => {
+            "unexpected content after last section"
+        }
+        ParseErrorKind::IntegerTooLong => "integer representation too long",
+        ParseErrorKind::MalformedUtf8 => "malformed UTF-8 encoding",
+        ParseErrorKind::MalformedValueType(_) => "malformed value type",
+        ParseErrorKind::MalformedRefType(_) => "malformed reference type",
+        ParseErrorKind::MalformedFuncType(_) => "malformed function type",
+        // The flags are a single byte, one with the continuation bit set is a longer integer
+        ParseErrorKind::MalformedLimits(flags) if flags & 0x80 != 0 => {
+            "integer representation too long"
+        }
+        ParseErrorKind::MalformedLimits(_) => "integer too large",
+        ParseErrorKind::MalformedMutability(_) => "malformed mutability",
+        ParseErrorKind::MalformedImportKind(_) => "malformed import kind",
+        ParseErrorKind::MalformedExportKind(_) => "malformed export kind",
+        ParseErrorKind::MalformedElementKind(_) => "malformed elements segment kind",
+        ParseErrorKind::MalformedDataKind(_) => "malformed data segment kind",
+        ParseErrorKind::IllegalOpcode(..) => "illegal opcode",
+        ParseErrorKind::ZeroByteExpected => "zero byte expected",
+        ParseErrorKind::UnknownLabel(_) | ParseErrorKind::UnknownType(_) => {
+            return kind.to_string()
+        }
+        ParseErrorKind::TooManyLocals => "too many locals",
+        ParseErrorKind::FunctionBodySizeMismatch => "section size mismatch",
+        ParseErrorKind::InconsistentFunctionAndCode => {
+            "function and code section have inconsistent lengths"
+        }
+        ParseErrorKind::MultipleMemories => "multiple memories",
+        ParseErrorKind::DataCountMismatch => {
+            "data count and data section have inconsistent lengths"
+        }
+        ParseErrorKind::Nom(_) => "malformed input",
+    };
+    message.to_string()
+}
+
+/// Checks the message of a rejected module, the expected one has to be a prefix of it
+fn expect_message(expected: &str, actual: &str, error: &dyn Display) -> Result<(), String> {
+    if actual.starts_with(expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {:?}, got {:?} ({})",
+            expected, actual, error
+        ))
+    }
+}
+
+/// The host functions of the `spectest` module the spec scripts import
+fn spectest_linker<'m, 'b>() -> Linker<'m, 'b> {
+    let [i32, i64, f32, f64] = [
+        NumericValueType::I32,
+        NumericValueType::I64,
+        NumericValueType::F32,
+        NumericValueType::F64,
+    ]
+    .map(ValueType::Numeric);
+    let prints = [
+        ("print", vec![]),
+        ("print_i32", vec![i32]),
+        ("print_i64", vec![i64]),
+        ("print_f32", vec![f32]),
+        ("print_f64", vec![f64]),
+        ("print_i32_f32", vec![i32, f32]),
+        ("print_f64_f64", vec![f64, f64]),
+    ];
+
+    let mut linker = Linker::new();
+    for (name, params) in prints {
+        let signature = FuncType {
+            params,
+            returns: vec![],
+        };
+        linker.func("spectest", name, signature, |_, args| {
+            println!("{:?}", args);
+            Ok(vec![])
+        });
+    }
+
+    linker
+        .global(
+            "spectest",
+            "global_i32",
+            GlobalHandle::new(Value::I32(666), Mutability::Const),
+        )
+        .global(
+            "spectest",
+            "global_i64",
+            GlobalHandle::new(Value::I64(666), Mutability::Const),
+        )
+        .global(
+            "spectest",
+            "global_f32",
+            GlobalHandle::new(Value::F32(666.6), Mutability::Const),
+        )
+        .global(
+            "spectest",
+            "global_f64",
+            GlobalHandle::new(Value::F64(666.6), Mutability::Const),
+        )
+        .table(
+            "spectest",
+            "table",
+            TableHandle::new(TableType(
+                RefType::FuncRef,
+                Limit {
+                    min: 10,
+                    max: Some(20),
+                },
+            )),
+        )
+        .memory(
+            "spectest",
+            "memory",
+            MemoryHandle::new(Limit {
+                min: 1,
+                max: Some(2),
+            }),
+        );
+    linker
+}
+
+struct SpecRunner<'m, 'b> {
+    instances: Vec<Instance<'m, 'b>>,
+    instance_names: HashMap<String, usize>,
+    definitions: HashMap<String, &'m Module<'b>>,
+    linker: Linker<'m, 'b>,
+    store: Store<'m, 'b>,
+}
+
+impl<'m, 'b> SpecRunner<'m, 'b> {
+    fn new(engine: Engine) -> Self {
+        Self {
+            instances: vec![],
+            instance_names: HashMap::new(),
+            definitions: HashMap::new(),
+            linker: spectest_linker(),
+            store: Store::with_config(Config {
+                engine,
+                ..Config::default()
+            }),
+        }
+    }
+
+    fn define(&self, module: &'m ScriptModule<'b>) -> Result<&'m Module<'b>, String> {
+        let module = module.parsed()?;
+        validate(module).map_err(|err| format!("invalid module: {}", err))?;
+        Ok(module)
+    }
+
+    fn instantiate(
+        &mut self,
+        module: &'m Module<'b>,
+        name: Option<Id>,
+    ) -> Result<Instance<'m, 'b>, String> {
+        let instance = self
+            .store
+            .instantiate(&self.linker, module)
+            .map_err(|err| format!("instantiation failed: {}", err))?;
+        if let Some(name) = name {
+            self.instance_names
+                .insert(name.name().to_string(), self.instances.len());
+        }
+        Ok(instance)
+    }
+
+    fn instance(&self, name: Option<Id>) -> Result<&Instance<'m, 'b>, String> {
+        let idx = match name {
+            Some(name) => *self
+                .instance_names
+                .get(name.name())
+                .ok_or_else(|| format!("unknown instance {:?}", name.name()))?,
+            None => self
+                .instances
+                .len()
+                .checked_sub(1)
+                .ok_or("no module was instantiated")?,
+        };
+        Ok(&self.instances[idx])
+    }
+
+    fn invoke(&self, invoke: &WastInvoke) -> Result<Result<Vec<Value>, Trap>, String> {
+        let func = self
+            .instance(invoke.module)?
+            .get_func(invoke.name)
+            .ok_or_else(|| format!("no exported function {:?}", invoke.name))?;
+        let args = invoke
+            .args
+            .iter()
+            .map(|arg| arg_value(arg, &self.store))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(func.call(&args))
+    }
+
+    fn execute(
+        &mut self,
+        exec: &WastExecute,
+        module: Option<&'m ScriptModule<'b>>,
+    ) -> Result<Result<Vec<Value>, Trap>, String> {
+        match exec {
+            WastExecute::Invoke(invoke) => self.invoke(invoke),
+            WastExecute::Get { module, global, .. } => {
+                let value = self
+                    .instance(*module)?
+                    .get_global(global)
+                    .map(|global| global.get())
+                    .ok_or_else(|| format!("no exported global {:?}", global))?;
+                Ok(Ok(vec![value]))
+            }
+            WastExecute::Wat(_) => {
+                let module = self.define(module.expect("Module was encoded"))?;
+                match self.store.instantiate(&self.linker, module) {
+                    Ok(_) => Ok(Ok(vec![])),
+                    Err(InstantiationError::Trap(trap)) => Ok(Err(trap)),
+                    Err(err) => Err(format!("instantiation failed: {}", err)),
+                }
+            }
+        }
+    }
+
+    fn run(
+        &mut self,
+        directive: &WastDirective,
+        module: Option<&'m ScriptModule<'b>>,
+    ) -> Result<(), String> {
+        match directive {
+            WastDirective::Module(wat) => {
+                let module = self.define(module.expect("Module was encoded"))?;
+                let instance = self.instantiate(module, wat.name())?;
+                self.instances.push(instance);
+                Ok(())
+            }
+            WastDirective::ModuleDefinition(wat) => {
+                let module = self.define(module.expect("Module was encoded"))?;
+                if let Some(name) = wat.name() {
+                    self.definitions.insert(name.name().to_string(), module);
+                }
+                Ok(())
+            }
+            WastDirective::ModuleInstance {
+                instance, module, ..
+            } => {
+                let module = module
+                    .and_then(|name| self.definitions.get(name.name()).copied())
+                    .ok_or("unknown module definition")?;
+                let instance = self.instantiate(module, *instance)?;
+                self.instances.push(instance);
+                Ok(())
+            }
+            WastDirective::AssertMalformed { message, .. } => {
+                match module.expect("Module was encoded") {
+                    ScriptModule::MalformedText(err) => Err(unchecked_text(err)),
+                    ScriptModule::Malformed(err) => {
+                        expect_message(message, &malformed_message(&err.kind), err)
+                    }
+                    ScriptModule::Parsed(_) => {
+                        Err(format!("module should have been malformed: {:?}", message))
+                    }
+                }
+            }
+            WastDirective::AssertInvalid { message, .. } => {
+                // Labels and types are resolved while parsing, some invalid modules are
+                // therefore already rejected by the parser
+                match module.expect("Module was encoded") {
+                    ScriptModule::MalformedText(err) => Err(unchecked_text(err)),
+                    ScriptModule::Malformed(err) => {
+                        expect_message(message, &malformed_message(&err.kind), err)
+                    }
+                    ScriptModule::Parsed(module) => match validate(module) {
+                        Ok(()) => Err(format!("module should have been invalid: {:?}", message)),
+                        // Validation errors are worded like the messages of the spec
+                        Err(err) => expect_message(message, &err.kind.to_string(), &err),
+                    },
+                }
+            }
+            WastDirective::AssertUnlinkable { message, .. } => {
+                let module = self.define(module.expect("Module was encoded"))?;
+                match self.store.instantiate(&self.linker, module) {
+                    Ok(_) => Err(format!("module should not link: {:?}", message)),
+                    Err(InstantiationError::Trap(trap)) => {
+                        Err(format!("expected a link error, got trap: {}", trap))
+                    }
+                    Err(_) => Ok(()),
+                }
+            }
+            WastDirective::Invoke(invoke) => {
+                self.invoke(invoke)?
+                    .map_err(|trap| format!("unexpected trap: {}", trap))?;
+                Ok(())
+            }
+            WastDirective::AssertReturn { exec, results, .. } => {
+                let values = self
+                    .execute(exec, module)?
+                    .map_err(|trap| format!("unexpected trap: {}", trap))?;
+                let all_match = values.len() == results.len()
+                    && results
+                        .iter()
+                        .zip(&values)
+                        .all(|(expected, actual)| result_matches(expected, actual, &self.store));
+                if !all_match {
+                    return Err(format!("expected {:?}, got {:?}", results, values));
+                }
+                Ok(())
+            }
+            WastDirective::AssertTrap { exec, message, .. } => {
+                expect_trap(self.execute(exec, module)?, message)
+            }
+            WastDirective::AssertExhaustion { call, message, .. } => {
+                expect_trap(self.invoke(call)?, message)
+            }
+            WastDirective::Register { name, module, .. } => {
+                let instance = self.instance(*module)?.clone();
+                self.linker.instance(name, &instance);
+                Ok(())
+            }
+            directive => Err(format!("{} is not supported", directive_name(directive))),
+        }
+    }
+}
+
+/// Runs every directive of the script on both engines, printing the outcome of each. Returns
+/// how many of them passed and how many were run
+fn run_script(path: &str) -> (usize, usize) {
+    let source = fs::read_to_string(path).unwrap();
+    // Some scripts name their exports with bidirectional text control characters on purpose
+    let mut lexer = Lexer::new(&source);
+    lexer.allow_confusing_unicode(true);
+    let buffer = ParseBuffer::new_with_lexer(lexer).unwrap();
+    let mut wast: Wast = parser::parse(&buffer).unwrap_or_else(|mut err| {
+        err.set_path(path.as_ref());
+        err.set_text(&source);
+        panic!("{}", err)
+    });
+
+    let binaries = encode_modules(&mut wast.directives);
+    let modules = binaries
+        .iter()
+        .map(|binary| {
+            binary.as_ref().map(|binary| match binary {
+                Ok(binary) => match Module::new(binary) {
+                    Ok(module) => ScriptModule::Parsed(module),
+                    Err(err) => ScriptModule::Malformed(err),
+                },
+                Err(err) => ScriptModule::MalformedText(err.clone()),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Both engines have to give the same results for every directive
+    let mut failed = 0;
+    for engine in [Engine::Stack, Engine::Registers] {
+        let mut runner = SpecRunner::new(engine);
+        for (directive, module) in wast.directives.iter().zip(&modules) {
+            let (line, _) = directive.span().linecol_in(&source);
+            let outcome =
+                panic::catch_unwind(AssertUnwindSafe(|| runner.run(directive, module.as_ref())))
+                    .unwrap_or_else(|payload| {
+                        let message = payload
+                            .downcast_ref::<&str>()
+                            .map(|message| message.to_string())
+                            .or_else(|| payload.downcast_ref::<String>().cloned())
+                            .unwrap_or_default();
+                        Err(format!("panicked: {}", message))
+                    });
+
+            let name = directive_name(directive);
+            match outcome {
+                Ok(()) => println!("{}:{}: {} ok ({:?})", path, line + 1, name, engine),
+                Err(reason) => {
+                    failed += 1;
+                    println!(
+                        "{}:{}: {} FAILED ({:?}): {}",
+                        path,
+                        line + 1,
+                        name,
+                        engine,
+                        reason
+                    );
+                }
+            }
+        }
+    }
+
+    let total = 2 * wast.directives.len();
+    println!(
+        "{}: {} of {} directives passed",
+        path,
+        total - failed,
+        total
+    );
+    (total - failed, total)
+}
+
+fn run_spec_test(path: &str) {
+    let (passed, total) = run_script(path);
+    assert_eq!(
+        passed,
+        total,
+        "{} of {} directives failed in {}",
+        total - passed,
+        total,
+        path
+    );
+}
+
+/// The vendored testsuite doesn't pass completely, each script only has to keep passing as many
+/// directives as `test/testsuite/passing` records for it
+fn run_testsuite_test(path: &str) {
+    let (passed, total) = run_script(path);
+    let script = Path::new(path).file_name().unwrap().to_str().unwrap();
+    let recorded = fs::read_to_string("./test/testsuite/passing")
+        .unwrap()
+        .lines()
+        .find_map(|line| {
+            let (name, passed) = line.split_once(' ')?;
+            (name == script).then(|| passed.parse::<usize>().unwrap())
+        })
+        .unwrap_or(0);
+    assert!(
+        passed >= recorded,
+        "{} of {} directives passed in {}, {} did before",
+        passed,
+        total,
+        path,
+        recorded
+    );
+}
+
+// Include the generated test functions
+include!(concat!(env!("OUT_DIR"), "/spec_tests_generated.rs"));
@@ -1,229 +1,125 @@
-use core::panic;
-
-use crate::types::{BlockIdx, NumericValueType, ValueType};
+use crate::types::{FuncIdx, LocalIdx, ValueType};
 
 use super::{
-    function_state::{FunctionState, InstructionIndex},
+    bytecode::DropKeep,
+    function_state::FunctionState,
+    store::StoreId,
     table::TableElementIdx,
     value::{Ref, Value},
 };
 
-#[derive(Debug)]
+/// The values of the stack engine and the functions they belong to. Each function has its
+/// params and locals in one piece of the stack, its operands are pushed above them. Values are
+/// kept as their bits only, validation made sure each instruction knows what it pops
+#[derive(Debug, Default)]
 pub struct Stack {
-    stack: Vec<StackValue>,
+    stack: Vec<u64>,
+    frames: Vec<FunctionState>,
 }
 
 impl Stack {
     pub fn new() -> Self {
-        Self { stack: vec![] }
+        Self::default()
     }
 
     pub fn is_empty(&self) -> bool {
         self.stack.is_empty()
     }
 
-    pub fn push_bool(&mut self, value: bool) {
-        let value = if value { 1 } else { 0 };
-        self.push_i32(value);
-    }
-
-    pub fn push_i32(&mut self, value: i32) {
-        self.stack.push(StackValue::Value(Value::I32(value)))
+    pub fn len(&self) -> usize {
+        self.stack.len()
     }
 
-    pub fn push_u32(&mut self, value: u32) {
-        self.push_i32(i32::from_le_bytes(value.to_le_bytes()))
+    pub fn truncate(&mut self, len: usize) {
+        self.stack.truncate(len);
     }
 
-    pub fn push_i64(&mut self, value: i64) {
-        self.stack.push(StackValue::Value(Value::I64(value)))
+    pub fn push(&mut self, bits: u64) {
+        self.stack.push(bits)
     }
 
-    pub fn push_u64(&mut self, value: u64) {
-        self.push_i64(i64::from_le_bytes(value.to_le_bytes()))
+    pub fn pop(&mut self) -> u64 {
+        self.stack
+            .pop()
+            .expect("Validated code doesn't pop more than it pushed")
     }
 
-    pub fn push_f32(&mut self, value: f32) {
-        self.stack.push(StackValue::Value(Value::F32(value)))
+    pub fn push_value(&mut self, value: Value) {
+        self.push(value.to_bits());
     }
 
-    pub fn push_f64(&mut self, value: f64) {
-        self.stack.push(StackValue::Value(Value::F64(value)))
+    pub fn pop_value(&mut self, value_type: ValueType, store: StoreId) -> Value {
+        Value::from_bits(self.pop(), value_type, store)
     }
 
-    pub fn push_ref(&mut self, value: Ref) {
-        self.stack.push(StackValue::Value(Value::Ref(value)))
+    pub fn pop_u32(&mut self) -> u32 {
+        self.pop() as u32
     }
 
     pub fn pop_bool(&mut self) -> bool {
-        let value = self.pop_i32();
-        value != 0
-    }
-
-    pub fn pop_i32(&mut self) -> i32 {
-        if let Some(StackValue::Value(Value::I32(value))) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping i32 from stack but failed")
-        }
+        self.pop_u32() != 0
     }
 
     pub fn pop_table_element_idx(&mut self) -> TableElementIdx {
         TableElementIdx(self.pop_u32() as usize)
     }
 
-    pub fn pop_u32(&mut self) -> u32 {
-        u32::from_le_bytes(self.pop_i32().to_le_bytes())
+    pub fn pop_ref(&mut self, store: StoreId) -> Ref {
+        Ref::from_bits(self.pop(), store)
     }
 
-    pub fn pop_i64(&mut self) -> i64 {
-        if let Some(StackValue::Value(Value::I64(value))) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping i64 from stack but failed")
+    /// Moves the top `keep` values down over the `drop` values below them
+    pub fn drop_keep(&mut self, DropKeep { drop, keep }: DropKeep) {
+        if drop > 0 {
+            let len = self.stack.len();
+            self.stack.copy_within(len - keep..len, len - keep - drop);
+            self.stack.truncate(len - drop);
         }
     }
 
-    pub fn pop_u64(&mut self) -> u64 {
-        u64::from_le_bytes(self.pop_i64().to_le_bytes())
+    /// Starts a function whose `params` are the top values, `locals` are the initial values of
+    /// the locals that follow them
+    pub fn enter(&mut self, func_idx: FuncIdx, params: usize, locals: &[u64]) {
+        let fp = self.stack.len() - params;
+        self.stack.extend_from_slice(locals);
+        self.frames.push(FunctionState::new_function(func_idx, fp));
     }
 
-    pub fn pop_f32(&mut self) -> f32 {
-        if let Some(StackValue::Value(Value::F32(value))) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping f32 from stack but failed")
-        }
+    /// Removes the function that returned along with its `amount_of_locals` params and
+    /// locals, the values it left above them move down to where they were
+    pub fn leave(&mut self, amount_of_locals: usize) {
+        let fp = self.frame().fp();
+        let keep = self.stack.len() - fp - amount_of_locals;
+        self.drop_keep(DropKeep {
+            drop: amount_of_locals,
+            keep,
+        });
+        self.frames.pop();
     }
 
-    pub fn pop_f64(&mut self) -> f64 {
-        if let Some(StackValue::Value(Value::F64(value))) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping f64 from stack but failed")
-        }
+    /// The function running right now
+    pub fn frame(&self) -> &FunctionState {
+        self.frames.last().expect("A function is running")
     }
 
-    pub fn pop_ref(&mut self) -> Ref {
-        if let Some(StackValue::Value(Value::Ref(value))) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping i32 from stack but failed")
-        }
+    pub fn frame_mut(&mut self) -> &mut FunctionState {
+        self.frames.last_mut().expect("A function is running")
     }
 
-    pub fn push_value(&mut self, value: Value) {
-        self.stack.push(StackValue::Value(value));
+    pub fn amount_of_frames(&self) -> usize {
+        self.frames.len()
     }
 
-    pub fn pop_value(&mut self) -> Value {
-        if let Some(StackValue::Value(value)) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping value from stack but failed")
-        }
+    pub fn truncate_frames(&mut self, len: usize) {
+        self.frames.truncate(len);
     }
 
-    pub fn pop_value_by_type(&mut self, value_type: ValueType) -> Value {
-        let poped_value = self.stack.pop();
-        if let Some(StackValue::Value(value)) = poped_value {
-            match (value_type, value) {
-                (ValueType::Numeric(NumericValueType::I32), Value::I32(_))
-                | (ValueType::Numeric(NumericValueType::I64), Value::I64(_))
-                | (ValueType::Numeric(NumericValueType::F32), Value::F32(_))
-                | (ValueType::Numeric(NumericValueType::F64), Value::F64(_)) => {}
-                _ => {
-                    panic!("Tried popping: {:?} received: {:?}", value_type, value);
-                }
-            }
-            value
-        } else {
-            panic!("Tried popping value from stack but failed")
-        }
+    pub fn local(&self, LocalIdx(idx): LocalIdx) -> u64 {
+        self.stack[self.frame().fp() + idx as usize]
     }
 
-    pub fn push_function_state(&mut self, function_state: FunctionState) {
-        self.stack.push(StackValue::Function(function_state))
+    pub fn set_local(&mut self, LocalIdx(idx): LocalIdx, bits: u64) {
+        let fp = self.frame().fp();
+        self.stack[fp + idx as usize] = bits;
     }
-
-    pub fn pop_function_state(&mut self) -> FunctionState {
-        if let Some(StackValue::Function(value)) = self.stack.pop() {
-            value
-        } else {
-            panic!("Tried popping value from stack but failed")
-        }
-    }
-
-    pub fn drop_value(&mut self) {
-        let Some(StackValue::Value(_)) = self.stack.pop() else {
-            panic!("Dropped something that isn't value");
-        };
-    }
-
-    pub fn pop_until_function_state(&mut self, current_function: &FunctionState) -> FunctionState {
-        let mut found_block = false;
-        loop {
-            let pop = self.stack.pop().expect("Popped all stack while breaking");
-            match pop {
-                StackValue::Value(_) => {
-                    continue;
-                }
-                StackValue::Function(f) => {
-                    if !found_block {
-                        match f.instruction_index() {
-                            InstructionIndex::IndexInFunction(_) => {
-                                found_block = f.function_idx() == current_function.function_idx();
-                            }
-                            InstructionIndex::IndexInBlock { .. } => {
-                                continue;
-                            }
-                        }
-                    } else {
-                        return f;
-                    }
-                }
-            };
-        }
-    }
-
-    pub fn break_from_block(&mut self, block_idx: BlockIdx) -> FunctionState {
-        let mut found_block = false;
-        loop {
-            let pop = self.stack.pop().expect("Popped all stack while breaking");
-            match pop {
-                StackValue::Value(_) => {
-                    continue;
-                }
-                StackValue::Function(f) => {
-                    if !found_block {
-                        match f.instruction_index() {
-                            InstructionIndex::IndexInFunction(_) => {
-                                panic!("Break out of function call not possible")
-                            }
-                            InstructionIndex::IndexInBlock {
-                                block_idx: poped_block_index,
-                                ..
-                            } => {
-                                found_block = poped_block_index == block_idx;
-                            }
-                        }
-                    } else {
-                        return f;
-                    }
-                }
-            };
-        }
-    }
-}
-
-impl Default for Stack {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-#[derive(Debug)]
-pub enum StackValue {
-    Value(Value),
-    Function(FunctionState),
 }
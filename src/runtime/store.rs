@@ -0,0 +1,755 @@
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    rc::{Rc, Weak},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::{module::Module, types::Instruction, validate::validate};
+
+use super::{
+    value::ExternRef, Config, Execution, Instance, InstantiationError, Linker, Runtime, Trap, Value,
+};
+
+/// Identifies an instance within its store, function references use it to find the instance
+/// their function belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceId(pub(super) u32);
+
+/// Identifies a store among all stores of the process. References carry it, so the ones of
+/// another store are rejected instead of pointing to whatever has the same index here
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreId(u64);
+
+impl Default for StoreId {
+    /// A new id, no two stores share one
+    fn default() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Host objects extern references point to. The guest may hold on to references anywhere, so
+/// an object is kept until the host drops it or the store is dropped. Slots of dropped objects
+/// are reused, references to the old object are told apart by the generation of the slot
+#[derive(Default)]
+pub(super) struct ExternObjects {
+    slots: RefCell<Vec<ExternSlot>>,
+    free: RefCell<Vec<u32>>,
+}
+
+struct ExternSlot {
+    generation: u32,
+    object: Option<Rc<dyn Any>>,
+}
+
+/// Generations are kept in 30 bits of a reference's operand, a slot that used them all up isn't
+/// reused anymore
+const MAX_EXTERN_GENERATION: u32 = (1 << 30) - 1;
+
+impl ExternObjects {
+    pub fn insert<T: Any>(&self, store: StoreId, object: T) -> ExternRef {
+        let mut slots = self.slots.borrow_mut();
+        let idx = self.free.borrow_mut().pop().unwrap_or_else(|| {
+            slots.push(ExternSlot {
+                generation: 0,
+                object: None,
+            });
+            slots.len() as u32 - 1
+        });
+        let slot = &mut slots[idx as usize];
+        slot.object = Some(Rc::new(object));
+        ExternRef {
+            store,
+            generation: slot.generation,
+            idx,
+        }
+    }
+
+    /// Returns `None` if the object isn't a `T`, was dropped or the reference is one of
+    /// another store
+    pub fn get<T: Any>(&self, store: StoreId, extern_ref: ExternRef) -> Option<Rc<T>> {
+        let slots = self.slots.borrow();
+        let object = Self::slot(&slots, store, extern_ref)?.object.clone()?;
+        object.downcast().ok()
+    }
+
+    /// Returns `false` if the object was already dropped or the reference is one of another
+    /// store
+    pub fn remove(&self, store: StoreId, extern_ref: ExternRef) -> bool {
+        let mut slots = self.slots.borrow_mut();
+        if Self::slot(&slots, store, extern_ref).is_none() {
+            return false;
+        }
+        let slot = &mut slots[extern_ref.idx as usize];
+        let Some(object) = slot.object.take() else {
+            return false;
+        };
+        slot.generation += 1;
+        if slot.generation <= MAX_EXTERN_GENERATION {
+            self.free.borrow_mut().push(extern_ref.idx);
+        }
+        // The object may run arbitrary code when dropped, like creating other objects
+        drop(slots);
+        drop(object);
+        true
+    }
+
+    fn slot(slots: &[ExternSlot], store: StoreId, extern_ref: ExternRef) -> Option<&ExternSlot> {
+        let slot = slots.get(extern_ref.idx as usize)?;
+        (extern_ref.store == store && slot.generation == extern_ref.generation).then_some(slot)
+    }
+}
+
+/// What executing an instruction costs
+type FuelCosts = Box<dyn Fn(&Instruction) -> u64>;
+
+/// Limits how much the instances of a store may execute, every instruction costs fuel
+#[derive(Default)]
+struct Fuel {
+    /// `None` while fuel isn't metered
+    remaining: Cell<Option<u64>>,
+    consumed: Cell<u64>,
+    /// Instructions cost one unit each if there are no costs configured
+    costs: RefCell<Option<FuelCosts>>,
+}
+
+impl Fuel {
+    fn consume(&self, instruction: &Instruction) -> Result<(), Trap> {
+        let Some(remaining) = self.remaining.get() else {
+            return Ok(());
+        };
+        let cost = match self.costs.borrow().as_ref() {
+            Some(costs) => costs(instruction),
+            None => 1,
+        };
+        let remaining = remaining.checked_sub(cost).ok_or(Trap::OutOfFuel)?;
+        self.remaining.set(Some(remaining));
+        self.consumed.set(self.consumed.get() + cost);
+        Ok(())
+    }
+}
+
+/// Lets other threads interrupt the instances of a store, by incrementing the epoch until it
+/// reaches the deadline set with [`Store::set_epoch_deadline`]
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle(Arc<AtomicU64>);
+
+impl InterruptHandle {
+    pub fn increment_epoch(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn epoch(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// What references point into, shared by all instances of a store: the instances of function
+/// references and the host objects of extern references. Also keeps track of the fuel and the
+/// calls running in the store
+#[derive(Default)]
+pub(super) struct StoreData<'b, 'a> {
+    pub id: StoreId,
+    instances: RefCell<Vec<Weak<Runtime<'b, 'a>>>>,
+    pub extern_objects: ExternObjects,
+    fuel: Fuel,
+    /// How many calls into instances of the store are running, calls between instances nest
+    calls: Cell<usize>,
+    /// The depth of the call that last ran out of fuel
+    ran_out_of_fuel_at: Cell<Option<usize>>,
+    /// An execution that ran out of fuel, with the instance it runs in
+    suspended: RefCell<Option<(InstanceId, Execution)>>,
+    epoch: InterruptHandle,
+    /// `None` while execution can't be interrupted
+    epoch_deadline: Cell<Option<u64>>,
+    config: Config,
+    /// Function calls waiting for a call into another instance to return
+    enclosing_depth: Cell<usize>,
+    /// Where the host's stack was when the outermost call started
+    native_stack_start: Cell<usize>,
+}
+
+impl<'b, 'a> StoreData<'b, 'a> {
+    /// Hands out the id of an instance before it is created, so its initialization can
+    /// already create references to its functions
+    pub fn reserve_instance(&self) -> InstanceId {
+        let mut instances = self.instances.borrow_mut();
+        instances.push(Weak::new());
+        InstanceId(instances.len() as u32 - 1)
+    }
+
+    pub fn set_instance(&self, InstanceId(id): InstanceId, runtime: &Rc<Runtime<'b, 'a>>) {
+        self.instances.borrow_mut()[id as usize] = Rc::downgrade(runtime);
+    }
+
+    pub fn instance(&self, InstanceId(id): InstanceId) -> Option<Rc<Runtime<'b, 'a>>> {
+        self.instances.borrow().get(id as usize)?.upgrade()
+    }
+
+    pub fn meters_fuel(&self) -> bool {
+        self.fuel.remaining.get().is_some()
+    }
+
+    pub fn consume_fuel(&self, instruction: &Instruction) -> Result<(), Trap> {
+        self.fuel
+            .consume(instruction)
+            .inspect_err(|_| self.ran_out_of_fuel_at.set(Some(self.calls.get())))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn enclosing_depth(&self) -> usize {
+        self.enclosing_depth.get()
+    }
+
+    /// Makes a call into another instance, which runs on top of `depth` calls of the calling
+    /// instance
+    pub fn nested_call<T>(&self, depth: usize, call: impl FnOnce() -> T) -> T {
+        self.enclosing_depth.set(self.enclosing_depth.get() + depth);
+        let result = call();
+        self.enclosing_depth.set(self.enclosing_depth.get() - depth);
+        result
+    }
+
+    /// Only checked at loop back-edges and calls, which is enough to interrupt any execution
+    /// that keeps running
+    pub fn check_epoch(&self) -> Result<(), Trap> {
+        match self.epoch_deadline.get() {
+            Some(deadline) if self.epoch.epoch() >= deadline => Err(Trap::Interrupted),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the depth of the new call, the outermost one is 1
+    pub fn enter_call(&self) -> usize {
+        if self.calls.get() == 0 {
+            self.native_stack_start.set(native_stack_position());
+        }
+        self.calls.set(self.calls.get() + 1);
+        self.calls.get()
+    }
+
+    /// Calls nested in other calls run on top of their host stack frames, the host's stack
+    /// could overflow before the call depth limit is reached
+    pub fn check_native_stack(&self) -> Result<(), Trap> {
+        if self.calls.get() == 0 {
+            return Ok(());
+        }
+        let used = self
+            .native_stack_start
+            .get()
+            .saturating_sub(native_stack_position());
+        if used > self.config.max_native_stack {
+            return Err(Trap::StackOverflow);
+        }
+        Ok(())
+    }
+
+    pub fn leave_call(&self) {
+        self.calls.set(self.calls.get() - 1);
+    }
+
+    /// Keeps an execution that ran out of fuel to resume it later. It's given back if it
+    /// can't be resumed: it has to be the outermost call, which ran out itself and not in a
+    /// call to another instance, and only one execution can be suspended at a time
+    pub fn suspend(
+        &self,
+        instance: InstanceId,
+        depth: usize,
+        execution: Execution,
+    ) -> Result<(), Execution> {
+        let ran_out_of_fuel_at = self.ran_out_of_fuel_at.take();
+        let mut suspended = self.suspended.borrow_mut();
+        if depth != 1 || ran_out_of_fuel_at != Some(depth) || suspended.is_some() {
+            return Err(execution);
+        }
+        *suspended = Some((instance, execution));
+        Ok(())
+    }
+}
+
+/// Roughly where the host's stack is right now, it grows downwards
+#[inline(never)]
+fn native_stack_position() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Owns instances that may import from each other and share function references through
+/// their tables, and the host objects passed to them as extern references. References are
+/// only meaningful within the store that created them, passing one to another store traps
+/// with [`Trap::ForeignReference`]. Tables and reference typed globals belong to the first
+/// store that puts a reference in them or instantiates a module importing them
+///
+/// A store can also bound how much its instances execute. Metering fuel with
+/// [`Store::set_fuel`] charges every instruction, the ones the register engine translated
+/// away are charged together with the next operation, and a call that ran out can be
+/// continued with [`Store::resume`] once fuel was added. Interrupting through an
+/// [`InterruptHandle`] is cheaper since the epoch is only checked at loop back-edges and
+/// calls, which makes it the better fit for timeouts
+#[derive(Default)]
+pub struct Store<'b, 'a> {
+    data: Rc<StoreData<'b, 'a>>,
+    owned: Vec<Instance<'b, 'a>>,
+}
+
+impl<'b, 'a> Store<'b, 'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_config(config: Config) -> Self {
+        Self {
+            data: Rc::new(StoreData {
+                config,
+                ..StoreData::default()
+            }),
+            owned: vec![],
+        }
+    }
+
+    /// Validates the module, resolves its imports with the linker and adds the new instance to
+    /// the store. Register it on the linker with [`Linker::instance`] to make its exports
+    /// importable by the next instances
+    pub fn instantiate(
+        &mut self,
+        linker: &Linker<'b, 'a>,
+        module: &'b Module<'a>,
+    ) -> Result<Instance<'b, 'a>, InstantiationError> {
+        validate(module).map_err(InstantiationError::Invalid)?;
+        let imports = linker.resolve(module, self.data.id)?;
+        let instance = Instance::uninitialized(module, imports, self.data.clone());
+        // Kept even if initialization traps, tables shared with other instances may already
+        // reference its functions
+        self.owned.push(instance.clone());
+        instance.initialize().map_err(InstantiationError::Trap)?;
+        Ok(instance)
+    }
+
+    pub fn instances(&self) -> &[Instance<'b, 'a>] {
+        &self.owned
+    }
+
+    /// Wraps a host object into a reference that can be passed to the instances of the store.
+    /// The object lives until it's dropped with [`Store::drop_extern`] or the store is dropped
+    pub fn extern_ref<T: Any>(&self, object: T) -> ExternRef {
+        self.data.extern_objects.insert(self.data.id, object)
+    }
+
+    /// The object behind a reference of this store, `None` if it isn't a `T` or was dropped
+    pub fn extern_object<T: Any>(&self, extern_ref: ExternRef) -> Option<Rc<T>> {
+        self.data.extern_objects.get(self.data.id, extern_ref)
+    }
+
+    /// Drops the store's object behind the reference once the host knows the guest is done
+    /// with it, `false` if it was already dropped. References to it the guest still holds
+    /// don't find any object anymore, even after its slot was reused
+    pub fn drop_extern(&self, extern_ref: ExternRef) -> bool {
+        self.data.extern_objects.remove(self.data.id, extern_ref)
+    }
+
+    /// Starts metering fuel, or replaces the fuel that is left. Executing an instruction with
+    /// not enough fuel left traps with [`Trap::OutOfFuel`]
+    pub fn set_fuel(&self, fuel: u64) {
+        self.data.fuel.remaining.set(Some(fuel));
+    }
+
+    /// Adds to the fuel that is left, starts metering if it wasn't already
+    pub fn add_fuel(&self, fuel: u64) {
+        let remaining = self.data.fuel.remaining.get().unwrap_or(0);
+        self.data
+            .fuel
+            .remaining
+            .set(Some(remaining.saturating_add(fuel)));
+    }
+
+    /// The fuel that is left, `None` if fuel isn't metered
+    pub fn fuel(&self) -> Option<u64> {
+        self.data.fuel.remaining.get()
+    }
+
+    /// All fuel consumed by the instances of the store so far
+    pub fn fuel_consumed(&self) -> u64 {
+        self.data.fuel.consumed.get()
+    }
+
+    /// Sets what executing each instruction costs, instead of one unit each. Blocks and loops
+    /// are never charged, only the instructions in them
+    pub fn set_fuel_costs(&self, costs: impl Fn(&Instruction) -> u64 + 'static) {
+        *self.data.fuel.costs.borrow_mut() = Some(Box::new(costs));
+    }
+
+    /// A handle to interrupt the instances of the store from another thread
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.data.epoch.clone()
+    }
+
+    /// Interrupts execution with [`Trap::Interrupted`] once the epoch was incremented `ticks`
+    /// more times through the [`InterruptHandle`]. A deadline of 0 interrupts right away
+    pub fn set_epoch_deadline(&self, ticks: u64) {
+        let deadline = self.data.epoch.epoch().saturating_add(ticks);
+        self.data.epoch_deadline.set(Some(deadline));
+    }
+
+    /// Lets execution run without being interrupted again
+    pub fn clear_epoch_deadline(&self) {
+        self.data.epoch_deadline.set(None);
+    }
+
+    /// Continues the call that last ran out of fuel where it stopped and returns its results.
+    /// `None` if there is nothing to resume. Only a call made from the outside that ran out
+    /// of fuel in its own instance can be resumed
+    pub fn resume(&self) -> Option<Result<Vec<Value>, Trap>> {
+        let (instance, execution) = self.data.suspended.borrow_mut().take()?;
+        let runtime = self.data.instance(instance)?;
+        Some(runtime.continue_execution(execution))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{
+        module::Module,
+        runtime::{
+            test::compile, Config, ExternRef, InstantiationError, Linker, Ref, SetGlobalError,
+            Trap, Value,
+        },
+        types::{FuncType, Instruction, NumericValueType, RefType, ValueType},
+    };
+
+    use super::Store;
+
+    struct File {
+        name: &'static str,
+        reads: Cell<i32>,
+    }
+
+    #[test]
+    fn extern_refs() {
+        let binary = compile(
+            r#"(module
+                (import "host" "open" (func $open (result externref)))
+                (import "host" "read" (func $read (param externref) (result i32)))
+                (table $files 1 externref)
+                (func (export "open") (table.set $files (i32.const 0) (call $open)))
+                (func (export "read") (result i32) (call $read (table.get $files (i32.const 0))))
+                (func (export "file") (result externref) (table.get $files (i32.const 0))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let externref = ValueType::Ref(RefType::ExternRef);
+        let mut linker = Linker::new();
+        linker.func(
+            "host",
+            "open",
+            FuncType {
+                params: vec![],
+                returns: vec![externref],
+            },
+            |caller, _| {
+                let file = caller.extern_ref(File {
+                    name: "data.txt",
+                    reads: Cell::new(0),
+                });
+                Ok(vec![Value::Ref(Ref::Extern(file))])
+            },
+        );
+        linker.func(
+            "host",
+            "read",
+            FuncType {
+                params: vec![externref],
+                returns: vec![ValueType::Numeric(NumericValueType::I32)],
+            },
+            |caller, args| {
+                let [Value::Ref(Ref::Extern(file))] = args else {
+                    return Ok(vec![Value::I32(-1)]);
+                };
+                let file = caller.extern_object::<File>(*file).unwrap();
+                file.reads.set(file.reads.get() + 1);
+                Ok(vec![Value::I32(file.reads.get())])
+            },
+        );
+
+        let mut store = Store::new();
+        let instance = store.instantiate(&linker, &module).unwrap();
+        let read = instance.get_typed_func::<(), i32>("read").unwrap();
+        assert_eq!(read.call(()), Ok(-1));
+
+        instance
+            .get_typed_func::<(), ()>("open")
+            .unwrap()
+            .call(())
+            .unwrap();
+        assert_eq!(read.call(()), Ok(1));
+        assert_eq!(read.call(()), Ok(2));
+
+        let file = instance
+            .get_typed_func::<(), Option<ExternRef>>("file")
+            .unwrap()
+            .call(())
+            .unwrap()
+            .unwrap();
+        let file = store.extern_object::<File>(file).unwrap();
+        assert_eq!(file.name, "data.txt");
+        assert_eq!(file.reads.get(), 2);
+        assert!(store.extern_object::<String>(store.extern_ref(1)).is_none());
+    }
+
+    #[test]
+    fn dropped_extern_objects() {
+        let binary = compile(
+            r#"(module
+                (global $kept (mut externref) (ref.null extern))
+                (func (export "keep") (param externref) (global.set $kept (local.get 0)))
+                (func (export "kept") (result externref) (global.get $kept)))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let keep = instance
+            .get_typed_func::<Option<ExternRef>, ()>("keep")
+            .unwrap();
+        let kept = instance
+            .get_typed_func::<(), Option<ExternRef>>("kept")
+            .unwrap();
+
+        let file = Rc::new("data.txt");
+        let old = store.extern_ref(file.clone());
+        keep.call(Some(old)).unwrap();
+        assert!(store.drop_extern(old));
+        assert_eq!(Rc::strong_count(&file), 1);
+        assert!(!store.drop_extern(old));
+
+        // The slot is reused, the reference the guest kept still doesn't find an object
+        let new = store.extern_ref(String::from("other.txt"));
+        assert_eq!(new.idx, old.idx);
+        let stale = kept.call(()).unwrap().unwrap();
+        assert!(store.extern_object::<String>(stale).is_none());
+        assert!(!store.drop_extern(stale));
+        assert_eq!(*store.extern_object::<String>(new).unwrap(), "other.txt");
+    }
+
+    #[test]
+    fn references_of_other_stores_are_rejected() {
+        let binary = compile(
+            r#"(module
+                (table (export "table") 1 funcref)
+                (global (export "file") (mut externref) (ref.null extern))
+                (func (export "answer") (result i32) (i32.const 42))
+                (func (export "call") (result i32) (call_indirect (result i32) (i32.const 0)))
+                (func (export "id") (param externref) (result externref) (local.get 0)))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let mut other_store = Store::new();
+        let other = other_store.instantiate(&Linker::new(), &module).unwrap();
+
+        let table = instance.get_table("table").unwrap();
+        let foreign_answer = Ref::Func(other.get_func("answer").unwrap().func_ref());
+        assert_eq!(table.set(0, foreign_answer), Err(Trap::ForeignReference));
+        assert_eq!(table.grow(1, foreign_answer), None);
+        let call = instance.get_typed_func::<(), i32>("call").unwrap();
+        assert_eq!(call.call(()), Err(Trap::UninitializedElement));
+        let answer = Ref::Func(instance.get_func("answer").unwrap().func_ref());
+        table.set(0, answer).unwrap();
+        assert_eq!(call.call(()), Ok(42));
+
+        let foreign_file = Value::Ref(Ref::Extern(other_store.extern_ref(1)));
+        assert_eq!(
+            instance.get_global("file").unwrap().set(foreign_file),
+            Err(SetGlobalError::ForeignReference)
+        );
+        let id = instance.get_func("id").unwrap();
+        assert_eq!(id.call(&[foreign_file]).err(), Some(Trap::ForeignReference));
+        assert!(other_store
+            .extern_object::<i32>(store.extern_ref(1))
+            .is_none());
+
+        // Once an instance imported the table it's bound to that store
+        let binary = compile(r#"(module (import "other" "table" (table 1 funcref)))"#);
+        let importer = Module::new(&binary).unwrap();
+        let mut linker = Linker::new();
+        linker.table("other", "table", table);
+        assert!(matches!(
+            other_store.instantiate(&linker, &importer),
+            Err(InstantiationError::ForeignImport { .. })
+        ));
+    }
+
+    #[test]
+    fn fuel() {
+        let binary = compile(
+            r#"(module
+                (func (export "sum") (param $n i32) (result i32) (local $sum i32)
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.eqz (local.get $n)))
+                            (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+                            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                            (br $next)))
+                    (local.get $sum))
+                (func (export "spin") (loop $forever (br $forever))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let sum = instance.get_typed_func::<i32, i32>("sum").unwrap();
+        let spin = instance.get_typed_func::<(), ()>("spin").unwrap();
+
+        assert_eq!(store.fuel(), None);
+        assert_eq!(sum.call(10), Ok(55));
+        assert_eq!(store.fuel_consumed(), 0);
+
+        store.set_fuel(1000);
+        assert_eq!(sum.call(10), Ok(55));
+        let consumed = store.fuel_consumed();
+        assert!(consumed > 0);
+        assert_eq!(store.fuel(), Some(1000 - consumed));
+
+        store.set_fuel(consumed / 2);
+        assert_eq!(sum.call(10), Err(Trap::OutOfFuel));
+        assert_eq!(store.fuel_consumed(), consumed + consumed / 2);
+        // Calls in between don't disturb the suspended one
+        assert_eq!(spin.call(()), Err(Trap::OutOfFuel));
+        store.add_fuel(consumed);
+        assert!(matches!(
+            store.resume(),
+            Some(Ok(results)) if matches!(results[..], [Value::I32(55)])
+        ));
+        assert_eq!(store.fuel_consumed(), 2 * consumed);
+        assert!(store.resume().is_none());
+
+        store.set_fuel(100);
+        store.set_fuel_costs(|instruction| match instruction {
+            Instruction::Break(_) => 10,
+            _ => 0,
+        });
+        assert_eq!(spin.call(()), Err(Trap::OutOfFuel));
+        assert_eq!(store.fuel(), Some(0));
+        assert_eq!(store.fuel_consumed(), 2 * consumed + 100);
+    }
+
+    #[test]
+    fn epoch_interruption() {
+        let binary = compile(
+            r#"(module
+                (func $one (result i32) (i32.const 1))
+                (func (export "one") (result i32) (i32.const 1))
+                (func (export "call") (result i32) (call $one))
+                (func (export "spin") (loop $forever (br $forever))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut store = Store::new();
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let one = instance.get_typed_func::<(), i32>("one").unwrap();
+        let call = instance.get_typed_func::<(), i32>("call").unwrap();
+        let spin = instance.get_typed_func::<(), ()>("spin").unwrap();
+
+        let handle = store.interrupt_handle();
+        handle.increment_epoch();
+        assert_eq!(call.call(()), Ok(1));
+
+        store.set_epoch_deadline(1);
+        assert_eq!(call.call(()), Ok(1));
+        handle.increment_epoch();
+        assert_eq!(one.call(()), Ok(1));
+        assert_eq!(call.call(()), Err(Trap::Interrupted));
+
+        store.set_epoch_deadline(1);
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            handle.increment_epoch();
+        });
+        assert_eq!(spin.call(()), Err(Trap::Interrupted));
+        interrupter.join().unwrap();
+
+        store.clear_epoch_deadline();
+        assert_eq!(call.call(()), Ok(1));
+    }
+
+    #[test]
+    fn stack_limits() {
+        let binary = compile(
+            r#"(module
+                (func $recurse (export "recurse") (param $n i32) (result i32)
+                    (if (result i32) (i32.eqz (local.get $n))
+                        (then (i32.const 0))
+                        (else (i32.add
+                            (local.get $n)
+                            (call $recurse (i32.sub (local.get $n) (i32.const 1))))))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut store = Store::with_config(Config {
+            max_call_depth: 10,
+            ..Config::default()
+        });
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let recurse = instance.get_typed_func::<i32, i32>("recurse").unwrap();
+        assert_eq!(recurse.call(9), Ok(45));
+        assert_eq!(recurse.call(10), Err(Trap::StackOverflow));
+        assert_eq!(recurse.call(9), Ok(45));
+
+        let mut store = Store::with_config(Config {
+            max_stack_size: 100,
+            ..Config::default()
+        });
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let recurse = instance.get_typed_func::<i32, i32>("recurse").unwrap();
+        assert_eq!(recurse.call(10), Ok(55));
+        assert_eq!(recurse.call(1000), Err(Trap::StackOverflow));
+    }
+
+    #[test]
+    fn canonical_nans() {
+        let binary = compile(
+            r#"(module
+                (func (export "div") (param f32 f32) (result f32) (f32.div (local.get 0) (local.get 1)))
+                (func (export "promote") (param f32) (result f64) (f64.promote_f32 (local.get 0)))
+                (func (export "neg") (param f32) (result f32) (f32.neg (local.get 0))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let nan = f32::from_bits(0xffa0_0001);
+
+        let mut store = Store::new();
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let div = instance.get_typed_func::<(f32, f32), f32>("div").unwrap();
+        assert_ne!(div.call((nan, 1.0)).unwrap().to_bits(), 0x7fc0_0000);
+
+        let mut store = Store::with_config(Config {
+            canonicalize_nans: true,
+            ..Config::default()
+        });
+        let instance = store.instantiate(&Linker::new(), &module).unwrap();
+        let div = instance.get_typed_func::<(f32, f32), f32>("div").unwrap();
+        let promote = instance.get_typed_func::<f32, f64>("promote").unwrap();
+        let neg = instance.get_typed_func::<f32, f32>("neg").unwrap();
+        assert_eq!(div.call((nan, 1.0)).unwrap().to_bits(), 0x7fc0_0000);
+        assert_eq!(div.call((0.0, 0.0)).unwrap().to_bits(), 0x7fc0_0000);
+        assert_eq!(div.call((1.0, 2.0)), Ok(0.5));
+        assert_eq!(promote.call(nan).unwrap().to_bits(), 0x7ff8_0000_0000_0000);
+        // Only arithmetic is canonicalized, sign operations just flip the bit
+        assert_eq!(neg.call(nan).unwrap().to_bits(), 0x7fa0_0001);
+    }
+
+    #[test]
+    fn invalid_modules_are_rejected() {
+        let binary = compile(
+            r#"(module
+                (type $void (func))
+                (table 1 funcref)
+                (func (export "call") (call_indirect (type 7) (i32.const 0))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let result = Store::new().instantiate(&Linker::new(), &module);
+        assert!(matches!(result, Err(InstantiationError::Invalid(_))));
+    }
+}
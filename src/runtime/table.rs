@@ -1,89 +1,203 @@
-use crate::types::{Limit, RefType, TableIdx, TableType};
+use std::{
+    cell::{Ref as CellRef, RefCell, RefMut},
+    ops::Range,
+    rc::Rc,
+};
 
-use super::value::Ref;
+use crate::types::{Limit, TableIdx, TableType};
+
+use super::{store::StoreId, trap::Trap, value::Ref};
 
 #[derive(Debug, Clone, Copy)]
 pub struct TableElementIdx(pub usize);
 
+/// A table that can be shared between the host and instances importing it
+#[derive(Debug, Clone)]
+pub struct TableHandle(Rc<RefCell<Table>>);
+
+impl TableHandle {
+    pub fn new(table_type: TableType) -> Self {
+        Self(Rc::new(RefCell::new(Table::new(table_type))))
+    }
+
+    pub fn table_type(&self) -> TableType {
+        self.0.borrow().table_type()
+    }
+
+    pub fn size(&self) -> u32 {
+        self.0.borrow().size() as u32
+    }
+
+    /// Returns `None` if the index is out of bounds
+    pub fn get(&self, idx: u32) -> Option<Ref> {
+        self.0.borrow().get(TableElementIdx(idx as usize)).ok()
+    }
+
+    /// Traps if the reference belongs to another store than the table
+    pub fn set(&self, idx: u32, element: Ref) -> Result<(), Trap> {
+        let mut table = self.0.borrow_mut();
+        if !table.bind(element.store()) {
+            return Err(Trap::ForeignReference);
+        }
+        table.set(TableElementIdx(idx as usize), element)
+    }
+
+    /// Returns the previous size, or `None` if the table can't grow that much or `init`
+    /// belongs to another store than the table
+    pub fn grow(&self, delta: u32, init: Ref) -> Option<u32> {
+        let mut table = self.0.borrow_mut();
+        if !table.bind(init.store()) {
+            return None;
+        }
+        table.grow(delta, init)
+    }
+
+    /// Binds the table to the store of an instance importing it, `false` if it already
+    /// belongs to another one
+    pub(super) fn bind(&self, store: StoreId) -> bool {
+        self.0.borrow_mut().bind(Some(store))
+    }
+}
+
+/// Imported tables followed by the ones defined by the module
 #[derive(Debug)]
-pub struct Tables(Vec<Table>);
+pub struct Tables(Vec<TableHandle>);
 
 impl Tables {
-    pub fn new(table_types: &[TableType]) -> Self {
-        Self(
-            table_types
-                .iter()
-                .map(|TableType(RefType::FuncRef, limit)| Table::new(*limit))
-                .collect(),
-        )
+    pub fn new(imported: Vec<TableHandle>, table_types: &[TableType], store: StoreId) -> Self {
+        let defined = table_types.iter().map(|table_type| {
+            let mut table = Table::new(*table_type);
+            table.store = Some(store);
+            TableHandle(Rc::new(RefCell::new(table)))
+        });
+        Self(imported.into_iter().chain(defined).collect())
     }
 
-    pub fn table_mut(&mut self, TableIdx(table_idx): TableIdx) -> &mut Table {
-        &mut self.0[table_idx as usize]
+    pub fn handle(&self, TableIdx(table_idx): TableIdx) -> TableHandle {
+        self.0[table_idx as usize].clone()
     }
 
-    pub fn table(&self, TableIdx(table_idx): TableIdx) -> &Table {
-        &self.0[table_idx as usize]
+    pub fn table_mut(&self, TableIdx(table_idx): TableIdx) -> RefMut<'_, Table> {
+        self.0[table_idx as usize].0.borrow_mut()
+    }
+
+    pub fn table(&self, TableIdx(table_idx): TableIdx) -> CellRef<'_, Table> {
+        self.0[table_idx as usize].0.borrow()
     }
 
     pub fn copy(
-        &mut self,
+        &self,
         dst_idx: TableIdx,
         src_idx: TableIdx,
         TableElementIdx(dst_offset): TableElementIdx,
         TableElementIdx(src_offset): TableElementIdx,
         len: usize,
-    ) {
-        // Borrow the tables to get access to their elements
-        let mut refs = vec![None; len];
-
-        // Perform the copy
-        let src_table = self.table(src_idx);
-        for (i, ref_value) in refs.iter_mut().enumerate() {
-            let table_value = src_table.get(TableElementIdx(src_offset + i));
-            *ref_value = table_value;
-        }
-
-        let dst_table = self.table_mut(dst_idx);
-        for (i, ref_value) in refs.iter().enumerate() {
-            dst_table.set(TableElementIdx(dst_offset + i), *ref_value);
-        }
+    ) -> Result<(), Trap> {
+        // Read all elements first, both indices may refer to the same table. Filling checks the
+        // whole destination range before writing anything
+        let refs = self
+            .table(src_idx)
+            .refs
+            .get(element_range(src_offset, len)?)
+            .ok_or(Trap::TableOutOfBounds)?
+            .to_vec();
+        self.table_mut(dst_idx)
+            .fill(TableElementIdx(dst_offset), &refs)
     }
 }
 
+/// The elements from `idx` on, trapping if the end can't even be represented
+fn element_range(idx: usize, len: usize) -> Result<Range<usize>, Trap> {
+    let end = idx.checked_add(len).ok_or(Trap::TableOutOfBounds)?;
+    Ok(idx..end)
+}
+
 #[derive(Debug)]
 pub struct Table {
     refs: Vec<Ref>,
+    table_type: TableType,
+    /// The store whose references the table holds, `None` until the host puts one in or an
+    /// instance imports it. References of other stores would point to unrelated instances
+    store: Option<StoreId>,
 }
 
 impl Table {
-    pub fn new(limit: Limit) -> Self {
+    pub fn new(table_type @ TableType(ref_type, limit): TableType) -> Self {
         Self {
-            refs: vec![Default::default(); limit.min as usize],
+            refs: vec![Ref::Null(ref_type); limit.min as usize],
+            table_type,
+            store: None,
+        }
+    }
+
+    /// Binds the table to `store` unless it's `None`, `false` if it belongs to another one
+    fn bind(&mut self, store: Option<StoreId>) -> bool {
+        match (self.store, store) {
+            (_, None) => true,
+            (None, Some(_)) => {
+                self.store = store;
+                true
+            }
+            (Some(own), Some(store)) => own == store,
         }
     }
 
-    pub fn get(&self, TableElementIdx(idx): TableElementIdx) -> Ref {
-        self.refs[idx]
+    /// Element type, current size and the maximum it can grow to
+    pub fn table_type(&self) -> TableType {
+        let TableType(ref_type, Limit { max, .. }) = self.table_type;
+        TableType(
+            ref_type,
+            Limit {
+                min: self.refs.len() as u32,
+                max,
+            },
+        )
     }
 
-    pub fn set(&mut self, TableElementIdx(idx): TableElementIdx, element: Ref) {
-        self.refs[idx] = element
+    pub fn get(&self, TableElementIdx(idx): TableElementIdx) -> Result<Ref, Trap> {
+        self.refs.get(idx).copied().ok_or(Trap::TableOutOfBounds)
     }
 
-    pub fn fill(&mut self, TableElementIdx(idx): TableElementIdx, elements: &[Ref]) {
-        self.refs[idx..idx + elements.len()].copy_from_slice(elements)
+    pub fn set(&mut self, TableElementIdx(idx): TableElementIdx, element: Ref) -> Result<(), Trap> {
+        *self.refs.get_mut(idx).ok_or(Trap::TableOutOfBounds)? = element;
+        Ok(())
     }
 
-    pub fn fill_value(&mut self, TableElementIdx(idx): TableElementIdx, value: Ref, len: usize) {
-        for i in 0..len {
-            self.refs[idx + i] = value;
-        }
+    pub fn fill(
+        &mut self,
+        TableElementIdx(idx): TableElementIdx,
+        elements: &[Ref],
+    ) -> Result<(), Trap> {
+        self.refs
+            .get_mut(element_range(idx, elements.len())?)
+            .ok_or(Trap::TableOutOfBounds)?
+            .copy_from_slice(elements);
+        Ok(())
     }
 
-    pub fn grow(&mut self, len: usize, value: Ref) -> usize {
-        self.refs.resize(len, value);
-        self.refs.len()
+    pub fn fill_value(
+        &mut self,
+        TableElementIdx(idx): TableElementIdx,
+        value: Ref,
+        len: usize,
+    ) -> Result<(), Trap> {
+        self.refs
+            .get_mut(element_range(idx, len)?)
+            .ok_or(Trap::TableOutOfBounds)?
+            .fill(value);
+        Ok(())
+    }
+
+    /// Returns the previous size, or `None` if the table can't grow that much
+    pub fn grow(&mut self, delta: u32, value: Ref) -> Option<u32> {
+        let TableType(_, Limit { max, .. }) = self.table_type;
+        let prev_size = self.refs.len() as u32;
+        let new_size = prev_size.checked_add(delta)?;
+        if max.is_some_and(|max| new_size > max) {
+            return None;
+        }
+        self.refs.resize(new_size as usize, value);
+        Some(prev_size)
     }
 
     pub fn size(&self) -> usize {
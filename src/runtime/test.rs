@@ -2,6 +2,33 @@ use std::fs;
 use std::io;
 use std::process::Command;
 
+use wast::{
+    parser::{self, ParseBuffer},
+    Wat,
+};
+
+use crate::{
+    module::{
+        functions::{Function, LocalFunction},
+        Module,
+    },
+    types::FuncIdx,
+};
+
+/// Encodes a module written in the text format
+pub(crate) fn compile(text: &str) -> Vec<u8> {
+    let buffer = ParseBuffer::new(text).unwrap();
+    parser::parse::<Wat>(&buffer).unwrap().encode().unwrap()
+}
+
+/// A function the module defines itself, not an imported one
+pub(super) fn local_function<'m>(module: &'m Module<'_>, func_idx: FuncIdx) -> &'m LocalFunction {
+    let Some(Function::Local(function)) = module.get_function(func_idx) else {
+        panic!("function {} isn't local", func_idx.0)
+    };
+    function
+}
+
 fn run_test_for_file(path_str: &str) -> io::Result<()> {
     let path = std::path::Path::new(path_str);
     let out_dir = "./out";
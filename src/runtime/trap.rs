@@ -0,0 +1,56 @@
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trap {
+    Unreachable,
+    MemoryOutOfBounds,
+    IntegerDivideByZero,
+    IntegerOverflow,
+    InvalidConversion,
+    IndirectCallTypeMismatch,
+    UninitializedElement,
+    TableOutOfBounds,
+    StackOverflow,
+    /// The store ran out of fuel, see [`Store::set_fuel`](super::Store::set_fuel)
+    OutOfFuel,
+    /// The epoch deadline of the store passed, see
+    /// [`Store::set_epoch_deadline`](super::Store::set_epoch_deadline)
+    Interrupted,
+    /// A function was called from the host with arguments not matching its signature
+    ArgumentMismatch,
+    /// A host function returned values not matching its signature
+    HostResultMismatch,
+    /// A reference was used outside of the store it was created in, or its instance is gone
+    ForeignReference,
+    /// A host function failed with the given message
+    Host(String),
+    /// The guest asked to terminate with the given exit code (`proc_exit`)
+    Exit(i32),
+}
+
+impl Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trap::Unreachable => write!(f, "unreachable"),
+            Trap::MemoryOutOfBounds => write!(f, "out of bounds memory access"),
+            Trap::IntegerDivideByZero => write!(f, "integer divide by zero"),
+            Trap::IntegerOverflow => write!(f, "integer overflow"),
+            Trap::InvalidConversion => write!(f, "invalid conversion to integer"),
+            Trap::IndirectCallTypeMismatch => write!(f, "indirect call type mismatch"),
+            Trap::UninitializedElement => write!(f, "uninitialized element"),
+            Trap::TableOutOfBounds => write!(f, "out of bounds table access"),
+            Trap::StackOverflow => write!(f, "call stack exhausted"),
+            Trap::OutOfFuel => write!(f, "all fuel consumed"),
+            Trap::Interrupted => write!(f, "interrupted"),
+            Trap::ArgumentMismatch => write!(f, "arguments do not match the function signature"),
+            Trap::HostResultMismatch => {
+                write!(f, "host function results do not match its signature")
+            }
+            Trap::ForeignReference => write!(f, "reference belongs to another store"),
+            Trap::Host(message) => write!(f, "{}", message),
+            Trap::Exit(code) => write!(f, "exited with code {}", code),
+        }
+    }
+}
+
+impl std::error::Error for Trap {}
@@ -0,0 +1,249 @@
+use std::{marker::PhantomData, rc::Rc};
+
+use crate::types::{FuncIdx, FuncType, NumericValueType, RefType, ValueType};
+
+use super::{
+    value::{ExternRef, FuncRef, Ref},
+    Runtime, Trap, Value,
+};
+
+/// A Rust type that can be passed to or returned from a function of a module
+pub trait WasmType: Sized {
+    const VALUE_TYPE: ValueType;
+
+    fn into_value(self) -> Value;
+
+    /// Only called with values of [`Self::VALUE_TYPE`]
+    fn from_value(value: Value) -> Self;
+}
+
+macro_rules! impl_wasm_type {
+    ($ty:ty, $variant:ident, $value_type:expr) => {
+        impl WasmType for $ty {
+            const VALUE_TYPE: ValueType = $value_type;
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: Value) -> Self {
+                let Value::$variant(value) = value else {
+                    unreachable!("Value was checked to be {:?}", Self::VALUE_TYPE);
+                };
+                value
+            }
+        }
+    };
+}
+
+impl_wasm_type!(i32, I32, ValueType::Numeric(NumericValueType::I32));
+impl_wasm_type!(i64, I64, ValueType::Numeric(NumericValueType::I64));
+impl_wasm_type!(f32, F32, ValueType::Numeric(NumericValueType::F32));
+impl_wasm_type!(f64, F64, ValueType::Numeric(NumericValueType::F64));
+
+macro_rules! impl_wasm_ref_type {
+    ($ty:ty, $variant:ident, $ref_type:expr) => {
+        /// `None` is the null reference
+        impl WasmType for Option<$ty> {
+            const VALUE_TYPE: ValueType = ValueType::Ref($ref_type);
+
+            fn into_value(self) -> Value {
+                Value::Ref(match self {
+                    Some(value) => Ref::$variant(value),
+                    None => Ref::Null($ref_type),
+                })
+            }
+
+            fn from_value(value: Value) -> Self {
+                match value {
+                    Value::Ref(Ref::$variant(value)) => Some(value),
+                    Value::Ref(Ref::Null(_)) => None,
+                    _ => unreachable!("Value was checked to be {:?}", Self::VALUE_TYPE),
+                }
+            }
+        }
+    };
+}
+
+impl_wasm_ref_type!(FuncRef, Func, RefType::FuncRef);
+impl_wasm_ref_type!(ExternRef, Extern, RefType::ExternRef);
+
+/// Arguments of a [`TypedFunc`], a single [`WasmType`] or a tuple of them
+pub trait WasmParams {
+    type Values: AsRef<[Value]>;
+
+    fn value_types() -> Vec<ValueType>;
+
+    fn into_values(self) -> Self::Values;
+}
+
+/// Results of a [`TypedFunc`], a single [`WasmType`] or a tuple of them
+pub trait WasmResults: Sized {
+    fn value_types() -> Vec<ValueType>;
+
+    /// Only called with values of [`Self::value_types`]
+    fn from_values(values: Vec<Value>) -> Self;
+}
+
+impl<T: WasmType> WasmParams for T {
+    type Values = [Value; 1];
+
+    fn value_types() -> Vec<ValueType> {
+        vec![T::VALUE_TYPE]
+    }
+
+    fn into_values(self) -> Self::Values {
+        [self.into_value()]
+    }
+}
+
+impl<T: WasmType> WasmResults for T {
+    fn value_types() -> Vec<ValueType> {
+        vec![T::VALUE_TYPE]
+    }
+
+    fn from_values(values: Vec<Value>) -> Self {
+        let [value] = values[..] else {
+            unreachable!("Function returns a single value");
+        };
+        T::from_value(value)
+    }
+}
+
+macro_rules! impl_wasm_tuple {
+    ($amount:literal $(, $t:ident)*) => {
+        impl<$($t: WasmType),*> WasmParams for ($($t,)*) {
+            type Values = [Value; $amount];
+
+            fn value_types() -> Vec<ValueType> {
+                vec![$($t::VALUE_TYPE),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_values(self) -> Self::Values {
+                let ($($t,)*) = self;
+                [$($t.into_value()),*]
+            }
+        }
+
+        impl<$($t: WasmType),*> WasmResults for ($($t,)*) {
+            fn value_types() -> Vec<ValueType> {
+                vec![$($t::VALUE_TYPE),*]
+            }
+
+            #[allow(unused_mut, unused_variables, clippy::unused_unit)]
+            fn from_values(values: Vec<Value>) -> Self {
+                let mut values = values.into_iter();
+                ($($t::from_value(values.next().expect("Amount of results was checked")),)*)
+            }
+        }
+    };
+}
+
+impl_wasm_tuple!(0);
+impl_wasm_tuple!(1, A);
+impl_wasm_tuple!(2, A, B);
+impl_wasm_tuple!(3, A, B, C);
+impl_wasm_tuple!(4, A, B, C, D);
+impl_wasm_tuple!(5, A, B, C, D, E);
+impl_wasm_tuple!(6, A, B, C, D, E, F);
+impl_wasm_tuple!(7, A, B, C, D, E, F, G);
+impl_wasm_tuple!(8, A, B, C, D, E, F, G, H);
+
+/// An exported function whose signature was checked against `Params` and `Results` on lookup
+///
+/// Looked up with [`Instance::get_typed_func`](super::Instance::get_typed_func), calls then
+/// take and return plain Rust values instead of [`Value`]s: tuples of up to 8 of `i32`, `i64`,
+/// `f32`, `f64`, `Option<FuncRef>` and `Option<ExternRef>`, or a single one of them
+pub struct TypedFunc<'b, 'a, Params, Results> {
+    runtime: Rc<Runtime<'b, 'a>>,
+    func_idx: FuncIdx,
+    _signature: PhantomData<fn(Params) -> Results>,
+}
+
+impl<'b, 'a, Params: WasmParams, Results: WasmResults> TypedFunc<'b, 'a, Params, Results> {
+    /// Returns `None` if the signature doesn't match `Params` and `Results`
+    pub(super) fn new(
+        runtime: Rc<Runtime<'b, 'a>>,
+        func_idx: FuncIdx,
+        signature: &FuncType,
+    ) -> Option<Self> {
+        if signature.params != Params::value_types() || signature.returns != Results::value_types()
+        {
+            return None;
+        }
+        Some(Self {
+            runtime,
+            func_idx,
+            _signature: PhantomData,
+        })
+    }
+
+    pub fn call(&self, params: Params) -> Result<Results, Trap> {
+        let results = self
+            .runtime
+            .call(self.func_idx, params.into_values().as_ref())?;
+        Ok(Results::from_values(results))
+    }
+}
+
+impl<Params, Results> Clone for TypedFunc<'_, '_, Params, Results> {
+    fn clone(&self) -> Self {
+        Self {
+            runtime: self.runtime.clone(),
+            func_idx: self.func_idx,
+            _signature: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        module::Module,
+        runtime::{test::compile, Instance},
+    };
+
+    #[test]
+    fn typed_func_calls() {
+        let binary = compile(
+            r#"(module
+                (func (export "compute") (param i32 i64) (result f64)
+                    (f64.add
+                        (f64.convert_i32_s (local.get 0))
+                        (f64.convert_i64_s (local.get 1))))
+                (func (export "swap") (param i32 f32) (result f32 i32)
+                    (local.get 1) (local.get 0))
+                (func (export "nothing")))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let instance = Instance::new(&module).unwrap();
+
+        let compute = instance
+            .get_typed_func::<(i32, i64), f64>("compute")
+            .unwrap();
+        assert_eq!(compute.call((2, 40)), Ok(42.0));
+
+        let swap = instance
+            .get_typed_func::<(i32, f32), (f32, i32)>("swap")
+            .unwrap();
+        assert_eq!(swap.call((1, 2.5)), Ok((2.5, 1)));
+
+        let nothing = instance.get_typed_func::<(), ()>("nothing").unwrap();
+        assert_eq!(nothing.call(()), Ok(()));
+    }
+
+    #[test]
+    fn typed_func_signature_mismatch() {
+        let binary =
+            compile(r#"(module (func (export "id") (param i32) (result i32) (local.get 0)))"#);
+        let module = Module::new(&binary).unwrap();
+        let instance = Instance::new(&module).unwrap();
+
+        assert!(instance.get_typed_func::<i32, i32>("id").is_some());
+        assert!(instance.get_typed_func::<i64, i32>("id").is_none());
+        assert!(instance.get_typed_func::<i32, ()>("id").is_none());
+        assert!(instance.get_typed_func::<(i32, i32), i32>("id").is_none());
+        assert!(instance.get_typed_func::<i32, i32>("missing").is_none());
+    }
+}
@@ -1,6 +1,103 @@
-use crate::types::FuncIdx;
+use crate::types::{FuncIdx, NumericValueType, RefType, ValueType};
 
-pub type Ref = Option<FuncIdx>;
+use super::store::{InstanceId, StoreId};
+
+/// A function of an instance, the instance may be any of the store the reference was created in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuncRef {
+    pub(super) store: StoreId,
+    pub(super) instance: InstanceId,
+    pub(super) func_idx: FuncIdx,
+}
+
+/// A host object owned by the store the reference was created in, see [`Store::extern_ref`]
+///
+/// [`Store::extern_ref`]: super::Store::extern_ref
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExternRef {
+    pub(super) store: StoreId,
+    pub(super) generation: u32,
+    pub(super) idx: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ref {
+    Null(RefType),
+    Func(FuncRef),
+    Extern(ExternRef),
+}
+
+impl Ref {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Ref::Null(_))
+    }
+
+    pub fn ref_type(&self) -> RefType {
+        match self {
+            Ref::Null(ref_type) => *ref_type,
+            Ref::Func(_) => RefType::FuncRef,
+            Ref::Extern(_) => RefType::ExternRef,
+        }
+    }
+
+    /// The store the reference belongs to, `None` for null references which belong to any
+    pub(super) fn store(&self) -> Option<StoreId> {
+        match self {
+            Ref::Null(_) => None,
+            Ref::Func(func_ref) => Some(func_ref.store),
+            Ref::Extern(extern_ref) => Some(extern_ref.store),
+        }
+    }
+
+    /// The reference as an operand of the engines. The top two bits tell which kind it is, so
+    /// instructions like `ref.is_null` don't need its type. A function reference keeps the
+    /// instance in the 30 bits above its function index, an extern reference the generation of
+    /// its slot. The store is left out, the operands of a store only ever hold its own
+    /// references
+    pub(super) fn to_bits(self) -> u64 {
+        match self {
+            Ref::Null(RefType::FuncRef) => REF_NULL,
+            Ref::Null(RefType::ExternRef) => REF_NULL | 1,
+            Ref::Func(FuncRef {
+                instance: InstanceId(instance),
+                func_idx: FuncIdx(func_idx),
+                ..
+            }) => {
+                debug_assert!(
+                    instance < 1 << 30,
+                    "Too many instances for a function reference"
+                );
+                REF_FUNC | ((instance as u64) << 32) | func_idx as u64
+            }
+            Ref::Extern(ExternRef {
+                generation, idx, ..
+            }) => REF_EXTERN | ((generation as u64) << 32) | idx as u64,
+        }
+    }
+
+    /// The reference an operand of the store's engines holds
+    pub(super) fn from_bits(bits: u64, store: StoreId) -> Ref {
+        match bits & REF_KIND {
+            REF_NULL if bits & 1 == 0 => Ref::Null(RefType::FuncRef),
+            REF_NULL => Ref::Null(RefType::ExternRef),
+            REF_FUNC => Ref::Func(FuncRef {
+                store,
+                instance: InstanceId((bits >> 32) as u32 & !(3 << 30)),
+                func_idx: FuncIdx(bits as u32),
+            }),
+            _ => Ref::Extern(ExternRef {
+                store,
+                generation: (bits >> 32) as u32 & !(3 << 30),
+                idx: bits as u32,
+            }),
+        }
+    }
+}
+
+const REF_KIND: u64 = 3 << 62;
+const REF_NULL: u64 = 0;
+const REF_FUNC: u64 = 1 << 62;
+const REF_EXTERN: u64 = 2 << 62;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Value {
@@ -10,3 +107,56 @@ pub enum Value {
     F64(f64),
     Ref(Ref),
 }
+
+impl Value {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Value::I32(_) => ValueType::Numeric(NumericValueType::I32),
+            Value::I64(_) => ValueType::Numeric(NumericValueType::I64),
+            Value::F32(_) => ValueType::Numeric(NumericValueType::F32),
+            Value::F64(_) => ValueType::Numeric(NumericValueType::F64),
+            Value::Ref(value) => ValueType::Ref(value.ref_type()),
+        }
+    }
+
+    /// The value as an operand of the engines, which only keep its bits. Validation makes
+    /// sure every instruction knows the type of its operands
+    pub(super) fn to_bits(self) -> u64 {
+        match self {
+            Value::I32(value) => value as u32 as u64,
+            Value::I64(value) => value as u64,
+            Value::F32(value) => value.to_bits() as u64,
+            Value::F64(value) => value.to_bits(),
+            Value::Ref(value) => value.to_bits(),
+        }
+    }
+
+    /// Whether the value can be used in the store, only references of other stores can't
+    pub(super) fn belongs_to(&self, store: StoreId) -> bool {
+        match self {
+            Value::Ref(value) => value.store().is_none_or(|owner| owner == store),
+            _ => true,
+        }
+    }
+
+    pub(super) fn from_bits(bits: u64, value_type: ValueType, store: StoreId) -> Value {
+        match value_type {
+            ValueType::Numeric(NumericValueType::I32) => Value::I32(bits as i32),
+            ValueType::Numeric(NumericValueType::I64) => Value::I64(bits as i64),
+            ValueType::Numeric(NumericValueType::F32) => Value::F32(f32::from_bits(bits as u32)),
+            ValueType::Numeric(NumericValueType::F64) => Value::F64(f64::from_bits(bits)),
+            ValueType::Ref(_) => Value::Ref(Ref::from_bits(bits, store)),
+        }
+    }
+
+    /// The value a local of the type starts with
+    pub(super) fn default(value_type: ValueType) -> Value {
+        match value_type {
+            ValueType::Numeric(NumericValueType::I32) => Value::I32(0),
+            ValueType::Numeric(NumericValueType::I64) => Value::I64(0),
+            ValueType::Numeric(NumericValueType::F32) => Value::F32(0.0),
+            ValueType::Numeric(NumericValueType::F64) => Value::F64(0.0),
+            ValueType::Ref(ref_type) => Value::Ref(Ref::Null(ref_type)),
+        }
+    }
+}
@@ -24,6 +24,9 @@ impl Variable {
             (Value::F64(ref mut val), Value::F64(new_value)) => {
                 *val = new_value;
             }
+            (Value::Ref(ref mut val), Value::Ref(new_value)) => {
+                *val = new_value;
+            }
 
             _ => {
                 panic!(
@@ -37,49 +40,4 @@ impl Variable {
     pub fn from_value(value: Value) -> Variable {
         Variable { value }
     }
-
-    pub fn from_i32(value: i32) -> Variable {
-        Variable {
-            value: Value::I32(value),
-        }
-    }
-
-    pub fn from_i64(value: i64) -> Variable {
-        Variable {
-            value: Value::I64(value),
-        }
-    }
-
-    pub fn from_f32(value: f32) -> Variable {
-        Variable {
-            value: Value::F32(value),
-        }
-    }
-
-    pub fn from_f64(value: f64) -> Variable {
-        Variable {
-            value: Value::F64(value),
-        }
-    }
-
-    pub fn from_i32_default() -> Variable {
-        Variable {
-            value: Value::I32(0),
-        }
-    }
-    pub fn from_i64_default() -> Variable {
-        Variable {
-            value: Value::I64(0),
-        }
-    }
-    pub fn from_f32_default() -> Variable {
-        Variable {
-            value: Value::F32(0.0),
-        }
-    }
-    pub fn from_f64_default() -> Variable {
-        Variable {
-            value: Value::F64(0.0),
-        }
-    }
 }
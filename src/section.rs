@@ -1,4 +1,8 @@
-use nom::{bytes::complete::take, number::complete::le_u8, sequence::pair, IResult};
+use std::collections::HashMap;
+
+use nom::{bytes::complete::take, number::complete::le_u8, sequence::pair};
+
+use crate::parse_error::{fail, IResult, InputError, ParseError, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use crate::types::name;
@@ -40,6 +44,45 @@ pub enum SectionType<'a> {
     DataCount,
 }
 
+impl SectionType<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SectionType::Custom(_) => "custom",
+            SectionType::Type => "type",
+            SectionType::Import => "import",
+            SectionType::Function => "function",
+            SectionType::Table => "table",
+            SectionType::Memory => "memory",
+            SectionType::Global => "global",
+            SectionType::Export => "export",
+            SectionType::Start => "start",
+            SectionType::Element => "element",
+            SectionType::Code => "code",
+            SectionType::Data => "data",
+            SectionType::DataCount => "data count",
+        }
+    }
+
+    /// Position the section must appear in, custom sections may appear anywhere
+    pub fn order(&self) -> Option<u8> {
+        Some(match self {
+            SectionType::Custom(_) => return None,
+            SectionType::Type => 1,
+            SectionType::Import => 2,
+            SectionType::Function => 3,
+            SectionType::Table => 4,
+            SectionType::Memory => 5,
+            SectionType::Global => 6,
+            SectionType::Export => 7,
+            SectionType::Start => 8,
+            SectionType::Element => 9,
+            SectionType::DataCount => 10,
+            SectionType::Code => 11,
+            SectionType::Data => 12,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum Section<'a> {
     Custom(&'a str, &'a [u8]),
@@ -64,6 +107,17 @@ fn parse_section(input: &[u8]) -> IResult<&[u8], (u8, &[u8])> {
     Ok((input, (code, section_data)))
 }
 
+fn parse_whole_section<'a, T>(
+    section_data: &'a [u8],
+    parse: impl FnOnce(&'a [u8]) -> IResult<&'a [u8], T>,
+) -> IResult<&'a [u8], T> {
+    let (rest, value) = parse(section_data)?;
+    if !rest.is_empty() {
+        return fail(rest, ParseErrorKind::SectionSizeMismatch);
+    }
+    Ok((rest, value))
+}
+
 impl<'a> Section<'a> {
     pub fn get_variant(&self) -> SectionType<'a> {
         match self {
@@ -83,66 +137,174 @@ impl<'a> Section<'a> {
         }
     }
 
+    pub fn order(&self) -> Option<u8> {
+        self.get_variant().order()
+    }
+
     pub fn parse(input: &'a [u8]) -> IResult<&'a [u8], Section<'a>> {
+        // Checked before the size, so the error points at the id byte itself
+        let (_, code) = le_u8(input)?;
+        let Some(section_type) = Self::section_type(code) else {
+            return fail(input, ParseErrorKind::MalformedSectionId(code));
+        };
         let (input, (code, section_data)) = parse_section(input)?;
+        let section = Self::parse_section_data(code, section_data).map_err(|err| {
+            let section_name = section_type.name();
+            match err {
+                nom::Err::Incomplete(_) => nom::Err::Failure(
+                    InputError::new(
+                        &section_data[section_data.len()..],
+                        ParseErrorKind::UnexpectedEnd,
+                    )
+                    .in_section(section_name),
+                ),
+                err => err.map(|err| err.in_section(section_name)),
+            }
+        })?;
+
+        Ok((input, section))
+    }
+
+    fn section_type(code: u8) -> Option<SectionType<'static>> {
+        Some(match code {
+            0 => SectionType::Custom(""),
+            1 => SectionType::Type,
+            2 => SectionType::Import,
+            3 => SectionType::Function,
+            4 => SectionType::Table,
+            5 => SectionType::Memory,
+            6 => SectionType::Global,
+            7 => SectionType::Export,
+            8 => SectionType::Start,
+            9 => SectionType::Element,
+            10 => SectionType::Code,
+            11 => SectionType::Data,
+            12 => SectionType::DataCount,
+            _ => return None,
+        })
+    }
+
+    fn parse_section_data(
+        code: u8,
+        section_data: &'a [u8],
+    ) -> Result<Section<'a>, nom::Err<InputError<&'a [u8]>>> {
         let section = match code {
             0 => {
                 let (section_data, name) = name(section_data)?;
                 Section::Custom(name, section_data)
             }
             1 => {
-                let (_, type_section) = TypeSection::parse(section_data)?;
+                let (_, type_section) = parse_whole_section(section_data, TypeSection::parse)?;
                 Section::Type(type_section)
             }
             2 => {
-                let (_, import_section) = ImportSection::parse(section_data)?;
+                let (_, import_section) = parse_whole_section(section_data, ImportSection::parse)?;
                 Section::Import(import_section)
             }
             3 => {
-                let (_, function_section) = FunctionSection::parse(section_data)?;
+                let (_, function_section) =
+                    parse_whole_section(section_data, FunctionSection::parse)?;
                 Section::Function(function_section)
             }
             4 => {
-                let (_, table_section) = TableSection::parse(section_data)?;
+                let (_, table_section) = parse_whole_section(section_data, TableSection::parse)?;
                 Section::Table(table_section)
             }
             5 => {
-                let (_, memory_section) = MemorySection::parse(section_data)?;
+                let (_, memory_section) = parse_whole_section(section_data, MemorySection::parse)?;
                 Section::Memory(memory_section)
             }
             7 => {
-                let (_, export_section) = ExportSection::parse(section_data)?;
+                let (_, export_section) = parse_whole_section(section_data, ExportSection::parse)?;
                 Section::Export(export_section)
             }
             6 => {
-                let (_, global_section) = GlobalSection::parse(section_data)?;
+                let (_, global_section) = parse_whole_section(section_data, GlobalSection::parse)?;
                 Section::Global(global_section)
             }
             8 => {
-                let (_, start_section) = StartSection::parse(section_data)?;
+                let (_, start_section) = parse_whole_section(section_data, StartSection::parse)?;
                 Section::Start(start_section)
             }
             9 => {
-                let (_, elements) = ElementSection::parse(section_data)?;
+                let (_, elements) = parse_whole_section(section_data, ElementSection::parse)?;
                 Section::Element(elements)
             }
             10 => {
-                let (_, code_section) = CodeSection::parse(section_data)?;
+                let (_, code_section) = parse_whole_section(section_data, CodeSection::parse)?;
                 Section::Code(code_section)
             }
             11 => {
-                let (_, data_section) = DataSection::parse(section_data)?;
+                let (_, data_section) = parse_whole_section(section_data, DataSection::parse)?;
                 Section::Data(data_section)
             }
             12 => {
-                let (_, amount_of_datas) = DataCountSection::parse(section_data)?;
+                let (_, amount_of_datas) =
+                    parse_whole_section(section_data, DataCountSection::parse)?;
                 Section::DataCount(amount_of_datas)
             }
-            _ => {
-                panic!("Invalid section type {}", code);
-            }
+            _ => unreachable!("Section id was checked before parsing"),
         };
 
-        Ok((input, section))
+        Ok(section)
+    }
+}
+
+/// The sections of a module, along with where each one starts in the binary
+#[derive(Debug)]
+pub struct Sections<'a> {
+    sections: HashMap<SectionType<'a>, Section<'a>>,
+    offsets: HashMap<SectionType<'a>, usize>,
+}
+
+impl<'a> Sections<'a> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            sections: HashMap::with_capacity(capacity),
+            offsets: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Custom sections may repeat, only the first one with a given name is kept
+    pub fn insert(&mut self, offset: usize, section: Section<'a>) {
+        let section_type = section.get_variant();
+        if self.sections.contains_key(&section_type) {
+            return;
+        }
+        self.offsets.insert(section.get_variant(), offset);
+        self.sections.insert(section_type, section);
+    }
+
+    pub fn get(&self, section_type: &SectionType<'a>) -> Option<&Section<'a>> {
+        self.sections.get(section_type)
+    }
+
+    pub fn get_mut(&mut self, section_type: &SectionType<'a>) -> Option<&mut Section<'a>> {
+        self.sections.get_mut(section_type)
+    }
+
+    pub fn remove(&mut self, section_type: &SectionType<'a>) -> Option<Section<'a>> {
+        self.sections.remove(section_type)
+    }
+
+    pub fn error(&self, section_type: SectionType<'a>, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            offset: self.offsets.get(&section_type).copied().unwrap_or_default(),
+            section: Some(section_type.name()),
+            kind,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{module::Module, parse_error::ParseErrorKind};
+
+    #[test]
+    fn malformed_section_ids_point_at_the_id() {
+        let binary = b"\0asm\x01\0\0\0\x0e\x01\0";
+        let err = Module::new(binary).err().unwrap();
+        assert_eq!(err.kind, ParseErrorKind::MalformedSectionId(14));
+        assert_eq!(err.offset, 8);
     }
 }
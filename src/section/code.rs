@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 
 use crate::types::{wasm_vec, FunctionCode};
 
@@ -7,6 +7,10 @@ pub struct CodeSection {
     pub functions: Vec<FunctionCode>,
 }
 impl CodeSection {
+    pub fn empty() -> Self {
+        Self { functions: vec![] }
+    }
+
     pub fn parse(input: &[u8]) -> IResult<&[u8], CodeSection> {
         let (input, functions) = wasm_vec(FunctionCode::parse)(input)?;
         Ok((input, CodeSection { functions }))
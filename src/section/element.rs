@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 
 use crate::types::{wasm_vec, ElementDeclaration};
 
@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 
 use crate::types::{wasm_vec, Export};
 
@@ -8,7 +8,7 @@ pub struct ExportSection<'a> {
 }
 
 impl ExportSection<'_> {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], ExportSection> {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], ExportSection<'_>> {
         let (input, exports) = wasm_vec(Export::parse)(input)?;
         Ok((input, ExportSection { exports }))
     }
@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 
 use crate::types::{wasm_vec, FuncIdx, FuncTypeIdx};
 
@@ -8,6 +8,18 @@ pub struct FunctionSection {
 }
 
 impl FunctionSection {
+    pub fn empty() -> Self {
+        Self { functions: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
     pub fn get_func_type_idx(&self, FuncIdx(idx): FuncIdx) -> Option<FuncTypeIdx> {
         self.functions.get(idx as usize).copied()
     }
@@ -1,4 +1,6 @@
-use nom::{sequence::pair, IResult, Parser};
+use nom::{sequence::pair, Parser};
+
+use crate::parse_error::IResult;
 
 use crate::{
     module::functions::{Function, LocalFunction},
@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 
 use crate::types::{wasm_vec, Import};
 
@@ -6,7 +6,7 @@ use crate::types::{wasm_vec, Import};
 pub struct ImportSection<'a>(pub Vec<Import<'a>>);
 
 impl ImportSection<'_> {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], ImportSection> {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], ImportSection<'_>> {
         let (input, imports) = wasm_vec(Import::parse)(input)?;
         Ok((input, ImportSection(imports)))
     }
@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 
 use crate::types::{wasm_vec, MemoryType};
 
@@ -9,8 +9,10 @@ pub struct MemorySection {
 
 impl MemorySection {
     pub fn parse(input: &[u8]) -> IResult<&[u8], MemorySection> {
-        let (input, mems) = wasm_vec(MemoryType::parse)(input)?;
-        assert_eq!(mems.len(), 1, "Only one memory supported");
-        Ok((input, MemorySection { memories: mems }))
+        let (rest, mems) = wasm_vec(MemoryType::parse)(input)?;
+        if mems.len() > 1 {
+            return fail(input, ParseErrorKind::MultipleMemories);
+        }
+        Ok((rest, MemorySection { memories: mems }))
     }
 }
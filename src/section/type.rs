@@ -1,7 +1,7 @@
-use std::rc::Rc;
+use std::{rc::Rc, slice};
 
-use crate::types::{wasm_vec, FuncType, FuncTypeIdx};
-use nom::IResult;
+use crate::parse_error::IResult;
+use crate::types::{wasm_vec, BlockType, FuncType, FuncTypeIdx, ValueType};
 
 #[derive(Debug)]
 pub struct TypeSection {
@@ -21,7 +21,30 @@ impl TypeSection {
         Ok((input, TypeSection { funcs }))
     }
 
+    pub fn len(&self) -> usize {
+        self.funcs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.funcs.is_empty()
+    }
+
     pub fn get_function_type(&self, FuncTypeIdx(idx): FuncTypeIdx) -> Option<Rc<FuncType>> {
         self.funcs.get(idx as usize).cloned()
     }
+
+    /// The params and results of a block, `None` if it refers to an unknown type
+    pub fn block_signature<'s>(
+        &'s self,
+        block_type: &'s BlockType,
+    ) -> Option<(&'s [ValueType], &'s [ValueType])> {
+        match block_type {
+            BlockType::Empty => Some((&[], &[])),
+            BlockType::Value(value_type) => Some((&[], slice::from_ref(value_type))),
+            BlockType::Type(FuncTypeIdx(idx)) => {
+                let func_type = self.funcs.get(*idx as usize)?;
+                Some((&func_type.params, &func_type.returns))
+            }
+        }
+    }
 }
@@ -1,11 +1,13 @@
 use nom::{
-    bytes::complete::take,
+    bytes::complete::{tag, take},
     error::{ContextError, ParseError},
     multi::count,
-    IResult, Parser,
+    Parser,
 };
 use nom_leb128::leb128_u32;
 
+use crate::parse_error::{fail, IResult, InputError, ParseErrorKind};
+
 mod block_type;
 mod code;
 mod data;
@@ -53,12 +55,24 @@ pub use code::Expr;
 
 pub fn name(input: &[u8]) -> IResult<&[u8], &str> {
     let (input, name_len) = leb128_u32(input)?;
-    let (input, name) = take(name_len)(input)?;
-    let name = std::str::from_utf8(name).unwrap();
-    Ok((input, name))
+    let (rest, name) = take(name_len)(input)?;
+    let Ok(name) = std::str::from_utf8(name) else {
+        return fail(input, ParseErrorKind::MalformedUtf8);
+    };
+    Ok((rest, name))
+}
+
+/// Parses the reserved zero byte that follows some instructions and element kinds
+pub fn zero_byte(input: &[u8]) -> IResult<&[u8], ()> {
+    let (input, _) = tag(&[0][..])(input).map_err(|_: nom::Err<InputError<&[u8]>>| {
+        nom::Err::Failure(InputError::new(input, ParseErrorKind::ZeroByteExpected))
+    })?;
+    Ok((input, ()))
 }
 
-pub fn wasm_vec<'a, T, F, E>(mut parse: F) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Vec<T>, E>
+pub fn wasm_vec<'a, T, F, E>(
+    mut parse: F,
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], Vec<T>, E>
 where
     T: 'a,
     F: Parser<&'a [u8], T, E>,
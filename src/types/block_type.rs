@@ -1,19 +1,38 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+use nom_leb128::leb128_i64;
 
-use super::ValueType;
+use crate::parse_error::{fail, IResult, ParseErrorKind};
+
+use super::{FuncTypeIdx, ValueType};
 
 #[derive(Debug, Clone, Copy)]
-pub struct BlockType(pub Option<ValueType>);
+pub enum BlockType {
+    Empty,
+    Value(ValueType),
+    /// Takes the params and leaves the results of the function type
+    Type(FuncTypeIdx),
+}
 
 impl BlockType {
     pub fn parse(input: &[u8]) -> IResult<&[u8], BlockType> {
-        let (input, value) = u8(input)?;
-        Ok((
-            input,
-            BlockType(match value {
-                0x40 => None,
-                v => Some(ValueType::try_from(v).expect("Valid blocktype valuetype")),
-            }),
-        ))
+        let Some(&first) = input.first() else {
+            return fail(input, ParseErrorKind::UnexpectedEnd);
+        };
+        if first == 0x40 {
+            let (rest, _) = u8(input)?;
+            return Ok((rest, BlockType::Empty));
+        }
+        if let Ok(value_type) = ValueType::try_from(first) {
+            let (rest, _) = u8(input)?;
+            return Ok((rest, BlockType::Value(value_type)));
+        }
+
+        // Type indices are encoded as a positive signed 33 bit integer, so they can't be
+        // mistaken for the negative single byte value types
+        let (rest, idx) = leb128_i64(input)?;
+        match u32::try_from(idx) {
+            Ok(idx) => Ok((rest, BlockType::Type(FuncTypeIdx(idx)))),
+            Err(_) => fail(input, ParseErrorKind::MalformedValueType(first)),
+        }
     }
 }
@@ -4,9 +4,11 @@ use std::{
     rc::Rc,
 };
 
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
 
-use crate::{runtime::function_state::InstructionIndex, types::BlockType};
+use crate::parse_error::{fail, IResult, ParseErrorKind};
+
+use crate::types::BlockType;
 
 use super::{instruction::BlockIdx, Instruction};
 
@@ -41,6 +43,14 @@ impl Expr {
         self.blocks.get(block_idx).is_loop()
     }
 
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.expr
+    }
+
+    pub fn block_instructions(&self, block_idx: BlockIdx) -> &[Instruction] {
+        self.blocks.get(block_idx).instructions()
+    }
+
     pub fn from_raw_instructions(instructions: Vec<Instruction>) -> Self {
         Self {
             expr: Instructions(instructions),
@@ -48,32 +58,12 @@ impl Expr {
         }
     }
 
-    pub fn amount_of_instructions_in_block(&self, block_idx: BlockIdx) -> usize {
-        self.blocks.get(block_idx).0.len()
+    pub fn amount_of_blocks(&self) -> usize {
+        self.blocks.0.len()
     }
 
-    pub fn get_instruction(&self, state: InstructionIndex) -> &Instruction {
-        match state {
-            InstructionIndex::IndexInFunction(i) => &self.expr[i],
-            InstructionIndex::IndexInBlock {
-                block_idx,
-                index_in_block: i,
-                ..
-            } => {
-                let current_block = &self.blocks.get(block_idx);
-                &current_block.instructions()[i]
-            }
-        }
-    }
-    pub fn done(&self, state: InstructionIndex) -> bool {
-        match state {
-            InstructionIndex::IndexInFunction(i) => self.expr.0.len() == i,
-            InstructionIndex::IndexInBlock {
-                block_idx,
-                index_in_block: i,
-                ..
-            } => self.blocks.get(block_idx).instructions().len() == i,
-        }
+    pub fn amount_of_instructions_in_block(&self, block_idx: BlockIdx) -> usize {
+        self.blocks.get(block_idx).0.len()
     }
 }
 
@@ -99,13 +89,13 @@ impl Instructions {
         block_stack: Rc<RefCell<Vec<BlockIdx>>>,
         reached_end: impl Fn(u8) -> (bool, u8),
     ) -> IResult<&[u8], (Self, u8)> {
-        if input.is_empty() {
-            return Ok((input, (Self::empty(), 0x0B)));
-        }
         let mut instructions = vec![];
         let ending_byte = loop {
             let instruction;
-            let end = reached_end(input[0]);
+            let Some(&next_byte) = input.first() else {
+                return fail(input, ParseErrorKind::UnexpectedEnd);
+            };
+            let end = reached_end(next_byte);
             if end.0 {
                 (input, _) = u8(input)?;
                 break end.1;
@@ -1,4 +1,6 @@
-use nom::{bytes::complete::take, IResult};
+use nom::bytes::complete::take;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use super::{local::LocalTypes, Expr};
@@ -16,7 +18,10 @@ impl FunctionCode {
 
         let (input, locals) = LocalTypes::parse(input)?;
 
-        let (_, instructions) = Expr::parse(input)?;
+        let (input, instructions) = Expr::parse(input)?;
+        if !input.is_empty() {
+            return fail(input, ParseErrorKind::FunctionBodySizeMismatch);
+        }
 
         Ok((
             rest,
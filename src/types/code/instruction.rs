@@ -1,18 +1,17 @@
 use std::{cell::RefCell, ops::Deref, rc::Rc};
 
-use nom::{
-    bytes::complete::tag,
-    number::{
-        complete::{f32, f64, u8},
-        Endianness,
-    },
-    IResult, Parser,
+use nom::number::{
+    complete::{f32, f64, u8},
+    Endianness,
 };
 use nom_leb128::{leb128_i32, leb128_i64, leb128_u32};
 
-use crate::types::{
-    wasm_vec, BlockType, DataIdx, ElementIdx, FuncIdx, FuncTypeIdx, GlobalIdx, LabelIdx, LocalIdx,
-    MemoryArgument, RefType, TableIdx, ValueType,
+use crate::{
+    parse_error::{fail, IResult, ParseErrorKind},
+    types::{
+        wasm_vec, zero_byte, BlockType, DataIdx, ElementIdx, FuncIdx, FuncTypeIdx, GlobalIdx,
+        LabelIdx, LocalIdx, MemoryArgument, RefType, TableIdx, ValueType,
+    },
 };
 
 use super::expr::{Blocks, Instructions};
@@ -20,6 +19,11 @@ use super::expr::{Blocks, Instructions};
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BlockIdx(pub usize);
 
+impl BlockIdx {
+    /// The label of the function body itself, branching to it returns from the function
+    pub const FUNCTION: BlockIdx = BlockIdx(usize::MAX);
+}
+
 #[derive(Debug)]
 pub enum Instruction {
     Unreachable,
@@ -211,6 +215,14 @@ pub enum Instruction {
     I64Extend8S,
     I64Extend16S,
     I64Extend32S,
+    I32TruncSatF32S,
+    I32TruncSatF32U,
+    I32TruncSatF64S,
+    I32TruncSatF64U,
+    I64TruncSatF32S,
+    I64TruncSatF32U,
+    I64TruncSatF64S,
+    I64TruncSatF64U,
 
     PushNullRef(RefType),
     RefIsNull,
@@ -235,13 +247,17 @@ impl Instruction {
         blocks: Rc<RefCell<Blocks>>,
         block_stack: Rc<RefCell<Vec<BlockIdx>>>,
     ) -> IResult<&[u8], Instruction> {
-        let block_stack_c = block_stack.clone();
-        let label_index_to_block_index = move |LabelIdx(idx)| {
-            let block_stack = block_stack_c.deref().borrow();
-            let block_stack_index = block_stack.len() - 1 - idx as usize;
-            block_stack[block_stack_index]
+        let parse_label = |input| {
+            let (rest, LabelIdx(idx)) = LabelIdx::parse(input)?;
+            let block_stack = block_stack.deref().borrow();
+            match block_stack.len().checked_sub(idx as usize + 1) {
+                Some(block_stack_index) => Ok((rest, block_stack[block_stack_index])),
+                None if idx as usize == block_stack.len() => Ok((rest, BlockIdx::FUNCTION)),
+                None => fail(input, ParseErrorKind::UnknownLabel(idx)),
+            }
         };
 
+        let opcode_start = input;
         let (input, value) = u8(input)?;
         let (input, instruction) = match value {
             0x00 => (input, Instruction::Unreachable),
@@ -305,8 +321,7 @@ impl Instruction {
                 )
             }
             0x0c | 0x0d => {
-                let (input, label_idx) = LabelIdx::parse(input)?;
-                let block_idx = label_index_to_block_index(label_idx);
+                let (input, block_idx) = parse_label(input)?;
 
                 (
                     input,
@@ -318,11 +333,8 @@ impl Instruction {
                 )
             }
             0x0e => {
-                let (input, labels) =
-                    wasm_vec(LabelIdx::parse.map(&label_index_to_block_index))(input)?;
-                let (input, default_label) = LabelIdx::parse
-                    .map(&label_index_to_block_index)
-                    .parse(input)?;
+                let (input, labels) = wasm_vec(&parse_label)(input)?;
+                let (input, default_label) = parse_label(input)?;
                 (
                     input,
                     Instruction::BreakTable {
@@ -413,7 +425,7 @@ impl Instruction {
                 (input, instruction)
             }
             0x3F | 0x40 => {
-                let (input, _) = tag(&[0][..])(input)?;
+                let (input, _) = zero_byte(input)?;
                 let instruction = match value {
                     0x3f => Instruction::MemorySize,
                     0x40 => Instruction::MemoryGrow,
@@ -577,9 +589,17 @@ impl Instruction {
             0xFC => {
                 let (input, opcode) = leb128_u32(input)?;
                 match opcode {
+                    0 => (input, Instruction::I32TruncSatF32S),
+                    1 => (input, Instruction::I32TruncSatF32U),
+                    2 => (input, Instruction::I32TruncSatF64S),
+                    3 => (input, Instruction::I32TruncSatF64U),
+                    4 => (input, Instruction::I64TruncSatF32S),
+                    5 => (input, Instruction::I64TruncSatF32U),
+                    6 => (input, Instruction::I64TruncSatF64S),
+                    7 => (input, Instruction::I64TruncSatF64U),
                     8 => {
                         let (input, data_idx) = DataIdx::parse(input)?;
-                        let (input, _) = tag(&[0][..])(input)?;
+                        let (input, _) = zero_byte(input)?;
                         (input, Instruction::MemoryInit(data_idx))
                     }
                     9 => {
@@ -587,11 +607,12 @@ impl Instruction {
                         (input, Instruction::DataDrop(data_idx))
                     }
                     10 => {
-                        let (input, _) = tag(&[0, 0][..])(input)?;
+                        let (input, _) = zero_byte(input)?;
+                        let (input, _) = zero_byte(input)?;
                         (input, Instruction::Memcpy)
                     }
                     11 => {
-                        let (input, _) = tag(&[0][..])(input)?;
+                        let (input, _) = zero_byte(input)?;
                         (input, Instruction::Memfill)
                     }
                     12 => {
@@ -620,10 +641,15 @@ impl Instruction {
                         let (input, table_idx) = TableIdx::parse(input)?;
                         (input, Instruction::TableFill(table_idx))
                     }
-                    _ => panic!("Unknown instruction: 0x{:x} {}", value, opcode),
+                    _ => {
+                        return fail(
+                            opcode_start,
+                            ParseErrorKind::IllegalOpcode(value, Some(opcode)),
+                        )
+                    }
                 }
             }
-            _ => panic!("Invalid instruction: 0x{:x}", value),
+            _ => return fail(opcode_start, ParseErrorKind::IllegalOpcode(value, None)),
         };
         Ok((input, instruction))
     }
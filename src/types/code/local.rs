@@ -1,4 +1,6 @@
-use nom::{sequence::pair, IResult};
+use nom::sequence::pair;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use crate::types::{wasm_vec, ValueType};
@@ -6,15 +8,22 @@ use crate::types::{wasm_vec, ValueType};
 #[derive(Debug)]
 pub struct LocalTypes(pub Vec<ValueType>);
 
+/// Upper bound on the locals of a single function, keeps a tiny body from allocating gigabytes
+const MAX_LOCALS: u64 = 50_000;
+
 impl LocalTypes {
     pub fn parse(input: &[u8]) -> IResult<&[u8], LocalTypes> {
-        let (input, locals) = wasm_vec(pair(leb128_u32, ValueType::parse))(input)?;
+        let (rest, locals) = wasm_vec(pair(leb128_u32, ValueType::parse))(input)?;
+        let amount_of_locals: u64 = locals.iter().map(|(num, _)| *num as u64).sum();
+        if amount_of_locals > MAX_LOCALS {
+            return fail(input, ParseErrorKind::TooManyLocals);
+        }
         Ok((
-            input,
+            rest,
             LocalTypes(
                 locals
                     .into_iter()
-                    .flat_map(|(num, value_type)| std::iter::repeat(value_type).take(num as usize))
+                    .flat_map(|(num, value_type)| std::iter::repeat_n(value_type, num as usize))
                     .collect(),
             ),
         ))
@@ -1,4 +1,6 @@
-use nom::{sequence::pair, IResult};
+use nom::sequence::pair;
+
+use crate::parse_error::IResult;
 use nom_leb128::leb128_u32;
 
 #[derive(Debug, Clone, Copy, Default)]
@@ -1,4 +1,6 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use crate::module::functions::{self, Function};
@@ -31,10 +33,10 @@ pub enum DataMode {
 
 impl DataDeclaration {
     pub fn parse(input: &[u8]) -> IResult<&[u8], DataDeclaration> {
-        let (input, variant) = leb128_u32(input)?;
+        let (rest, variant) = leb128_u32(input)?;
         let (input, data) = match variant {
             0 => {
-                let (input, offset) = Expr::parse(input)?;
+                let (input, offset) = Expr::parse(rest)?;
                 let (input, init) = wasm_vec(u8)(input)?;
                 (
                     input,
@@ -48,7 +50,7 @@ impl DataDeclaration {
                 )
             }
             1 => {
-                let (input, init) = wasm_vec(u8)(input)?;
+                let (input, init) = wasm_vec(u8)(rest)?;
                 (
                     input,
                     DataDeclaration {
@@ -58,7 +60,7 @@ impl DataDeclaration {
                 )
             }
             2 => {
-                let (input, memidx) = MemoryIdx::parse(input)?;
+                let (input, memidx) = MemoryIdx::parse(rest)?;
                 let (input, offset) = Expr::parse(input)?;
                 let (input, init) = wasm_vec(u8)(input)?;
                 (
@@ -69,7 +71,7 @@ impl DataDeclaration {
                     },
                 )
             }
-            _ => panic!("Invalid data variant {}", variant),
+            _ => return fail(input, ParseErrorKind::MalformedDataKind(variant)),
         };
         Ok((input, data))
     }
@@ -1,9 +1,9 @@
-use nom::{bytes::complete::tag, IResult};
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use crate::module::functions::{Function, LocalFunction};
 
-use super::{wasm_vec, Expr, FuncIdx, Instruction, RefType, TableIdx};
+use super::{wasm_vec, zero_byte, Expr, FuncIdx, Instruction, RefType, TableIdx};
 
 #[derive(Debug)]
 pub struct Element {
@@ -47,10 +47,10 @@ fn func_idx_to_initializer(functions: Vec<FuncIdx>) -> Vec<Expr> {
 
 impl ElementDeclaration {
     pub fn parse(input: &[u8]) -> IResult<&[u8], ElementDeclaration> {
-        let (input, variant) = leb128_u32(input)?;
+        let (rest, variant) = leb128_u32(input)?;
         let (input, element) = match variant {
             0 => {
-                let (input, offset_in_table) = Expr::parse(input)?;
+                let (input, offset_in_table) = Expr::parse(rest)?;
                 let (input, functions) = wasm_vec(FuncIdx::parse)(input)?;
                 (
                     input,
@@ -65,7 +65,7 @@ impl ElementDeclaration {
                 )
             }
             1 => {
-                let (input, _) = tag(&[0][..])(input)?;
+                let (input, _) = zero_byte(rest)?;
                 let (input, functions) = wasm_vec(FuncIdx::parse)(input)?;
                 (
                     input,
@@ -77,9 +77,9 @@ impl ElementDeclaration {
                 )
             }
             2 => {
-                let (input, table) = TableIdx::parse(input)?;
+                let (input, table) = TableIdx::parse(rest)?;
                 let (input, offset) = Expr::parse(input)?;
-                let (input, _) = tag(&[0][..])(input)?;
+                let (input, _) = zero_byte(input)?;
                 let (input, functions) = wasm_vec(FuncIdx::parse)(input)?;
                 (
                     input,
@@ -94,7 +94,7 @@ impl ElementDeclaration {
                 )
             }
             3 => {
-                let (input, _) = tag(&[0][..])(input)?;
+                let (input, _) = zero_byte(rest)?;
                 let (input, functions) = wasm_vec(FuncIdx::parse)(input)?;
                 (
                     input,
@@ -106,7 +106,7 @@ impl ElementDeclaration {
                 )
             }
             4 => {
-                let (input, offset) = Expr::parse(input)?;
+                let (input, offset) = Expr::parse(rest)?;
                 let (input, init) = wasm_vec(Expr::parse)(input)?;
                 (
                     input,
@@ -121,7 +121,7 @@ impl ElementDeclaration {
                 )
             }
             5 => {
-                let (input, ref_type) = RefType::parse(input)?;
+                let (input, ref_type) = RefType::parse(rest)?;
                 let (input, init) = wasm_vec(Expr::parse)(input)?;
                 (
                     input,
@@ -133,7 +133,7 @@ impl ElementDeclaration {
                 )
             }
             6 => {
-                let (input, table_idx) = TableIdx::parse(input)?;
+                let (input, table_idx) = TableIdx::parse(rest)?;
                 let (input, offset) = Expr::parse(input)?;
                 let (input, ref_type) = RefType::parse(input)?;
                 let (input, init) = wasm_vec(Expr::parse)(input)?;
@@ -150,7 +150,7 @@ impl ElementDeclaration {
                 )
             }
             7 => {
-                let (input, ref_type) = RefType::parse(input)?;
+                let (input, ref_type) = RefType::parse(rest)?;
                 let (input, init) = wasm_vec(Expr::parse)(input)?;
                 (
                     input,
@@ -161,7 +161,7 @@ impl ElementDeclaration {
                     },
                 )
             }
-            _ => panic!("Invailid element variant {}", variant),
+            _ => return fail(input, ParseErrorKind::MalformedElementKind(variant)),
         };
 
         Ok((input, element))
@@ -1,4 +1,6 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 
 use super::{name, FuncIdx, GlobalIdx, MemoryIdx, TableIdx};
 
@@ -9,7 +11,7 @@ pub struct Export<'a> {
 }
 
 impl Export<'_> {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Export> {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Export<'_>> {
         let (input, name) = name(input)?;
         let (input, desc) = ExportDesc::parse(input)?;
         Ok((input, Export { name, desc }))
@@ -26,27 +28,25 @@ pub enum ExportDesc {
 
 impl ExportDesc {
     pub fn parse(input: &[u8]) -> IResult<&[u8], ExportDesc> {
-        let (input, flag) = u8(input)?;
+        let (rest, flag) = u8(input)?;
         match flag {
             0x00 => {
-                let (input, func_idx) = FuncIdx::parse(input)?;
+                let (input, func_idx) = FuncIdx::parse(rest)?;
                 Ok((input, ExportDesc::Func(func_idx)))
             }
             0x01 => {
-                let (input, table_idx) = TableIdx::parse(input)?;
+                let (input, table_idx) = TableIdx::parse(rest)?;
                 Ok((input, ExportDesc::Table(table_idx)))
             }
             0x02 => {
-                let (input, memory_idx) = MemoryIdx::parse(input)?;
+                let (input, memory_idx) = MemoryIdx::parse(rest)?;
                 Ok((input, ExportDesc::Memory(memory_idx)))
             }
             0x03 => {
-                let (input, global_idx) = GlobalIdx::parse(input)?;
+                let (input, global_idx) = GlobalIdx::parse(rest)?;
                 Ok((input, ExportDesc::Global(global_idx)))
             }
-            _ => {
-                panic!("Invalid export type")
-            }
+            _ => fail(input, ParseErrorKind::MalformedExportKind(flag)),
         }
     }
 }
@@ -1,9 +1,8 @@
-use nom::{
-    bytes::complete::{tag, take},
-    IResult,
-};
+use nom::{bytes::complete::take, number::complete::u8};
 use nom_leb128::leb128_u32;
 
+use crate::parse_error::{fail, IResult, ParseErrorKind};
+
 use super::value::ValueType;
 
 #[derive(Debug, Clone, Copy)]
@@ -30,25 +29,31 @@ pub struct FuncType {
 
 impl FuncType {
     pub fn parse(input: &[u8]) -> IResult<&[u8], FuncType> {
-        let (input, _) = tag([0x60])(input)?;
-        let (input, param_length) = leb128_u32(input)?;
-        let (input, params) = take(param_length)(input)?;
-        let params: Vec<ValueType> = params
-            .iter()
-            .copied()
-            .map(|e| e.try_into().expect("Invalid functype"))
-            .collect();
-
-        let (input, returns_length) = leb128_u32(input)?;
-        let (input, returns) = take(returns_length)(input)?;
-        let returns: Vec<ValueType> = returns
-            .iter()
-            .copied()
-            .map(|e| e.try_into().expect("Invalid functype"))
-            .collect();
+        let (rest, form) = u8(input)?;
+        if form != 0x60 {
+            return fail(input, ParseErrorKind::MalformedFuncType(form));
+        }
+        let (input, params) = Self::parse_result_type(rest)?;
+        let (input, returns) = Self::parse_result_type(input)?;
         Ok((input, FuncType { params, returns }))
     }
 
+    fn parse_result_type(input: &[u8]) -> IResult<&[u8], Vec<ValueType>> {
+        let (input, length) = leb128_u32(input)?;
+        let (rest, value_types) = take(length)(input)?;
+        let mut types = Vec::with_capacity(value_types.len());
+        for (i, value_type) in value_types.iter().enumerate() {
+            let Ok(value_type) = ValueType::try_from(*value_type) else {
+                return fail(
+                    &value_types[i..],
+                    ParseErrorKind::MalformedValueType(*value_type),
+                );
+            };
+            types.push(value_type);
+        }
+        Ok((rest, types))
+    }
+
     pub fn empty() -> Self {
         Self {
             params: vec![],
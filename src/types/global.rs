@@ -1,4 +1,6 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use super::ValueType;
@@ -11,7 +13,7 @@ impl GlobalIdx {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct GlobalType {
     pub valtype: ValueType,
     pub mutability: Mutability,
@@ -31,7 +33,7 @@ impl GlobalType {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mutability {
     Mutable,
     Const,
@@ -39,8 +41,11 @@ pub enum Mutability {
 
 impl Mutability {
     fn parse(input: &[u8]) -> IResult<&[u8], Mutability> {
-        let (input, value) = u8(input)?;
-        Ok((input, value.try_into().unwrap()))
+        let (rest, value) = u8(input)?;
+        match value.try_into() {
+            Ok(mutability) => Ok((rest, mutability)),
+            Err(()) => fail(input, ParseErrorKind::MalformedMutability(value)),
+        }
     }
 }
 
@@ -1,4 +1,6 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom_leb128::leb128_u32;
 
 use crate::types::{FuncTypeIdx, GlobalType, MemoryType, TableType};
@@ -21,34 +23,32 @@ pub enum ImportDesc {
 }
 
 impl Import<'_> {
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Import> {
+    pub fn parse(input: &[u8]) -> IResult<&[u8], Import<'_>> {
         let (input, mod_name) = name(input)?;
 
         let (input, import_name) = name(input)?;
 
-        let (input, import_type) = u8(input)?;
+        let (rest, import_type) = u8(input)?;
         let (input, import_desc) = match import_type {
             0x00 => {
                 //Function
-                let (input, func_type_index) = leb128_u32(input)?;
+                let (input, func_type_index) = leb128_u32(rest)?;
                 (input, ImportDesc::Func(FuncTypeIdx(func_type_index)))
             }
             0x01 => {
-                let (input, table_type) = TableType::parse(input)?;
+                let (input, table_type) = TableType::parse(rest)?;
 
                 (input, ImportDesc::Table(table_type))
             }
             0x02 => {
-                let (input, memory_type) = MemoryType::parse(input)?;
+                let (input, memory_type) = MemoryType::parse(rest)?;
                 (input, ImportDesc::Memory(memory_type))
             }
             0x03 => {
-                let (input, global_type) = GlobalType::parse(input)?;
+                let (input, global_type) = GlobalType::parse(rest)?;
                 (input, ImportDesc::Global(global_type))
             }
-            _ => {
-                panic!("Invalid import_type: {import_type}")
-            }
+            _ => return fail(input, ParseErrorKind::MalformedImportKind(import_type)),
         };
 
         Ok((
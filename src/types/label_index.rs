@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 use nom_leb128::leb128_u32;
 
 #[derive(Debug)]
@@ -1,8 +1,8 @@
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 use nom::number::complete::u8;
-use nom::IResult;
 use nom_leb128::leb128_u32;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Limit {
     pub min: u32,
     pub max: Option<u32>,
@@ -10,17 +10,15 @@ pub struct Limit {
 
 impl Limit {
     pub fn parse(input: &[u8]) -> IResult<&[u8], Limit> {
-        let (input, flag) = u8(input)?;
-        let (input, min) = leb128_u32(input)?;
+        let (rest, flag) = u8(input)?;
+        let (rest, min) = leb128_u32(rest)?;
         let (input, max) = match flag {
-            0x00 => (input, None),
+            0x00 => (rest, None),
             0x01 => {
-                let (input, max) = leb128_u32(input)?;
-                (input, Some(max))
-            }
-            _ => {
-                panic!("Invalid limit flag")
+                let (rest, max) = leb128_u32(rest)?;
+                (rest, Some(max))
             }
+            _ => return fail(input, ParseErrorKind::MalformedLimits(flag)),
         };
         Ok((input, Limit { min, max }))
     }
@@ -1,15 +1,20 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum RefType {
     FuncRef,
+    ExternRef,
 }
 
 impl RefType {
     pub fn parse(input: &[u8]) -> IResult<&[u8], RefType> {
-        let (input, value) = u8(input)?;
-        let reftype = value.try_into().expect("Invalid reftype value");
-        Ok((input, reftype))
+        let (rest, value) = u8(input)?;
+        match value.try_into() {
+            Ok(reftype) => Ok((rest, reftype)),
+            Err(()) => fail(input, ParseErrorKind::MalformedRefType(value)),
+        }
     }
 }
 
@@ -19,7 +24,7 @@ impl TryFrom<u8> for RefType {
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         Ok(match value {
             0x70 => RefType::FuncRef,
-            0x6F => panic!("Externref not supported yet"),
+            0x6F => RefType::ExternRef,
             _ => return Err(()),
         })
     }
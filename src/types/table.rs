@@ -1,4 +1,4 @@
-use nom::IResult;
+use crate::parse_error::IResult;
 use nom_leb128::leb128_u32;
 
 use super::{Limit, RefType};
@@ -11,7 +11,7 @@ impl TableIdx {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TableType(pub RefType, pub Limit);
 
 impl TableType {
@@ -1,4 +1,6 @@
-use nom::{number::complete::u8, IResult};
+use nom::number::complete::u8;
+
+use crate::parse_error::{fail, IResult, ParseErrorKind};
 
 use super::RefType;
 
@@ -17,6 +19,7 @@ pub enum NumericValueType {
     F64 = 0x7C,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy)]
 pub enum VectorType {
     V128 = 0x7B,
@@ -35,8 +38,11 @@ impl TryFrom<u8> for ValueType {
 
 impl ValueType {
     pub fn parse(input: &[u8]) -> IResult<&[u8], ValueType> {
-        let (input, value) = u8(input)?;
-        Ok((input, value.try_into().expect("Invalid value type")))
+        let (rest, value) = u8(input)?;
+        match value.try_into() {
+            Ok(value_type) => Ok((rest, value_type)),
+            Err(()) => fail(input, ParseErrorKind::MalformedValueType(value)),
+        }
     }
 }
 
@@ -0,0 +1,325 @@
+use std::{collections::HashSet, rc::Rc};
+
+use crate::{
+    module::{
+        functions::{Function, LocalFunction},
+        Module,
+    },
+    section::r#type::TypeSection,
+    types::{
+        DataMode, ElementMode, Export, ExportDesc, Expr, FuncIdx, FuncType, GlobalIdx, GlobalType,
+        ImportDesc, Instruction, Limit, MemoryIdx, Mutability, NumericValueType, RefType, TableIdx,
+        ValueType,
+    },
+};
+
+use self::expr::ExprValidator;
+
+pub use self::error::{ValidationContext, ValidationError, ValidationErrorKind};
+
+mod error;
+mod expr;
+
+/// Largest amount of 64KiB pages a 32 bit memory can address
+const MAX_MEMORY_PAGES: u32 = 65536;
+
+/// Everything defined or imported by a module that instructions can refer to
+struct Context<'m> {
+    types: &'m TypeSection,
+    functions: Vec<Rc<FuncType>>,
+    tables: Vec<RefType>,
+    memories: usize,
+    globals: Vec<GlobalType>,
+    imported_globals: usize,
+    elements: Vec<RefType>,
+    data_count: Option<u32>,
+    /// Functions that `ref.func` may refer to from a function body
+    refs: HashSet<u32>,
+}
+
+impl<'m> Context<'m> {
+    fn new(module: &'m Module<'_>) -> Self {
+        let functions = (0..module.amount_of_functions())
+            .map(|idx| {
+                module
+                    .get_function(FuncIdx(idx as u32))
+                    .expect("Function index is below the amount of functions")
+                    .signature()
+            })
+            .collect();
+
+        let mut tables: Vec<_> = module.imported_tables().map(|table| table.0).collect();
+        let mut memories = module
+            .imports()
+            .iter()
+            .filter(|import| matches!(import.desc, ImportDesc::Memory(_)))
+            .count();
+        let mut globals: Vec<_> = module.imported_globals().collect();
+        let imported_globals = globals.len();
+
+        tables.extend(module.tables().iter().map(|table| table.0));
+        memories += module.memory_limit().iter().count();
+        globals.extend(
+            module
+                .global_initializers()
+                .iter()
+                .map(|global| global.signature),
+        );
+
+        let const_expr_refs = (module.amount_of_functions()..)
+            .map_while(|idx| module.get_function(FuncIdx(idx as u32)))
+            .flat_map(|function| match function {
+                Function::Local(LocalFunction { code, .. }) => code.instructions.instructions(),
+                Function::Imported(_) => &[],
+            })
+            .filter_map(|instruction| match instruction {
+                Instruction::PushFuncRef(FuncIdx(idx)) => Some(*idx),
+                _ => None,
+            });
+        let export_refs = module
+            .exports()
+            .iter()
+            .filter_map(|export| match export.desc {
+                ExportDesc::Func(FuncIdx(idx)) => Some(idx),
+                _ => None,
+            });
+
+        Self {
+            types: module.function_types(),
+            functions,
+            tables,
+            memories,
+            globals,
+            imported_globals,
+            elements: module
+                .elements()
+                .iter()
+                .map(|element| element.ref_type)
+                .collect(),
+            data_count: module.data_count(),
+            refs: const_expr_refs.chain(export_refs).collect(),
+        }
+    }
+
+    fn table(&self, idx: u32) -> Result<RefType, ValidationErrorKind> {
+        self.tables
+            .get(idx as usize)
+            .copied()
+            .ok_or(ValidationErrorKind::UnknownTable(idx))
+    }
+}
+
+/// Checks that a parsed module is well typed, so the runtime can rely on it
+pub fn validate(module: &Module<'_>) -> Result<(), ValidationError> {
+    let context = Context::new(module);
+
+    let error = |context: ValidationContext| {
+        move |kind: ValidationErrorKind| ValidationError { context, kind }
+    };
+
+    let mut tables = 0;
+    for (idx, import) in module.imports().iter().enumerate() {
+        let import_error = error(ValidationContext::Import(idx as u32));
+        match &import.desc {
+            ImportDesc::Table(table) => {
+                validate_limit(table.1, u32::MAX).map_err(import_error)?;
+                tables += 1;
+            }
+            ImportDesc::Memory(memory) => {
+                validate_limit(memory.0, MAX_MEMORY_PAGES).map_err(import_error)?
+            }
+            ImportDesc::Func(_) | ImportDesc::Global(_) => {}
+        }
+    }
+
+    for table in module.tables() {
+        validate_limit(table.1, u32::MAX).map_err(error(ValidationContext::Table(tables)))?;
+        tables += 1;
+    }
+
+    if let Some(limit) = module.memory_limit() {
+        validate_limit(limit, MAX_MEMORY_PAGES).map_err(error(ValidationContext::Memory))?;
+    }
+    if context.memories > 1 {
+        return Err(error(ValidationContext::Module)(
+            ValidationErrorKind::MultipleMemories,
+        ));
+    }
+
+    for idx in 0..module.amount_of_functions() {
+        let Some(Function::Local(function)) = module.get_function(FuncIdx(idx as u32)) else {
+            continue;
+        };
+        let locals = function
+            .signature
+            .params
+            .iter()
+            .chain(function.code.locals.0.iter())
+            .copied()
+            .collect();
+        ExprValidator::new(&context, &context.globals, locals)
+            .validate(&function.code.instructions, &function.signature.returns)
+            .map_err(error(ValidationContext::Function(idx as u32)))?;
+    }
+
+    for (idx, global) in module.global_initializers().iter().enumerate() {
+        // Initializers only see the imported globals, not the ones defined next to them
+        let visible_globals = &context.globals[..context.imported_globals];
+        validate_const_expr(
+            &context,
+            visible_globals,
+            const_expr(module, global.init),
+            global.signature.valtype,
+        )
+        .map_err(error(ValidationContext::Global(
+            (context.imported_globals + idx) as u32,
+        )))?;
+    }
+
+    for (idx, element) in module.elements().iter().enumerate() {
+        let element_error = error(ValidationContext::Element(idx as u32));
+        for init in &element.init {
+            validate_const_expr(
+                &context,
+                &context.globals,
+                const_expr(module, *init),
+                ValueType::Ref(element.ref_type),
+            )
+            .map_err(element_error)?;
+        }
+        if let ElementMode::Active {
+            table: TableIdx(table),
+            offset_in_table,
+        } = element.mode
+        {
+            if context.table(table).map_err(element_error)? != element.ref_type {
+                return Err(element_error(ValidationErrorKind::TypeMismatch));
+            }
+            validate_const_expr(
+                &context,
+                &context.globals,
+                const_expr(module, offset_in_table),
+                ValueType::Numeric(NumericValueType::I32),
+            )
+            .map_err(element_error)?;
+        }
+    }
+
+    for (idx, data) in module.datas().iter().enumerate() {
+        let data_error = error(ValidationContext::Data(idx as u32));
+        if let DataMode::Active {
+            memidx: MemoryIdx(memory),
+            offset,
+        } = data.mode
+        {
+            if memory as usize >= context.memories {
+                return Err(data_error(ValidationErrorKind::UnknownMemory(memory)));
+            }
+            validate_const_expr(
+                &context,
+                &context.globals,
+                const_expr(module, offset),
+                ValueType::Numeric(NumericValueType::I32),
+            )
+            .map_err(data_error)?;
+        }
+    }
+
+    if let Some(FuncIdx(start)) = module.get_initializer() {
+        let start_error = error(ValidationContext::Start);
+        let signature = context
+            .functions
+            .get(start as usize)
+            .ok_or(ValidationErrorKind::UnknownFunction(start))
+            .map_err(start_error)?;
+        if !signature.params.is_empty() || !signature.returns.is_empty() {
+            return Err(start_error(ValidationErrorKind::InvalidStartFunction));
+        }
+    }
+
+    let mut export_names = HashSet::new();
+    for (idx, export) in module.exports().iter().enumerate() {
+        validate_export(&context, export, &mut export_names)
+            .map_err(error(ValidationContext::Export(idx as u32)))?;
+    }
+
+    Ok(())
+}
+
+fn validate_limit(limit: Limit, max_allowed: u32) -> Result<(), ValidationErrorKind> {
+    if limit.min > max_allowed || limit.max.is_some_and(|max| max > max_allowed) {
+        return Err(ValidationErrorKind::MemorySizeTooLarge);
+    }
+    if limit.max.is_some_and(|max| max < limit.min) {
+        return Err(ValidationErrorKind::InvalidLimits);
+    }
+    Ok(())
+}
+
+fn const_expr<'m>(module: &'m Module<'_>, idx: FuncIdx) -> &'m Expr {
+    let Some(Function::Local(function)) = module.get_function(idx) else {
+        unreachable!("Constant expressions are stored as local functions");
+    };
+    &function.code.instructions
+}
+
+fn validate_const_expr(
+    context: &Context,
+    globals: &[GlobalType],
+    expr: &Expr,
+    value_type: ValueType,
+) -> Result<(), ValidationErrorKind> {
+    for instruction in expr.instructions() {
+        match instruction {
+            Instruction::I32Const(_)
+            | Instruction::I64Const(_)
+            | Instruction::F32Const(_)
+            | Instruction::F64Const(_)
+            | Instruction::PushNullRef(_)
+            | Instruction::PushFuncRef(_) => {}
+            Instruction::GlobalGet(GlobalIdx(idx)) => {
+                let global = globals
+                    .get(*idx as usize)
+                    .ok_or(ValidationErrorKind::UnknownGlobal(*idx))?;
+                if let Mutability::Mutable = global.mutability {
+                    return Err(ValidationErrorKind::ConstantExpressionRequired);
+                }
+            }
+            _ => return Err(ValidationErrorKind::ConstantExpressionRequired),
+        }
+    }
+    ExprValidator::new(context, globals, vec![]).validate(expr, &[value_type])
+}
+
+fn validate_export<'a>(
+    context: &Context,
+    export: &Export<'a>,
+    export_names: &mut HashSet<&'a str>,
+) -> Result<(), ValidationErrorKind> {
+    match export.desc {
+        ExportDesc::Func(FuncIdx(idx)) => {
+            if idx as usize >= context.functions.len() {
+                return Err(ValidationErrorKind::UnknownFunction(idx));
+            }
+        }
+        ExportDesc::Table(TableIdx(idx)) => {
+            context.table(idx)?;
+        }
+        ExportDesc::Memory(MemoryIdx(idx)) => {
+            if idx as usize >= context.memories {
+                return Err(ValidationErrorKind::UnknownMemory(idx));
+            }
+        }
+        ExportDesc::Global(GlobalIdx(idx)) => {
+            if idx as usize >= context.globals.len() {
+                return Err(ValidationErrorKind::UnknownGlobal(idx));
+            }
+        }
+    }
+    if !export_names.insert(export.name) {
+        return Err(ValidationErrorKind::DuplicateExportName(
+            export.name.to_string(),
+        ));
+    }
+    Ok(())
+}
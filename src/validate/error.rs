@@ -0,0 +1,116 @@
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationErrorKind {
+    TypeMismatch,
+    UnknownLabel,
+    UnknownLocal(u32),
+    UnknownGlobal(u32),
+    UnknownFunction(u32),
+    UnknownTable(u32),
+    UnknownMemory(u32),
+    UnknownType(u32),
+    UnknownDataSegment(u32),
+    UnknownElementSegment(u32),
+    ImmutableGlobal,
+    ConstantExpressionRequired,
+    UndeclaredFunctionReference,
+    DataCountRequired,
+    InvalidResultArity,
+    InvalidAlignment,
+    InvalidLimits,
+    MemorySizeTooLarge,
+    MultipleMemories,
+    InvalidStartFunction,
+    DuplicateExportName(String),
+}
+
+impl Display for ValidationErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationErrorKind::TypeMismatch => write!(f, "type mismatch"),
+            ValidationErrorKind::UnknownLabel => write!(f, "unknown label"),
+            ValidationErrorKind::UnknownLocal(idx) => write!(f, "unknown local {}", idx),
+            ValidationErrorKind::UnknownGlobal(idx) => write!(f, "unknown global {}", idx),
+            ValidationErrorKind::UnknownFunction(idx) => write!(f, "unknown function {}", idx),
+            ValidationErrorKind::UnknownTable(idx) => write!(f, "unknown table {}", idx),
+            ValidationErrorKind::UnknownMemory(idx) => write!(f, "unknown memory {}", idx),
+            ValidationErrorKind::UnknownType(idx) => write!(f, "unknown type {}", idx),
+            ValidationErrorKind::UnknownDataSegment(idx) => {
+                write!(f, "unknown data segment {}", idx)
+            }
+            ValidationErrorKind::UnknownElementSegment(idx) => {
+                write!(f, "unknown elem segment {}", idx)
+            }
+            ValidationErrorKind::ImmutableGlobal => write!(f, "global is immutable"),
+            ValidationErrorKind::ConstantExpressionRequired => {
+                write!(f, "constant expression required")
+            }
+            ValidationErrorKind::UndeclaredFunctionReference => {
+                write!(f, "undeclared function reference")
+            }
+            ValidationErrorKind::DataCountRequired => write!(f, "data count section required"),
+            ValidationErrorKind::InvalidResultArity => write!(f, "invalid result arity"),
+            ValidationErrorKind::InvalidAlignment => {
+                write!(f, "alignment must not be larger than natural")
+            }
+            ValidationErrorKind::InvalidLimits => {
+                write!(f, "size minimum must not be greater than maximum")
+            }
+            ValidationErrorKind::MemorySizeTooLarge => {
+                write!(f, "memory size must be at most 65536 pages (4GiB)")
+            }
+            ValidationErrorKind::MultipleMemories => write!(f, "multiple memories"),
+            ValidationErrorKind::InvalidStartFunction => write!(f, "start function"),
+            ValidationErrorKind::DuplicateExportName(name) => {
+                write!(f, "duplicate export name {:?}", name)
+            }
+        }
+    }
+}
+
+/// The part of the module a validation error was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationContext {
+    Module,
+    Import(u32),
+    Function(u32),
+    Table(u32),
+    Memory,
+    Global(u32),
+    Export(u32),
+    Start,
+    Element(u32),
+    Data(u32),
+}
+
+impl Display for ValidationContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationContext::Module => write!(f, "module"),
+            ValidationContext::Import(idx) => write!(f, "import {}", idx),
+            ValidationContext::Function(idx) => write!(f, "function {}", idx),
+            ValidationContext::Table(idx) => write!(f, "table {}", idx),
+            ValidationContext::Memory => write!(f, "memory"),
+            ValidationContext::Global(idx) => write!(f, "global {}", idx),
+            ValidationContext::Export(idx) => write!(f, "export {}", idx),
+            ValidationContext::Start => write!(f, "start function"),
+            ValidationContext::Element(idx) => write!(f, "elem segment {}", idx),
+            ValidationContext::Data(idx) => write!(f, "data segment {}", idx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub context: ValidationContext,
+    pub kind: ValidationErrorKind,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", self.kind, self.context)
+    }
+}
+
+impl std::error::Error for ValidationError {}
@@ -0,0 +1,680 @@
+use std::rc::Rc;
+
+use crate::types::{
+    BlockIdx, BlockType, DataIdx, ElementIdx, Expr, FuncIdx, FuncType, FuncTypeIdx, GlobalIdx,
+    GlobalType, Instruction, LocalIdx, MemoryArgument, Mutability, NumericValueType, RefType,
+    TableIdx, ValueType,
+};
+
+use super::{Context, ValidationErrorKind};
+
+const I32: ValueType = ValueType::Numeric(NumericValueType::I32);
+const I64: ValueType = ValueType::Numeric(NumericValueType::I64);
+const F32: ValueType = ValueType::Numeric(NumericValueType::F32);
+const F64: ValueType = ValueType::Numeric(NumericValueType::F64);
+
+/// A `None` operand is one of unknown type, popped from the stack of unreachable code
+type Operand = Option<ValueType>;
+
+struct ControlFrame {
+    /// `None` for the frame of the function body itself
+    block: Option<BlockIdx>,
+    label_types: Vec<ValueType>,
+    end_types: Vec<ValueType>,
+    height: usize,
+    unreachable: bool,
+}
+
+/// Type checks a single expression following the validation algorithm of the spec's appendix
+pub struct ExprValidator<'c> {
+    context: &'c Context<'c>,
+    globals: &'c [GlobalType],
+    locals: Vec<ValueType>,
+    operands: Vec<Operand>,
+    controls: Vec<ControlFrame>,
+}
+
+impl<'c> ExprValidator<'c> {
+    pub fn new(
+        context: &'c Context<'c>,
+        globals: &'c [GlobalType],
+        locals: Vec<ValueType>,
+    ) -> Self {
+        Self {
+            context,
+            globals,
+            locals,
+            operands: vec![],
+            controls: vec![],
+        }
+    }
+
+    pub fn validate(
+        mut self,
+        expr: &Expr,
+        results: &[ValueType],
+    ) -> Result<(), ValidationErrorKind> {
+        self.push_control(Some(BlockIdx::FUNCTION), results.to_vec(), results.to_vec());
+        self.validate_instructions(expr, expr.instructions())?;
+        self.pop_control()?;
+        Ok(())
+    }
+
+    fn push(&mut self, value_type: ValueType) {
+        self.operands.push(Some(value_type));
+    }
+
+    fn push_all(&mut self, value_types: &[ValueType]) {
+        self.operands.extend(value_types.iter().copied().map(Some));
+    }
+
+    fn pop(&mut self) -> Result<Operand, ValidationErrorKind> {
+        let frame = self.controls.last().expect("Control stack is never empty");
+        if self.operands.len() == frame.height {
+            return if frame.unreachable {
+                Ok(None)
+            } else {
+                Err(ValidationErrorKind::TypeMismatch)
+            };
+        }
+        Ok(self
+            .operands
+            .pop()
+            .expect("Operand stack is above the frame height"))
+    }
+
+    fn pop_expect(&mut self, expected: ValueType) -> Result<Operand, ValidationErrorKind> {
+        match self.pop()? {
+            Some(actual) if actual != expected => Err(ValidationErrorKind::TypeMismatch),
+            actual => Ok(actual),
+        }
+    }
+
+    fn pop_all(&mut self, value_types: &[ValueType]) -> Result<Vec<Operand>, ValidationErrorKind> {
+        let mut popped = value_types
+            .iter()
+            .rev()
+            .map(|value_type| self.pop_expect(*value_type))
+            .collect::<Result<Vec<_>, _>>()?;
+        popped.reverse();
+        Ok(popped)
+    }
+
+    fn push_control(
+        &mut self,
+        block: Option<BlockIdx>,
+        label_types: Vec<ValueType>,
+        end_types: Vec<ValueType>,
+    ) {
+        self.controls.push(ControlFrame {
+            block,
+            label_types,
+            end_types,
+            height: self.operands.len(),
+            unreachable: false,
+        });
+    }
+
+    fn pop_control(&mut self) -> Result<Vec<ValueType>, ValidationErrorKind> {
+        let end_types = self
+            .controls
+            .last()
+            .expect("Control stack is never empty")
+            .end_types
+            .clone();
+        self.pop_all(&end_types)?;
+        let frame = self.controls.pop().expect("Control stack is never empty");
+        if self.operands.len() != frame.height {
+            return Err(ValidationErrorKind::TypeMismatch);
+        }
+        Ok(end_types)
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self
+            .controls
+            .last_mut()
+            .expect("Control stack is never empty");
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn label_types(&self, block: BlockIdx) -> Result<Vec<ValueType>, ValidationErrorKind> {
+        self.controls
+            .iter()
+            .rev()
+            .find(|frame| frame.block == Some(block))
+            .map(|frame| frame.label_types.clone())
+            .ok_or(ValidationErrorKind::UnknownLabel)
+    }
+
+    fn unary(&mut self, value_type: ValueType) -> Result<(), ValidationErrorKind> {
+        self.convert(value_type, value_type)
+    }
+
+    fn binary(&mut self, value_type: ValueType) -> Result<(), ValidationErrorKind> {
+        self.pop_expect(value_type)?;
+        self.pop_expect(value_type)?;
+        self.push(value_type);
+        Ok(())
+    }
+
+    fn test(&mut self, value_type: ValueType) -> Result<(), ValidationErrorKind> {
+        self.convert(value_type, I32)
+    }
+
+    fn compare(&mut self, value_type: ValueType) -> Result<(), ValidationErrorKind> {
+        self.pop_expect(value_type)?;
+        self.pop_expect(value_type)?;
+        self.push(I32);
+        Ok(())
+    }
+
+    fn convert(&mut self, from: ValueType, to: ValueType) -> Result<(), ValidationErrorKind> {
+        self.pop_expect(from)?;
+        self.push(to);
+        Ok(())
+    }
+
+    fn memory_argument(
+        &self,
+        memory_argument: MemoryArgument,
+        natural_alignment: u32,
+    ) -> Result<(), ValidationErrorKind> {
+        self.memory()?;
+        if memory_argument.align > natural_alignment {
+            return Err(ValidationErrorKind::InvalidAlignment);
+        }
+        Ok(())
+    }
+
+    /// `natural_alignment` is the log2 of the amount of bytes accessed
+    fn load(
+        &mut self,
+        memory_argument: MemoryArgument,
+        natural_alignment: u32,
+        value_type: ValueType,
+    ) -> Result<(), ValidationErrorKind> {
+        self.memory_argument(memory_argument, natural_alignment)?;
+        self.convert(I32, value_type)
+    }
+
+    fn store(
+        &mut self,
+        memory_argument: MemoryArgument,
+        natural_alignment: u32,
+        value_type: ValueType,
+    ) -> Result<(), ValidationErrorKind> {
+        self.memory_argument(memory_argument, natural_alignment)?;
+        self.pop_expect(value_type)?;
+        self.pop_expect(I32)?;
+        Ok(())
+    }
+
+    fn memory(&self) -> Result<(), ValidationErrorKind> {
+        if self.context.memories == 0 {
+            return Err(ValidationErrorKind::UnknownMemory(0));
+        }
+        Ok(())
+    }
+
+    fn local(&self, LocalIdx(idx): LocalIdx) -> Result<ValueType, ValidationErrorKind> {
+        self.locals
+            .get(idx as usize)
+            .copied()
+            .ok_or(ValidationErrorKind::UnknownLocal(idx))
+    }
+
+    fn global(&self, GlobalIdx(idx): GlobalIdx) -> Result<GlobalType, ValidationErrorKind> {
+        self.globals
+            .get(idx as usize)
+            .copied()
+            .ok_or(ValidationErrorKind::UnknownGlobal(idx))
+    }
+
+    fn table(&self, TableIdx(idx): TableIdx) -> Result<RefType, ValidationErrorKind> {
+        self.context.table(idx)
+    }
+
+    fn function(&self, FuncIdx(idx): FuncIdx) -> Result<Rc<FuncType>, ValidationErrorKind> {
+        self.context
+            .functions
+            .get(idx as usize)
+            .cloned()
+            .ok_or(ValidationErrorKind::UnknownFunction(idx))
+    }
+
+    fn element(&self, ElementIdx(idx): &ElementIdx) -> Result<RefType, ValidationErrorKind> {
+        self.context
+            .elements
+            .get(*idx as usize)
+            .copied()
+            .ok_or(ValidationErrorKind::UnknownElementSegment(*idx))
+    }
+
+    fn data(&self, DataIdx(idx): &DataIdx) -> Result<(), ValidationErrorKind> {
+        match self.context.data_count {
+            None => Err(ValidationErrorKind::DataCountRequired),
+            Some(count) if *idx >= count => Err(ValidationErrorKind::UnknownDataSegment(*idx)),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn call(&mut self, signature: &FuncType) -> Result<(), ValidationErrorKind> {
+        self.pop_all(&signature.params)?;
+        self.push_all(&signature.returns);
+        Ok(())
+    }
+
+    /// The params and results of a block
+    fn block_signature(
+        &self,
+        block_type: BlockType,
+    ) -> Result<(Vec<ValueType>, Vec<ValueType>), ValidationErrorKind> {
+        let (params, results) =
+            self.context
+                .types
+                .block_signature(&block_type)
+                .ok_or_else(|| match block_type {
+                    BlockType::Type(FuncTypeIdx(idx)) => ValidationErrorKind::UnknownType(idx),
+                    _ => unreachable!("Only type indices can be unknown"),
+                })?;
+        Ok((params.to_vec(), results.to_vec()))
+    }
+
+    /// Validates the instructions of a block whose params were already popped. Branches to a
+    /// loop start it again and therefore take its params, other blocks take their results
+    fn validate_block(
+        &mut self,
+        expr: &Expr,
+        block: BlockIdx,
+        is_loop: bool,
+        params: &[ValueType],
+        results: Vec<ValueType>,
+    ) -> Result<Vec<ValueType>, ValidationErrorKind> {
+        let label_types = if is_loop {
+            params.to_vec()
+        } else {
+            results.clone()
+        };
+        self.push_control(Some(block), label_types, results);
+        self.push_all(params);
+        self.validate_instructions(expr, expr.block_instructions(block))?;
+        self.pop_control()
+    }
+
+    fn validate_instructions(
+        &mut self,
+        expr: &Expr,
+        instructions: &[Instruction],
+    ) -> Result<(), ValidationErrorKind> {
+        for instruction in instructions {
+            self.validate_instruction(expr, instruction)?;
+        }
+        Ok(())
+    }
+
+    fn validate_instruction(
+        &mut self,
+        expr: &Expr,
+        instruction: &Instruction,
+    ) -> Result<(), ValidationErrorKind> {
+        match instruction {
+            Instruction::Unreachable => self.set_unreachable(),
+            Instruction::Nop => {}
+            Instruction::Block(block) | Instruction::Loop(block) => {
+                let (params, results) = self.block_signature(expr.get_block_type(*block))?;
+                self.pop_all(&params)?;
+                let is_loop = matches!(instruction, Instruction::Loop(_));
+                let end_types = self.validate_block(expr, *block, is_loop, &params, results)?;
+                self.push_all(&end_types);
+            }
+            Instruction::If { if_expr, else_expr } => {
+                self.pop_expect(I32)?;
+                let (params, results) = self.block_signature(expr.get_block_type(*if_expr))?;
+                self.pop_all(&params)?;
+                self.validate_block(expr, *if_expr, false, &params, results.clone())?;
+                let end_types = self.validate_block(expr, *else_expr, false, &params, results)?;
+                self.push_all(&end_types);
+            }
+            Instruction::Break(block) => {
+                let label_types = self.label_types(*block)?;
+                self.pop_all(&label_types)?;
+                self.set_unreachable();
+            }
+            Instruction::BreakIf(block) => {
+                self.pop_expect(I32)?;
+                let label_types = self.label_types(*block)?;
+                self.pop_all(&label_types)?;
+                self.push_all(&label_types);
+            }
+            Instruction::BreakTable { labels, default } => {
+                self.pop_expect(I32)?;
+                let default_types = self.label_types(*default)?;
+                for label in labels {
+                    let label_types = self.label_types(*label)?;
+                    if label_types.len() != default_types.len() {
+                        return Err(ValidationErrorKind::TypeMismatch);
+                    }
+                    let popped = self.pop_all(&label_types)?;
+                    self.operands.extend(popped);
+                }
+                self.pop_all(&default_types)?;
+                self.set_unreachable();
+            }
+            Instruction::Return => {
+                let returns = self.controls[0].end_types.clone();
+                self.pop_all(&returns)?;
+                self.set_unreachable();
+            }
+            Instruction::Call(func_idx) => {
+                let signature = self.function(*func_idx)?;
+                self.call(&signature)?;
+            }
+            Instruction::CallIndirect(FuncTypeIdx(type_idx), table_idx) => {
+                if self.table(*table_idx)? != RefType::FuncRef {
+                    return Err(ValidationErrorKind::TypeMismatch);
+                }
+                let signature = self
+                    .context
+                    .types
+                    .get_function_type(FuncTypeIdx(*type_idx))
+                    .ok_or(ValidationErrorKind::UnknownType(*type_idx))?;
+                self.pop_expect(I32)?;
+                self.call(&signature)?;
+            }
+            Instruction::Drop => {
+                self.pop()?;
+            }
+            Instruction::Select => {
+                self.pop_expect(I32)?;
+                let first = self.pop()?;
+                let second = self.pop()?;
+                let value_type = match (first, second) {
+                    (Some(ValueType::Ref(_)), _) | (_, Some(ValueType::Ref(_))) => {
+                        return Err(ValidationErrorKind::TypeMismatch)
+                    }
+                    (Some(first), Some(second)) if first != second => {
+                        return Err(ValidationErrorKind::TypeMismatch)
+                    }
+                    (first, second) => first.or(second),
+                };
+                self.operands.push(value_type);
+            }
+            Instruction::SelectTyped(value_types) => {
+                let [value_type] = value_types[..] else {
+                    return Err(ValidationErrorKind::InvalidResultArity);
+                };
+                self.pop_expect(I32)?;
+                self.pop_expect(value_type)?;
+                self.pop_expect(value_type)?;
+                self.push(value_type);
+            }
+            Instruction::LocalGet(local_idx) => {
+                let value_type = self.local(*local_idx)?;
+                self.push(value_type);
+            }
+            Instruction::LocalSet(local_idx) => {
+                let value_type = self.local(*local_idx)?;
+                self.pop_expect(value_type)?;
+            }
+            Instruction::LocalTee(local_idx) => {
+                let value_type = self.local(*local_idx)?;
+                self.unary(value_type)?;
+            }
+            Instruction::GlobalGet(global_idx) => {
+                let global = self.global(*global_idx)?;
+                self.push(global.valtype);
+            }
+            Instruction::GlobalSet(global_idx) => {
+                let global = self.global(*global_idx)?;
+                if let Mutability::Const = global.mutability {
+                    return Err(ValidationErrorKind::ImmutableGlobal);
+                }
+                self.pop_expect(global.valtype)?;
+            }
+            Instruction::TableGet(table_idx) => {
+                let ref_type = self.table(*table_idx)?;
+                self.convert(I32, ValueType::Ref(ref_type))?;
+            }
+            Instruction::TableSet(table_idx) => {
+                let ref_type = self.table(*table_idx)?;
+                self.pop_expect(ValueType::Ref(ref_type))?;
+                self.pop_expect(I32)?;
+            }
+
+            Instruction::I32Load(memarg) => self.load(*memarg, 2, I32)?,
+            Instruction::I64Load(memarg) => self.load(*memarg, 3, I64)?,
+            Instruction::F32Load(memarg) => self.load(*memarg, 2, F32)?,
+            Instruction::F64Load(memarg) => self.load(*memarg, 3, F64)?,
+            Instruction::I32Load8S(memarg) | Instruction::I32Load8U(memarg) => {
+                self.load(*memarg, 0, I32)?
+            }
+            Instruction::I32Load16S(memarg) | Instruction::I32Load16U(memarg) => {
+                self.load(*memarg, 1, I32)?
+            }
+            Instruction::I64Load8S(memarg) | Instruction::I64Load8U(memarg) => {
+                self.load(*memarg, 0, I64)?
+            }
+            Instruction::I64Load16S(memarg) | Instruction::I64Load16U(memarg) => {
+                self.load(*memarg, 1, I64)?
+            }
+            Instruction::I64Load32S(memarg) | Instruction::I64Load32U(memarg) => {
+                self.load(*memarg, 2, I64)?
+            }
+            Instruction::I32Store(memarg) => self.store(*memarg, 2, I32)?,
+            Instruction::I64Store(memarg) => self.store(*memarg, 3, I64)?,
+            Instruction::F32Store(memarg) => self.store(*memarg, 2, F32)?,
+            Instruction::F64Store(memarg) => self.store(*memarg, 3, F64)?,
+            Instruction::I32Store8(memarg) => self.store(*memarg, 0, I32)?,
+            Instruction::I32Store16(memarg) => self.store(*memarg, 1, I32)?,
+            Instruction::I64Store8(memarg) => self.store(*memarg, 0, I64)?,
+            Instruction::I64Store16(memarg) => self.store(*memarg, 1, I64)?,
+            Instruction::I64Store32(memarg) => self.store(*memarg, 2, I64)?,
+
+            Instruction::MemorySize => {
+                self.memory()?;
+                self.push(I32);
+            }
+            Instruction::MemoryGrow => {
+                self.memory()?;
+                self.unary(I32)?;
+            }
+
+            Instruction::I32Const(_) => self.push(I32),
+            Instruction::I64Const(_) => self.push(I64),
+            Instruction::F32Const(_) => self.push(F32),
+            Instruction::F64Const(_) => self.push(F64),
+
+            Instruction::I32Eqz => self.test(I32)?,
+            Instruction::I32Eq
+            | Instruction::I32Ne
+            | Instruction::I32LtS
+            | Instruction::I32LtU
+            | Instruction::I32GtS
+            | Instruction::I32GtU
+            | Instruction::I32LeS
+            | Instruction::I32LeU
+            | Instruction::I32GeS
+            | Instruction::I32GeU => self.compare(I32)?,
+            Instruction::I64Eqz => self.test(I64)?,
+            Instruction::I64Eq
+            | Instruction::I64Ne
+            | Instruction::I64LtS
+            | Instruction::I64LtU
+            | Instruction::I64GtS
+            | Instruction::I64GtU
+            | Instruction::I64LeS
+            | Instruction::I64LeU
+            | Instruction::I64GeS
+            | Instruction::I64GeU => self.compare(I64)?,
+            Instruction::F32Eq
+            | Instruction::F32Ne
+            | Instruction::F32Lt
+            | Instruction::F32Gt
+            | Instruction::F32Le
+            | Instruction::F32Ge => self.compare(F32)?,
+            Instruction::F64Eq
+            | Instruction::F64Ne
+            | Instruction::F64Lt
+            | Instruction::F64Gt
+            | Instruction::F64Le
+            | Instruction::F64Ge => self.compare(F64)?,
+
+            Instruction::I32Clz
+            | Instruction::I32Ctz
+            | Instruction::I32Popcnt
+            | Instruction::I32Extend8S
+            | Instruction::I32Extend16S => self.unary(I32)?,
+            Instruction::I32Add
+            | Instruction::I32Sub
+            | Instruction::I32Mul
+            | Instruction::I32DivS
+            | Instruction::I32DivU
+            | Instruction::I32RemS
+            | Instruction::I32RemU
+            | Instruction::I32And
+            | Instruction::I32Or
+            | Instruction::I32Xor
+            | Instruction::I32Shl
+            | Instruction::I32ShrS
+            | Instruction::I32ShrU
+            | Instruction::I32Rotl
+            | Instruction::I32Rotr => self.binary(I32)?,
+            Instruction::I64Clz
+            | Instruction::I64Ctz
+            | Instruction::I64Popcnt
+            | Instruction::I64Extend8S
+            | Instruction::I64Extend16S
+            | Instruction::I64Extend32S => self.unary(I64)?,
+            Instruction::I64Add
+            | Instruction::I64Sub
+            | Instruction::I64Mul
+            | Instruction::I64DivS
+            | Instruction::I64DivU
+            | Instruction::I64RemS
+            | Instruction::I64RemU
+            | Instruction::I64And
+            | Instruction::I64Or
+            | Instruction::I64Xor
+            | Instruction::I64Shl
+            | Instruction::I64ShrS
+            | Instruction::I64ShrU
+            | Instruction::I64Rotl
+            | Instruction::I64Rotr => self.binary(I64)?,
+            Instruction::F32Abs
+            | Instruction::F32Neg
+            | Instruction::F32Ceil
+            | Instruction::F32Floor
+            | Instruction::F32Trunc
+            | Instruction::F32Nearest
+            | Instruction::F32Sqrt => self.unary(F32)?,
+            Instruction::F32Add
+            | Instruction::F32Sub
+            | Instruction::F32Mul
+            | Instruction::F32Div
+            | Instruction::F32Min
+            | Instruction::F32Max
+            | Instruction::F32Copysign => self.binary(F32)?,
+            Instruction::F64Abs
+            | Instruction::F64Neg
+            | Instruction::F64Ceil
+            | Instruction::F64Floor
+            | Instruction::F64Trunc
+            | Instruction::F64Nearest
+            | Instruction::F64Sqrt => self.unary(F64)?,
+            Instruction::F64Add
+            | Instruction::F64Sub
+            | Instruction::F64Mul
+            | Instruction::F64Div
+            | Instruction::F64Min
+            | Instruction::F64Max
+            | Instruction::F64Copysign => self.binary(F64)?,
+
+            Instruction::I32WrapI64 => self.convert(I64, I32)?,
+            Instruction::I32TruncF32S | Instruction::I32TruncF32U => self.convert(F32, I32)?,
+            Instruction::I32TruncF64S | Instruction::I32TruncF64U => self.convert(F64, I32)?,
+            Instruction::I64ExtendI32S | Instruction::I64ExtendI32U => self.convert(I32, I64)?,
+            Instruction::I64TruncF32S | Instruction::I64TruncF32U => self.convert(F32, I64)?,
+            Instruction::I64TruncF64S | Instruction::I64TruncF64U => self.convert(F64, I64)?,
+            Instruction::F32ConvertI32S | Instruction::F32ConvertI32U => self.convert(I32, F32)?,
+            Instruction::F32ConvertI64S | Instruction::F32ConvertI64U => self.convert(I64, F32)?,
+            Instruction::F32DemoteF64 => self.convert(F64, F32)?,
+            Instruction::F64ConvertI32S | Instruction::F64ConvertI32U => self.convert(I32, F64)?,
+            Instruction::F64ConvertI64S | Instruction::F64ConvertI64U => self.convert(I64, F64)?,
+            Instruction::F64PromoteF32 => self.convert(F32, F64)?,
+            Instruction::I32TruncSatF32S | Instruction::I32TruncSatF32U => {
+                self.convert(F32, I32)?
+            }
+            Instruction::I32TruncSatF64S | Instruction::I32TruncSatF64U => {
+                self.convert(F64, I32)?
+            }
+            Instruction::I64TruncSatF32S | Instruction::I64TruncSatF32U => {
+                self.convert(F32, I64)?
+            }
+            Instruction::I64TruncSatF64S | Instruction::I64TruncSatF64U => {
+                self.convert(F64, I64)?
+            }
+            Instruction::I32ReinterpretF32 => self.convert(F32, I32)?,
+            Instruction::I64ReinterpretF64 => self.convert(F64, I64)?,
+            Instruction::F32ReinterpretI32 => self.convert(I32, F32)?,
+            Instruction::F64ReinterpretI64 => self.convert(I64, F64)?,
+
+            Instruction::PushNullRef(ref_type) => self.push(ValueType::Ref(*ref_type)),
+            Instruction::RefIsNull => {
+                if let Some(ValueType::Numeric(_)) = self.pop()? {
+                    return Err(ValidationErrorKind::TypeMismatch);
+                }
+                self.push(I32);
+            }
+            Instruction::PushFuncRef(func_idx @ FuncIdx(idx)) => {
+                self.function(*func_idx)?;
+                if !self.context.refs.contains(idx) {
+                    return Err(ValidationErrorKind::UndeclaredFunctionReference);
+                }
+                self.push(ValueType::Ref(RefType::FuncRef));
+            }
+
+            Instruction::MemoryInit(data_idx) => {
+                self.memory()?;
+                self.data(data_idx)?;
+                self.pop_all(&[I32, I32, I32])?;
+            }
+            Instruction::DataDrop(data_idx) => self.data(data_idx)?,
+            Instruction::Memcpy | Instruction::Memfill => {
+                self.memory()?;
+                self.pop_all(&[I32, I32, I32])?;
+            }
+
+            Instruction::TableInit(element_idx, table_idx) => {
+                if self.element(element_idx)? != self.table(*table_idx)? {
+                    return Err(ValidationErrorKind::TypeMismatch);
+                }
+                self.pop_all(&[I32, I32, I32])?;
+            }
+            Instruction::ElementDrop(element_idx) => {
+                self.element(element_idx)?;
+            }
+            Instruction::TableCopy(destination, source) => {
+                if self.table(*destination)? != self.table(*source)? {
+                    return Err(ValidationErrorKind::TypeMismatch);
+                }
+                self.pop_all(&[I32, I32, I32])?;
+            }
+            Instruction::TableGrow(table_idx) => {
+                let ref_type = self.table(*table_idx)?;
+                self.pop_all(&[ValueType::Ref(ref_type), I32])?;
+                self.push(I32);
+            }
+            Instruction::TableSize(table_idx) => {
+                self.table(*table_idx)?;
+                self.push(I32);
+            }
+            Instruction::TableFill(table_idx) => {
+                let ref_type = self.table(*table_idx)?;
+                self.pop_all(&[I32, ValueType::Ref(ref_type), I32])?;
+            }
+        }
+        Ok(())
+    }
+}
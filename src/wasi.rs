@@ -1,38 +1,27 @@
 use std::{
-    cell::RefMut,
     io::{stderr, stdout, IoSlice, Write},
-    ops::Range,
-    process::exit,
+    rc::Rc,
 };
 
-use nom::{
-    combinator::cut, multi::count, number::complete::le_u32, sequence::pair, IResult, Parser,
-};
+use nom::{combinator::cut, multi::count, number::complete::le_u32, sequence::pair, IResult};
 mod error;
 
-use crate::runtime::{memory::Memory, stack::Stack};
+use crate::{
+    runtime::{memory::Memory, Linker, Trap, Value},
+    types::{FuncType, NumericValueType, ValueType},
+};
 
 use self::error::WasiError;
 
 #[derive(Debug, Clone, Copy)]
 struct IOVec {
-    address: usize,
-    size: usize,
+    address: u32,
+    size: u32,
 }
 impl IOVec {
     pub fn parse(input: &[u8]) -> IResult<&[u8], IOVec> {
         let (input, (address, size)) = pair(le_u32, le_u32)(input)?;
-        Ok((
-            input,
-            IOVec {
-                address: address as usize,
-                size: size as usize,
-            },
-        ))
-    }
-
-    pub fn as_range(&self) -> Range<usize> {
-        self.address..self.address + self.size
+        Ok((input, IOVec { address, size }))
     }
 }
 
@@ -41,138 +30,162 @@ pub struct Wasi {
     envs: Vec<String>,
 }
 
+/// Module name WASI preview 1 functions are imported from
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
 impl Wasi {
-    pub fn run_function(
-        &mut self,
-        function_name: &str,
-        mut stack: RefMut<Stack>,
-        mut memory: RefMut<Memory>,
-    ) {
-        match function_name {
-            "proc_exit" => {
-                let exit_code = stack.pop_i32();
-                exit(exit_code);
-            }
-            "fd_write" => {
-                let n_written_addr = stack.pop_u32();
-                let amount_of_iovs = stack.pop_u32() as usize;
-                let iov_addr = stack.pop_u32() as usize;
-                let fd = stack.pop_i32();
-
-                let (_, iovs) =
-                    count(
-                        cut(IOVec::parse.map(|iov| IoSlice::new(memory.get_range(iov.as_range())))),
-                        amount_of_iovs,
-                    )(memory.get_range(iov_addr..iov_addr + amount_of_iovs * 8))
-                    .unwrap();
-
-                let result = self.write_iovs(fd, &iovs);
-                let (n_written, result) = match result {
-                    Ok(n) => (n as u32, WasiError::Success),
-                    Err(e) => (0, e),
-                };
-
-                memory.store_u32(n_written, n_written_addr);
-                stack.push_i32(result as u16 as i32);
-            }
-            "args_sizes_get" => {
-                let buf_size_addr = stack.pop_u32();
-                let argc_addr = stack.pop_u32();
-
-                // Calculate argc (number of arguments)
-                let argc = self.args.len() as u32;
-
-                // Calculate argv_buf_size (total size of all arguments including null terminators)
-                let argv_buf_size: u32 = self
-                    .args
-                    .iter()
-                    .map(|arg| arg.len() as u32 + 1) // +1 for the null terminator
-                    .sum();
-
-                // Store argc at the specified memory address
-                memory.store_u32(argc, argc_addr);
-
-                // Store argv_buf_size at the specified memory address
-                memory.store_u32(argv_buf_size, buf_size_addr);
-
-                // Push the success error code (0) onto the stack
-                stack.push_i32(0);
-            }
-            "args_get" => {
-                let argv_buf_addr = stack.pop_u32();
-                let argv_ptr = stack.pop_u32();
-                let mut current_offset = 0;
-
-                for (i, arg) in self.args.iter().enumerate() {
-                    // Calculate the address where the argument string will be stored
-                    let arg_str_addr = argv_buf_addr + current_offset;
-
-                    // Calculate where to store the pointer to the argument string
-                    let arg_ptr_addr = argv_ptr + (i as u32 * 4); // 4 bytes per pointer (assuming 32-bit pointers)
-
-                    // Write the pointer to the argument string into argv[i]
-                    memory.store_u32(arg_str_addr, arg_ptr_addr);
-
-                    // Write the argument string into argv_buf
-                    memory.fill_data(arg_str_addr, arg.as_bytes());
-
-                    // Add null terminator after the argument string
-                    memory.store_i32_8(0, arg_str_addr + arg.len() as u32, Default::default());
-
-                    // Update current_offset (length of arg + 1 for null terminator)
-                    current_offset += arg.len() as u32 + 1;
-                }
-
-                // Push success code onto the stack
-                stack.push_i32(0);
-            }
-            "environ_sizes_get" => {
-                // Pop the addresses from the stack
-                let environ_buf_size_addr = stack.pop_u32(); // Address to store environ_buf_size
-                let environ_count_addr = stack.pop_u32(); // Address to store environ_count
-
-                // Calculate environ_count (number of environment variables)
-                let environ_count = self.envs.len() as u32;
-
-                // Calculate environ_buf_size (total size of all environment variables including null terminators)
-                let environ_buf_size: u32 = self
-                    .envs
-                    .iter()
-                    .map(|env| env.len() as u32 + 1) // +1 for the null terminator
-                    .sum();
-
-                // Store environ_count at the specified memory address
-                memory.store_u32(environ_count, environ_count_addr);
-
-                // Store environ_buf_size at the specified memory address
-                memory.store_u32(environ_buf_size, environ_buf_size_addr);
-
-                // Push the success error code (0) onto the stack
-                stack.push_i32(0);
-            }
-            "environ_get" => {
-                let environ_buf_addr = stack.pop_u32();
-                let environ_ptr = stack.pop_u32();
-
-                let mut current_offset = 0;
-
-                for (i, env) in self.envs.iter().enumerate() {
-                    let env_str_addr = environ_buf_addr + current_offset;
-                    let env_ptr_addr = environ_ptr + (i as u32 * 4);
-
-                    memory.store_u32(env_str_addr, env_ptr_addr);
-                    memory.fill_data(env_str_addr, env.as_bytes());
-                    memory.store_i32_8(0, env_str_addr + env.len() as u32, Default::default());
-
-                    current_offset += env.len() as u32 + 1;
-                }
-
-                stack.push_i32(0);
-            }
-            _ => {
-                panic!("Unknown wasi function: {}", function_name);
-            }
+    /// Registers the supported WASI functions, modules importing any other one can't be
+    /// instantiated
+    pub fn add_to_linker(self, linker: &mut Linker) {
+        let wasi = Rc::new(self);
+        let signature = |params: usize| FuncType {
+            params: vec![ValueType::Numeric(NumericValueType::I32); params],
+            returns: vec![ValueType::Numeric(NumericValueType::I32)],
+        };
+
+        linker.func(
+            MODULE,
+            "proc_exit",
+            FuncType {
+                params: vec![ValueType::Numeric(NumericValueType::I32)],
+                returns: vec![],
+            },
+            |_, args| Err(Trap::Exit(arg(args, 0) as i32)),
+        );
+
+        let fd_write = wasi.clone();
+        linker.func(MODULE, "fd_write", signature(4), move |caller, args| {
+            let result = fd_write.fd_write(
+                caller.memory(),
+                arg(args, 0) as i32,
+                arg(args, 1),
+                arg(args, 2),
+                arg(args, 3),
+            )?;
+            Ok(vec![Value::I32(result)])
+        });
+
+        let args_sizes_get = wasi.clone();
+        linker.func(
+            MODULE,
+            "args_sizes_get",
+            signature(2),
+            move |caller, args| {
+                args_sizes_get.sizes_get(
+                    &args_sizes_get.args,
+                    caller.memory(),
+                    arg(args, 0),
+                    arg(args, 1),
+                )?;
+                Ok(vec![Value::I32(0)])
+            },
+        );
+
+        let args_get = wasi.clone();
+        linker.func(MODULE, "args_get", signature(2), move |caller, args| {
+            args_get.strings_get(&args_get.args, caller.memory(), arg(args, 0), arg(args, 1))?;
+            Ok(vec![Value::I32(0)])
+        });
+
+        let environ_sizes_get = wasi.clone();
+        linker.func(
+            MODULE,
+            "environ_sizes_get",
+            signature(2),
+            move |caller, args| {
+                environ_sizes_get.sizes_get(
+                    &environ_sizes_get.envs,
+                    caller.memory(),
+                    arg(args, 0),
+                    arg(args, 1),
+                )?;
+                Ok(vec![Value::I32(0)])
+            },
+        );
+
+        linker.func(MODULE, "environ_get", signature(2), move |caller, args| {
+            wasi.strings_get(&wasi.envs, caller.memory(), arg(args, 0), arg(args, 1))?;
+            Ok(vec![Value::I32(0)])
+        });
+    }
+
+    fn fd_write(
+        &self,
+        memory: &mut Memory,
+        fd: i32,
+        iov_addr: u32,
+        amount_of_iovs: u32,
+        n_written_addr: u32,
+    ) -> Result<i32, Trap> {
+        // 8 bytes per iovec
+        let iovs_size = amount_of_iovs
+            .checked_mul(8)
+            .ok_or(Trap::MemoryOutOfBounds)?;
+        let (_, iovs) = count(cut(IOVec::parse), amount_of_iovs as usize)(
+            memory.get_bytes(iov_addr, iovs_size)?,
+        )
+        .expect("Range holds exactly amount_of_iovs iovecs");
+        let iovs = iovs
+            .iter()
+            .map(|iov| memory.get_bytes(iov.address, iov.size).map(IoSlice::new))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let result = self.write_iovs(fd, &iovs);
+        let (n_written, result) = match result {
+            Ok(n) => (n as u32, WasiError::Success),
+            Err(e) => (0, e),
+        };
+
+        memory.store_u32(n_written, n_written_addr)?;
+        Ok(result as u16 as i32)
+    }
+
+    /// Stores the amount of strings and the total size of them including null terminators,
+    /// used by `args_sizes_get` and `environ_sizes_get`
+    fn sizes_get(
+        &self,
+        strings: &[String],
+        memory: &mut Memory,
+        count_addr: u32,
+        buf_size_addr: u32,
+    ) -> Result<(), Trap> {
+        let buf_size: u32 = strings
+            .iter()
+            .map(|string| string.len() as u32 + 1) // +1 for the null terminator
+            .sum();
+
+        memory.store_u32(strings.len() as u32, count_addr)?;
+        memory.store_u32(buf_size, buf_size_addr)?;
+        Ok(())
+    }
+
+    /// Writes null terminated strings into the buffer and pointers to them into the pointer
+    /// array, used by `args_get` and `environ_get`
+    fn strings_get(
+        &self,
+        strings: &[String],
+        memory: &mut Memory,
+        ptr_addr: u32,
+        buf_addr: u32,
+    ) -> Result<(), Trap> {
+        let out_of_bounds = || Trap::MemoryOutOfBounds;
+        let mut string_addr = buf_addr;
+        let mut string_ptr_addr = ptr_addr;
+
+        for string in strings {
+            let terminator_addr = string_addr
+                .checked_add(string.len() as u32)
+                .ok_or_else(out_of_bounds)?;
+
+            memory.store_u32(string_addr, string_ptr_addr)?;
+            memory.fill_data(string_addr, string.as_bytes())?;
+            memory.store_i32_8(0, terminator_addr, Default::default())?;
+
+            string_addr = terminator_addr.checked_add(1).ok_or_else(out_of_bounds)?;
+            // 4 bytes per pointer
+            string_ptr_addr = string_ptr_addr.checked_add(4).ok_or_else(out_of_bounds)?;
         }
+        Ok(())
     }
 
     fn write_iovs(&self, fd: i32, iovs: &[IoSlice]) -> Result<usize, WasiError> {
@@ -183,10 +196,7 @@ impl Wasi {
             2 => stderr()
                 .write_vectored(iovs)
                 .map_err(|err| err.kind().into()),
-
-            _ => {
-                panic!("Haven't implemented writing to files {}", fd)
-            }
+            _ => Err(WasiError::BadFileDescriptor),
         }
     }
 
@@ -199,13 +209,50 @@ impl Wasi {
             .map(|e| format!("{}={}", e.0, e.1))
             .collect::<Vec<_>>();
 
-        println!("{:?}", args);
         Self { args, envs }
     }
 }
 
+/// Arguments are checked against the signature before a host function gets called
+fn arg(args: &[Value], idx: usize) -> u32 {
+    let Value::I32(value) = args[idx] else {
+        unreachable!("WASI functions only take i32 arguments");
+    };
+    value as u32
+}
+
 impl Default for Wasi {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        module::Module,
+        runtime::{test::compile, Linker, Value},
+    };
+
+    use super::Wasi;
+
+    #[test]
+    fn writing_to_an_unknown_descriptor_fails() {
+        let binary = compile(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory 1)
+                (func (export "write") (result i32)
+                    (call $fd_write (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 16))))"#,
+        );
+        let module = Module::new(&binary).unwrap();
+        let mut linker = Linker::new();
+        Wasi::new().add_to_linker(&mut linker);
+        let instance = linker.instantiate(&module).unwrap();
+
+        let write = instance.get_func("write").unwrap();
+        // `EBADF` is errno 8
+        assert!(matches!(write.call(&[]).as_deref(), Ok([Value::I32(8)])));
+    }
+}
@@ -0,0 +1,61 @@
+;; Malformed binaries have to be rejected by the parser
+
+(module binary "\00asm" "\01\00\00\00")
+
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\00asm") "unexpected end")
+(assert_malformed (module binary "asm\00" "\01\00\00\00") "magic header not detected")
+(assert_malformed (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+
+;; Section id 14 does not exist
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\0e\01\00") "malformed section id")
+
+;; Type section declares one function type but ends early
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\01\04\01\60\01\7f")
+  "unexpected end"
+)
+
+;; Value type 0x7a does not exist
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\01\05\01\60\01\7a\00")
+  "malformed value type"
+)
+
+;; A type section followed by a second one
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"
+    "\01\04\01\60\00\00"
+  )
+  "unexpected content after last section"
+)
+
+;; Function section without a code section
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"
+    "\03\02\01\00"
+  )
+  "function and code section have inconsistent lengths"
+)
+
+;; Data count section announcing a data segment that never comes
+(assert_malformed
+  (module binary "\00asm" "\01\00\00\00" "\0c\01\01")
+  "data count and data section have inconsistent lengths"
+)
+
+;; memory.size has to be followed by a zero byte
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"
+    "\03\02\01\00"
+    "\05\03\01\00\01"
+    "\0a\07\01\05\00\3f\01\1a\0b"
+  )
+  "zero byte expected"
+)
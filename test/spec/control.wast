@@ -0,0 +1,173 @@
+;; Structured control flow, branches and calls
+
+(module
+  (func $fac (export "fac") (param i64) (result i64)
+    (if (result i64) (i64.eqz (local.get 0))
+      (then (i64.const 1))
+      (else (i64.mul (local.get 0) (call $fac (i64.sub (local.get 0) (i64.const 1)))))
+    )
+  )
+  (func (export "fac-iter") (param i64) (result i64)
+    (local i64)
+    (local.set 1 (i64.const 1))
+    (block
+      (loop
+        (br_if 1 (i64.eqz (local.get 0)))
+        (local.set 1 (i64.mul (local.get 0) (local.get 1)))
+        (local.set 0 (i64.sub (local.get 0) (i64.const 1)))
+        (br 0)
+      )
+    )
+    (local.get 1)
+  )
+  (func (export "block-value") (result i32)
+    (block (result i32) (i32.const 1) (br 0) (i32.const 2))
+  )
+  (func (export "br_if-value") (param i32) (result i32)
+    (block (result i32)
+      (drop (br_if 0 (i32.const 10) (local.get 0)))
+      (i32.const 20)
+    )
+  )
+  (func (export "br_table") (param i32) (result i32)
+    (block
+      (block
+        (block
+          (br_table 0 1 2 (local.get 0))
+        )
+        (return (i32.const 100))
+      )
+      (return (i32.const 101))
+    )
+    (i32.const 102)
+  )
+  (func (export "early-return") (param i32) (result i32)
+    (if (local.get 0) (then (return (i32.const 1))))
+    (i32.const 0)
+  )
+  (func (export "select") (param i32) (result i32)
+    (select (i32.const 10) (i32.const 20) (local.get 0))
+  )
+  (func (export "nested-calls") (result i32)
+    (call $add3 (i32.const 1) (call $add3 (i32.const 2) (i32.const 3) (i32.const 4)) (i32.const 5))
+  )
+  (func $add3 (param i32 i32 i32) (result i32)
+    (i32.add (i32.add (local.get 0) (local.get 1)) (local.get 2))
+  )
+  (func (export "unreachable") (unreachable))
+  (func (export "trap-in-call") (result i32) (call $trap) (i32.const 1))
+  (func $trap (unreachable))
+)
+
+(assert_return (invoke "fac" (i64.const 0)) (i64.const 1))
+(assert_return (invoke "fac" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "fac" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "fac-iter" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "fac-iter" (i64.const 25)) (i64.const 7034535277573963776))
+(assert_return (invoke "block-value") (i32.const 1))
+(assert_return (invoke "br_if-value" (i32.const 0)) (i32.const 20))
+(assert_return (invoke "br_if-value" (i32.const 1)) (i32.const 10))
+(assert_return (invoke "br_table" (i32.const 0)) (i32.const 100))
+(assert_return (invoke "br_table" (i32.const 1)) (i32.const 101))
+(assert_return (invoke "br_table" (i32.const 2)) (i32.const 102))
+(assert_return (invoke "br_table" (i32.const 1000)) (i32.const 102))
+(assert_return (invoke "early-return" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "early-return" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "select" (i32.const 1)) (i32.const 10))
+(assert_return (invoke "select" (i32.const 0)) (i32.const 20))
+(assert_return (invoke "nested-calls") (i32.const 15))
+(assert_trap (invoke "unreachable") "unreachable")
+(assert_trap (invoke "trap-in-call") "unreachable")
+;; The instance is still usable after a trap
+(assert_return (invoke "fac" (i64.const 3)) (i64.const 6))
+
+(assert_invalid
+  (module (func (result i32) (block (result i32) (i64.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (if (result i32) (i32.const 1) (then (i32.const 1)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (param i32) (result i32) (br_table 0 0 (local.get 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (block (result i32) (br_if 0 (i32.const 1)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (call 1)))
+  "unknown function"
+)
+(assert_invalid
+  (module (func (local.get 0) (drop)))
+  "unknown local"
+)
+(assert_invalid
+  (module (func (select (i32.const 1) (i64.const 1) (i32.const 1)) (drop)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (result i32) (return)))
+  "type mismatch"
+)
+
+;; Branches to the function body return from the function
+(module
+  (func (export "br-function") (result i32)
+    (i32.const 7) (br 0)
+  )
+  (func (export "br-function-nested") (param i32) (result i32)
+    (block (result i32)
+      (loop
+        (br 2 (i32.add (local.get 0) (i32.const 1)))
+      )
+      (i32.const 0)
+    )
+  )
+  (func (export "br-function-multi") (result i32 i64)
+    (i32.const 1) (i64.const 2) (br 0)
+  )
+  (func (export "br_if-function") (param i32) (result i32)
+    (drop (br_if 0 (i32.const 10) (local.get 0)))
+    (i32.const 20)
+  )
+  (func (export "br_if-function-nested") (param i32) (result i32)
+    (local i32)
+    (local.set 1 (i32.const 1))
+    (block
+      (drop (br_if 1 (local.get 1) (local.get 0)))
+      (local.set 1 (i32.const 2))
+    )
+    (local.get 1)
+  )
+  (func (export "br_table-function") (param i32) (result i32)
+    (block (result i32)
+      (br_table 0 1 0 (i32.const 30) (local.get 0))
+    )
+    (i32.const 1)
+    (i32.add)
+  )
+)
+
+(assert_return (invoke "br-function") (i32.const 7))
+(assert_return (invoke "br-function-nested" (i32.const 4)) (i32.const 5))
+(assert_return (invoke "br-function-multi") (i32.const 1) (i64.const 2))
+(assert_return (invoke "br_if-function" (i32.const 1)) (i32.const 10))
+(assert_return (invoke "br_if-function" (i32.const 0)) (i32.const 20))
+(assert_return (invoke "br_if-function-nested" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "br_if-function-nested" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "br_table-function" (i32.const 0)) (i32.const 31))
+(assert_return (invoke "br_table-function" (i32.const 1)) (i32.const 30))
+(assert_return (invoke "br_table-function" (i32.const 2)) (i32.const 31))
+(assert_return (invoke "br_table-function" (i32.const 9)) (i32.const 31))
+(assert_invalid
+  (module (func (result i32) (br 0 (i64.const 1))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (br 1)))
+  "unknown label"
+)
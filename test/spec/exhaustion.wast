@@ -0,0 +1,51 @@
+;; Unbounded recursion exhausts the call stack instead of the host's memory
+
+(module
+  (func $runaway (export "runaway") (call $runaway))
+  (func $even (export "even") (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 1))
+      (else (call $odd (i64.sub (local.get 0) (i64.const 1))))))
+  (func $odd (param i64) (result i32)
+    (if (result i32) (i64.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (call $even (i64.sub (local.get 0) (i64.const 1))))))
+  (func $deep (export "deep") (param i32) (result i32)
+    (i32.const 1)
+    (i32.const 2)
+    (i32.const 3)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (call $deep (i32.sub (local.get 0) (i32.const 1)))))
+    (i32.add)
+    (i32.add)
+    (i32.add))
+)
+
+(assert_exhaustion (invoke "runaway") "call stack exhausted")
+(assert_return (invoke "even" (i64.const 1000)) (i32.const 1))
+(assert_exhaustion (invoke "even" (i64.const 10000000)) "call stack exhausted")
+(assert_return (invoke "even" (i64.const 77)) (i32.const 0))
+(assert_return (invoke "deep" (i32.const 100)) (i32.const 606))
+(assert_exhaustion (invoke "deep" (i32.const 10000000)) "call stack exhausted")
+
+;; Calls between instances count towards the same limit
+(module $a
+  (type $rec (func (param i32) (result i32)))
+  (table (export "table") 1 funcref)
+  (func (export "rec") (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (call_indirect (type $rec) (i32.sub (local.get 0) (i32.const 1)) (i32.const 0)))))
+)
+(register "a" $a)
+
+(module $b
+  (import "a" "table" (table 1 funcref))
+  (import "a" "rec" (func $rec (param i32) (result i32)))
+  (elem (i32.const 0) $bounce)
+  (func $bounce (param i32) (result i32) (call $rec (local.get 0)))
+)
+
+(assert_return (invoke $a "rec" (i32.const 20)) (i32.const 0))
+(assert_exhaustion (invoke $a "rec" (i32.const 10000000)) "call stack exhausted")
@@ -0,0 +1,69 @@
+;; Extern references are opaque host objects the guest can only pass around
+
+(module
+  (table $t 2 externref)
+  (global $g (mut externref) (ref.null extern))
+  (elem (table $t) (i32.const 1) externref (ref.null extern))
+
+  (func (export "id") (param externref) (result externref) (local.get 0))
+  (func (export "is_null") (param externref) (result i32) (ref.is_null (local.get 0)))
+  (func (export "null_local") (result externref) (local externref) (local.get 0))
+  (func (export "select") (param externref externref i32) (result externref)
+    (select (result externref) (local.get 0) (local.get 1) (local.get 2)))
+
+  (func (export "set_global") (param externref) (global.set $g (local.get 0)))
+  (func (export "get_global") (result externref) (global.get $g))
+
+  (func (export "set") (param i32 externref) (table.set $t (local.get 0) (local.get 1)))
+  (func (export "get") (param i32) (result externref) (table.get $t (local.get 0)))
+  (func (export "grow") (param externref i32) (result i32)
+    (table.grow $t (local.get 0) (local.get 1)))
+  (func (export "fill") (param i32 externref i32)
+    (table.fill $t (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "size") (result i32) (table.size $t))
+)
+
+(assert_return (invoke "id" (ref.extern 1)) (ref.extern 1))
+(assert_return (invoke "id" (ref.null extern)) (ref.null extern))
+(assert_return (invoke "is_null" (ref.extern 1)) (i32.const 0))
+(assert_return (invoke "is_null" (ref.null extern)) (i32.const 1))
+(assert_return (invoke "null_local") (ref.null extern))
+(assert_return (invoke "select" (ref.extern 1) (ref.extern 2) (i32.const 1)) (ref.extern 1))
+(assert_return (invoke "select" (ref.extern 1) (ref.extern 2) (i32.const 0)) (ref.extern 2))
+
+(assert_return (invoke "get_global") (ref.null extern))
+(invoke "set_global" (ref.extern 7))
+(assert_return (invoke "get_global") (ref.extern 7))
+
+(assert_return (invoke "get" (i32.const 0)) (ref.null extern))
+(invoke "set" (i32.const 0) (ref.extern 3))
+(assert_return (invoke "get" (i32.const 0)) (ref.extern 3))
+(assert_return (invoke "get" (i32.const 1)) (ref.null extern))
+(assert_trap (invoke "get" (i32.const 2)) "out of bounds table access")
+(assert_return (invoke "grow" (ref.extern 4) (i32.const 2)) (i32.const 2))
+(assert_return (invoke "size") (i32.const 4))
+(assert_return (invoke "get" (i32.const 3)) (ref.extern 4))
+(invoke "fill" (i32.const 1) (ref.extern 5) (i32.const 3))
+(assert_return (invoke "get" (i32.const 1)) (ref.extern 5))
+(assert_return (invoke "get" (i32.const 3)) (ref.extern 5))
+(assert_return (invoke "get" (i32.const 0)) (ref.extern 3))
+
+(assert_trap (invoke "id" (i32.const 0)) "arguments do not match")
+(assert_trap (invoke "is_null" (ref.null func)) "arguments do not match")
+
+(assert_invalid
+  (module
+    (table 1 externref)
+    (func (call_indirect (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module (func (result funcref) (ref.null extern)))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (table 1 funcref)
+    (func (param externref) (table.set 0 (i32.const 0) (local.get 0))))
+  "type mismatch"
+)
@@ -0,0 +1,86 @@
+;; Edge cases where the spec differs from what the host's float and integer operations do
+
+(module
+  (func (export "f32.nearest") (param f32) (result f32) (f32.nearest (local.get 0)))
+  (func (export "f64.nearest") (param f64) (result f64) (f64.nearest (local.get 0)))
+  (func (export "f32.min") (param f32 f32) (result f32) (f32.min (local.get 0) (local.get 1)))
+  (func (export "f32.max") (param f32 f32) (result f32) (f32.max (local.get 0) (local.get 1)))
+  (func (export "f64.min") (param f64 f64) (result f64) (f64.min (local.get 0) (local.get 1)))
+  (func (export "f64.max") (param f64 f64) (result f64) (f64.max (local.get 0) (local.get 1)))
+  (func (export "f32.add") (param f32 f32) (result f32) (f32.add (local.get 0) (local.get 1)))
+  (func (export "f32.sqrt") (param f32) (result f32) (f32.sqrt (local.get 0)))
+  (func (export "f64.div") (param f64 f64) (result f64) (f64.div (local.get 0) (local.get 1)))
+  (func (export "f32.neg") (param f32) (result f32) (f32.neg (local.get 0)))
+  (func (export "f64.abs") (param f64) (result f64) (f64.abs (local.get 0)))
+  (func (export "f32.copysign") (param f32 f32) (result f32) (f32.copysign (local.get 0) (local.get 1)))
+  (func (export "f32.demote") (param f64) (result f32) (f32.demote_f64 (local.get 0)))
+  (func (export "f32.convert_u64") (param i64) (result f32) (f32.convert_i64_u (local.get 0)))
+  (func (export "i32.shl") (param i32 i32) (result i32) (i32.shl (local.get 0) (local.get 1)))
+  (func (export "i32.shr_s") (param i32 i32) (result i32) (i32.shr_s (local.get 0) (local.get 1)))
+  (func (export "i32.shr_u") (param i32 i32) (result i32) (i32.shr_u (local.get 0) (local.get 1)))
+  (func (export "i32.rotl") (param i32 i32) (result i32) (i32.rotl (local.get 0) (local.get 1)))
+  (func (export "i64.shl") (param i64 i64) (result i64) (i64.shl (local.get 0) (local.get 1)))
+  (func (export "i64.shr_s") (param i64 i64) (result i64) (i64.shr_s (local.get 0) (local.get 1)))
+  (func (export "i64.shr_u") (param i64 i64) (result i64) (i64.shr_u (local.get 0) (local.get 1)))
+  (func (export "i64.rotr") (param i64 i64) (result i64) (i64.rotr (local.get 0) (local.get 1)))
+)
+
+;; Ties round to even
+(assert_return (invoke "f32.nearest" (f32.const 0.5)) (f32.const 0))
+(assert_return (invoke "f32.nearest" (f32.const 1.5)) (f32.const 2))
+(assert_return (invoke "f32.nearest" (f32.const 2.5)) (f32.const 2))
+(assert_return (invoke "f32.nearest" (f32.const -0.5)) (f32.const -0))
+(assert_return (invoke "f32.nearest" (f32.const -3.5)) (f32.const -4))
+(assert_return (invoke "f32.nearest" (f32.const 0x1.fffffep+22)) (f32.const 0x1p+23))
+(assert_return (invoke "f64.nearest" (f64.const 2.5)) (f64.const 2))
+(assert_return (invoke "f64.nearest" (f64.const -2.5)) (f64.const -2))
+(assert_return (invoke "f64.nearest" (f64.const 4503599627370497)) (f64.const 4503599627370497))
+(assert_return (invoke "f32.nearest" (f32.const nan)) (f32.const nan:canonical))
+
+;; NaN wins and -0 is less than +0
+(assert_return (invoke "f32.min" (f32.const nan) (f32.const 1)) (f32.const nan:canonical))
+(assert_return (invoke "f32.min" (f32.const 1) (f32.const nan)) (f32.const nan:canonical))
+(assert_return (invoke "f32.max" (f32.const nan:0x200000) (f32.const 1)) (f32.const nan:arithmetic))
+(assert_return (invoke "f32.min" (f32.const 0) (f32.const -0)) (f32.const -0))
+(assert_return (invoke "f32.min" (f32.const -0) (f32.const 0)) (f32.const -0))
+(assert_return (invoke "f32.max" (f32.const -0) (f32.const 0)) (f32.const 0))
+(assert_return (invoke "f32.max" (f32.const 0) (f32.const -0)) (f32.const 0))
+(assert_return (invoke "f32.min" (f32.const -inf) (f32.const 3)) (f32.const -inf))
+(assert_return (invoke "f64.min" (f64.const 0) (f64.const -0)) (f64.const -0))
+(assert_return (invoke "f64.max" (f64.const -0) (f64.const 0)) (f64.const 0))
+(assert_return (invoke "f64.max" (f64.const 1) (f64.const -nan)) (f64.const nan:canonical))
+(assert_return (invoke "f64.min" (f64.const 2) (f64.const 1)) (f64.const 1))
+
+;; Arithmetic results are NaNs, bitwise operations keep the payload
+(assert_return (invoke "f32.add" (f32.const inf) (f32.const -inf)) (f32.const nan:canonical))
+(assert_return (invoke "f32.sqrt" (f32.const -1)) (f32.const nan:canonical))
+(assert_return (invoke "f32.sqrt" (f32.const -0)) (f32.const -0))
+(assert_return (invoke "f64.div" (f64.const 0) (f64.const 0)) (f64.const nan:canonical))
+(assert_return (invoke "f64.div" (f64.const -1) (f64.const 0)) (f64.const -inf))
+(assert_return (invoke "f32.neg" (f32.const nan:0x200000)) (f32.const -nan:0x200000))
+(assert_return (invoke "f32.neg" (f32.const 0)) (f32.const -0))
+(assert_return (invoke "f64.abs" (f64.const -nan:0x4000000000000)) (f64.const nan:0x4000000000000))
+(assert_return (invoke "f32.copysign" (f32.const nan:0x200000) (f32.const -1)) (f32.const -nan:0x200000))
+
+;; Conversions round to nearest even
+(assert_return (invoke "f32.demote" (f64.const 0x1.000001p+0)) (f32.const 1))
+(assert_return (invoke "f32.demote" (f64.const 0x1.0000030000001p+0)) (f32.const 0x1.000004p+0))
+(assert_return (invoke "f32.demote" (f64.const 0x1p+128)) (f32.const inf))
+(assert_return (invoke "f32.demote" (f64.const nan)) (f32.const nan:canonical))
+(assert_return (invoke "f32.convert_u64" (i64.const 0x8000_0080_0000_0000)) (f32.const 0x1p+63))
+(assert_return (invoke "f32.convert_u64" (i64.const 0x8000_0180_0000_0001)) (f32.const 0x1.000004p+63))
+(assert_return (invoke "f32.convert_u64" (i64.const -1)) (f32.const 0x1p+64))
+
+;; Shift counts are taken modulo the bit width, also when they are negative
+(assert_return (invoke "i32.shl" (i32.const 1) (i32.const -1)) (i32.const 0x80000000))
+(assert_return (invoke "i32.shl" (i32.const 1) (i32.const 33)) (i32.const 2))
+(assert_return (invoke "i32.shr_s" (i32.const -8) (i32.const -31)) (i32.const -4))
+(assert_return (invoke "i32.shr_s" (i32.const 0x80000000) (i32.const 31)) (i32.const -1))
+(assert_return (invoke "i32.shr_u" (i32.const -1) (i32.const -1)) (i32.const 1))
+(assert_return (invoke "i32.shr_u" (i32.const -1) (i32.const 32)) (i32.const -1))
+(assert_return (invoke "i32.rotl" (i32.const 0x80000001) (i32.const -31)) (i32.const 3))
+(assert_return (invoke "i64.shl" (i64.const 1) (i64.const -1)) (i64.const 0x8000000000000000))
+(assert_return (invoke "i64.shl" (i64.const 1) (i64.const 65)) (i64.const 2))
+(assert_return (invoke "i64.shr_s" (i64.const -16) (i64.const -62)) (i64.const -4))
+(assert_return (invoke "i64.shr_u" (i64.const -1) (i64.const 0x8000000000000001)) (i64.const 0x7fffffffffffffff))
+(assert_return (invoke "i64.rotr" (i64.const 1) (i64.const -1)) (i64.const 2))
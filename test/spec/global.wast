@@ -0,0 +1,49 @@
+;; Globals, their initializers and exports
+
+(module
+  (global $a i32 (i32.const -2))
+  (global $b (mut i64) (i64.const 5))
+  (global $c f32 (f32.const 1.5))
+  (global (export "exported") i32 (i32.const 42))
+  (global $d (export "mutable") (mut i32) (i32.const 0))
+  (func (export "get-a") (result i32) (global.get $a))
+  (func (export "get-b") (result i64) (global.get $b))
+  (func (export "get-c") (result f32) (global.get $c))
+  (func (export "set-b") (param i64) (global.set $b (local.get 0)))
+  (func (export "set-d") (param i32) (global.set $d (local.get 0)))
+)
+
+(assert_return (invoke "get-a") (i32.const -2))
+(assert_return (invoke "get-b") (i64.const 5))
+(assert_return (invoke "get-c") (f32.const 1.5))
+(invoke "set-b" (i64.const 9))
+(assert_return (invoke "get-b") (i64.const 9))
+(assert_return (get "exported") (i32.const 42))
+(assert_return (get "mutable") (i32.const 0))
+(invoke "set-d" (i32.const 7))
+(assert_return (get "mutable") (i32.const 7))
+
+(assert_invalid
+  (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1))))
+  "global is immutable"
+)
+(assert_invalid
+  (module (global i32 (i64.const 0)))
+  "type mismatch"
+)
+(assert_invalid
+  (module (global i32 (i32.add (i32.const 0) (i32.const 1))))
+  "constant expression required"
+)
+(assert_invalid
+  (module (global i32 (i32.const 0)) (global i32 (global.get 0)))
+  "unknown global"
+)
+(assert_invalid
+  (module (func (global.get 0) (drop)))
+  "unknown global"
+)
+(assert_invalid
+  (module (func) (export "a" (func 0)) (export "a" (func 0)))
+  "duplicate export name"
+)
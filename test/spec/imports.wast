@@ -0,0 +1,92 @@
+(module
+  (import "spectest" "print_i32" (func $print_i32 (param i32)))
+  (import "spectest" "print_f64_f64" (func $print_f64_f64 (param f64 f64)))
+  (func (export "print") (param i32) (result i32)
+    (call $print_i32 (local.get 0))
+    (call $print_f64_f64 (f64.const 1.5) (f64.const 2.5))
+    (i32.add (local.get 0) (i32.const 1))
+  )
+  (table 1 funcref)
+  (elem (i32.const 0) $print_i32)
+  (func (export "print_indirect") (param i32)
+    (call_indirect (param i32) (local.get 0) (i32.const 0))
+  )
+)
+
+(assert_return (invoke "print" (i32.const 41)) (i32.const 42))
+(assert_return (invoke "print_indirect" (i32.const 7)))
+
+(assert_unlinkable
+  (module (import "spectest" "unknown" (func)))
+  "unknown import"
+)
+(assert_unlinkable
+  (module (import "host" "print_i32" (func (param i32))))
+  "unknown import"
+)
+(assert_unlinkable
+  (module (import "spectest" "print_i32" (func (param i64))))
+  "incompatible import type"
+)
+
+(module
+  (import "spectest" "global_i32" (global $imported i32))
+  (import "spectest" "memory" (memory 1))
+  (import "spectest" "table" (table 10 funcref))
+  (global $defined i32 (global.get $imported))
+  (func $seven (result i32) (i32.const 7))
+  (elem (i32.const 1) $seven)
+  (data (i32.const 0) "\2a")
+  (func (export "imported_global") (result i32) (global.get $imported))
+  (func (export "defined_global") (result i32) (global.get $defined))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "store") (param i32 i32) (i32.store8 (local.get 0) (local.get 1)))
+  (func (export "size") (result i32) (memory.size))
+  (func (export "table_size") (result i32) (table.size))
+)
+
+(assert_return (invoke "imported_global") (i32.const 666))
+(assert_return (invoke "defined_global") (i32.const 666))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 42))
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "table_size") (i32.const 10))
+(assert_trap (invoke "load" (i32.const 65536)) "out of bounds memory access")
+
+;; The imported memory is shared with every module importing it
+(invoke "store" (i32.const 1) (i32.const 99))
+(module
+  (import "spectest" "memory" (memory 1 2))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+)
+(assert_return (invoke "load" (i32.const 1)) (i32.const 99))
+
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 2)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "memory" (memory 1 1)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 11 funcref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "global_i32" (global (mut i32))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "global_i32" (global i64)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "memory" (func)))
+  "incompatible import type"
+)
+
+;; The start function may be an import
+(module
+  (import "spectest" "print" (func $print))
+  (start $print)
+)
@@ -0,0 +1,88 @@
+(module $libc
+  (memory (export "memory") 1)
+  (table (export "table") 10 funcref)
+  (global (export "counter") (mut i32) (i32.const 0))
+  (func $strlen (export "strlen") (param $ptr i32) (result i32)
+    (local $len i32)
+    (block $done
+      (loop $next
+        (br_if $done (i32.eqz (i32.load8_u (i32.add (local.get $ptr) (local.get $len)))))
+        (local.set $len (i32.add (local.get $len) (i32.const 1)))
+        (br $next)))
+    (local.get $len))
+  (func (export "increment") (result i32)
+    (global.set 0 (i32.add (global.get 0) (i32.const 1)))
+    (global.get 0))
+  (func $apply (export "apply") (param $f i32) (param $x i32) (result i32)
+    (call_indirect (param i32) (result i32) (local.get $x) (local.get $f)))
+  (func $double (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+  (elem (i32.const 0) $double)
+)
+(register "libc" $libc)
+
+(module $tenant
+  (import "libc" "memory" (memory 1))
+  (import "libc" "table" (table 10 funcref))
+  (import "libc" "counter" (global $counter (mut i32)))
+  (import "libc" "strlen" (func $strlen (param i32) (result i32)))
+  (import "libc" "increment" (func $increment (result i32)))
+  (import "libc" "apply" (func $apply (param i32 i32) (result i32)))
+  (data (i32.const 100) "tenant\00")
+  (func $square (param i32) (result i32) (i32.mul (local.get 0) (local.get 0)))
+  (elem (i32.const 1) $square $strlen)
+  (func (export "name_length") (result i32) (call $strlen (i32.const 100)))
+  (func (export "increment_twice") (result i32) (drop (call $increment)) (call $increment))
+  (func (export "counter") (result i32) (global.get $counter))
+  (func (export "set_counter") (param i32) (global.set $counter (local.get 0)))
+  (func (export "apply_local") (param i32) (result i32) (call $apply (i32.const 1) (local.get 0)))
+  (func (export "call_table") (param i32 i32) (result i32)
+    (call_indirect (param i32) (result i32) (local.get 1) (local.get 0)))
+)
+
+;; Calls into the imported instance, which uses the memory written by the tenant
+(assert_return (invoke $tenant "name_length") (i32.const 6))
+(assert_return (invoke $libc "strlen" (i32.const 100)) (i32.const 6))
+
+;; Globals are shared
+(assert_return (invoke $tenant "increment_twice") (i32.const 2))
+(assert_return (invoke $libc "increment") (i32.const 3))
+(invoke $tenant "set_counter" (i32.const 10))
+(assert_return (invoke $tenant "counter") (i32.const 10))
+(assert_return (invoke $libc "increment") (i32.const 11))
+
+;; Indirect calls through the shared table in both directions
+(assert_return (invoke $libc "apply" (i32.const 0) (i32.const 21)) (i32.const 42))
+(assert_return (invoke $libc "apply" (i32.const 1) (i32.const 5)) (i32.const 25))
+(assert_return (invoke $tenant "apply_local" (i32.const 7)) (i32.const 49))
+(assert_return (invoke $tenant "call_table" (i32.const 0) (i32.const 4)) (i32.const 8))
+(assert_return (invoke $tenant "call_table" (i32.const 2) (i32.const 100)) (i32.const 6))
+(assert_trap (invoke $libc "apply" (i32.const 3) (i32.const 0)) "uninitialized element")
+
+(module $mismatch
+  (import "libc" "table" (table 10 funcref))
+  (func $f (param i64) (result i32) (i32.const 0))
+  (elem (i32.const 3) $f)
+)
+(assert_trap (invoke $libc "apply" (i32.const 3) (i32.const 0)) "indirect call type mismatch")
+
+;; Elements written before a failing data segment stay in the shared table
+(assert_trap
+  (module
+    (import "libc" "table" (table 10 funcref))
+    (import "libc" "memory" (memory 1))
+    (func $seven (param i32) (result i32) (i32.const 7))
+    (elem (i32.const 4) $seven)
+    (data (i32.const 65536) "x")
+  )
+  "out of bounds memory access"
+)
+(assert_return (invoke $libc "apply" (i32.const 4) (i32.const 0)) (i32.const 7))
+
+(assert_unlinkable
+  (module (import "libc" "strlen" (func (param i64) (result i32))))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "libc" "missing" (func)))
+  "unknown import"
+)
@@ -0,0 +1,121 @@
+;; Linear memory accesses, data segments and growing
+
+(module
+  (memory 1 2)
+  (data (i32.const 0) "abcdefgh")
+  (data (i32.const 0xfff8) "\01\02\03\04\05\06\07\08")
+  (func (export "load8_u") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "load8_s") (param i32) (result i32) (i32.load8_s (local.get 0)))
+  (func (export "load") (param i32) (result i32) (i32.load (local.get 0)))
+  (func (export "load64") (param i32) (result i64) (i64.load (local.get 0)))
+  (func (export "store") (param i32 i32) (i32.store (local.get 0) (local.get 1)))
+  (func (export "store-offset") (param i32 i32) (i32.store offset=4 (local.get 0) (local.get 1)))
+  (func (export "size") (result i32) (memory.size))
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "load8_u" (i32.const 0)) (i32.const 97))
+(assert_return (invoke "load8_u" (i32.const 7)) (i32.const 104))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0x64636261))
+(assert_return (invoke "load64" (i32.const 0xfff8)) (i64.const 0x0807060504030201))
+(assert_return (invoke "load8_s" (i32.const 0xffff)) (i32.const 8))
+(invoke "store" (i32.const 8) (i32.const 0xff))
+(assert_return (invoke "load8_s" (i32.const 8)) (i32.const -1))
+(assert_return (invoke "load8_u" (i32.const 8)) (i32.const 255))
+(invoke "store-offset" (i32.const 8) (i32.const 42))
+(assert_return (invoke "load" (i32.const 12)) (i32.const 42))
+(assert_trap (invoke "load" (i32.const 0xfffd)) "out of bounds memory access")
+(assert_trap (invoke "load8_u" (i32.const 0x10000)) "out of bounds memory access")
+(assert_trap (invoke "store" (i32.const 0xfffd) (i32.const 0)) "out of bounds memory access")
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 2))
+(assert_return (invoke "load" (i32.const 0xfffd)) (i32.const 0x00080706))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "size") (i32.const 2))
+
+(assert_trap
+  (module (memory 1) (data (i32.const 0xffff) "ab"))
+  "out of bounds memory access"
+)
+
+(assert_invalid
+  (module (func (drop (i32.load (i32.const 0)))))
+  "unknown memory"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i64.load align=16 (i32.const 0)))))
+  "alignment must not be larger than natural"
+)
+(assert_invalid
+  (module (memory 2 1))
+  "size minimum must not be greater than maximum"
+)
+(assert_invalid
+  (module (memory 65537))
+  "memory size must be at most 65536 pages (4GiB)"
+)
+(assert_invalid
+  (module (data (i32.const 0) "a"))
+  "unknown memory"
+)
+
+;; Effective addresses beyond 4GiB and bulk memory operations
+(module
+  (memory 1)
+  (data $passive "0123456789")
+  (data (i32.const 16) "abcdef")
+  (func (export "load-max-offset") (param i32) (result i32)
+    (i32.load offset=0xffffffff (local.get 0)))
+  (func (export "load8-offset") (param i32) (result i32)
+    (i32.load8_u offset=0xfffe (local.get 0)))
+  (func (export "store64-offset") (param i32)
+    (i64.store offset=0xfff8 (local.get 0) (i64.const -1)))
+  (func (export "load8") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "fill") (param i32 i32 i32) (memory.fill (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "copy") (param i32 i32 i32) (memory.copy (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init") (param i32 i32 i32) (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init-active") (param i32 i32 i32) (memory.init 1 (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "drop") (data.drop $passive))
+)
+
+(assert_trap (invoke "load-max-offset" (i32.const 0)) "out of bounds memory access")
+(assert_trap (invoke "load-max-offset" (i32.const 0xffffffff)) "out of bounds memory access")
+(assert_return (invoke "load8-offset" (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "load8-offset" (i32.const 2)) "out of bounds memory access")
+(assert_trap (invoke "load8-offset" (i32.const 0xffffffff)) "out of bounds memory access")
+(invoke "store64-offset" (i32.const 0))
+(assert_trap (invoke "store64-offset" (i32.const 1)) "out of bounds memory access")
+(assert_return (invoke "load8" (i32.const 0xffff)) (i32.const 255))
+
+(invoke "fill" (i32.const 100) (i32.const 7) (i32.const 3))
+(assert_return (invoke "load8" (i32.const 102)) (i32.const 7))
+(assert_return (invoke "load8" (i32.const 103)) (i32.const 0))
+(invoke "fill" (i32.const 0x10000) (i32.const 1) (i32.const 0))
+(assert_trap (invoke "fill" (i32.const 0x10001) (i32.const 1) (i32.const 0)) "out of bounds memory access")
+(assert_trap (invoke "fill" (i32.const 0xffff) (i32.const 1) (i32.const 2)) "out of bounds memory access")
+(assert_return (invoke "load8" (i32.const 0xffff)) (i32.const 255))
+(assert_trap (invoke "fill" (i32.const 1) (i32.const 1) (i32.const 0xffffffff)) "out of bounds memory access")
+
+(invoke "copy" (i32.const 17) (i32.const 16) (i32.const 6))
+(assert_return (invoke "load8" (i32.const 17)) (i32.const 97))
+(assert_return (invoke "load8" (i32.const 22)) (i32.const 102))
+(invoke "copy" (i32.const 16) (i32.const 17) (i32.const 6))
+(assert_return (invoke "load8" (i32.const 21)) (i32.const 102))
+(assert_trap (invoke "copy" (i32.const 0xfffe) (i32.const 0) (i32.const 3)) "out of bounds memory access")
+(assert_trap (invoke "copy" (i32.const 0) (i32.const 0xfffe) (i32.const 3)) "out of bounds memory access")
+(assert_trap (invoke "copy" (i32.const 0) (i32.const 0xffffffff) (i32.const 0xffffffff)) "out of bounds memory access")
+(assert_return (invoke "load8" (i32.const 0)) (i32.const 0))
+
+(invoke "init" (i32.const 200) (i32.const 2) (i32.const 3))
+(assert_return (invoke "load8" (i32.const 200)) (i32.const 50))
+(assert_return (invoke "load8" (i32.const 202)) (i32.const 52))
+(assert_trap (invoke "init" (i32.const 0) (i32.const 8) (i32.const 3)) "out of bounds memory access")
+(assert_trap (invoke "init" (i32.const 0xffff) (i32.const 0) (i32.const 2)) "out of bounds memory access")
+(assert_trap (invoke "init" (i32.const 0) (i32.const 0xffffffff) (i32.const 0xffffffff)) "out of bounds memory access")
+(invoke "init" (i32.const 0) (i32.const 10) (i32.const 0))
+(invoke "drop")
+(invoke "init" (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_trap (invoke "init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds memory access")
+(invoke "init-active" (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_trap (invoke "init-active" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds memory access")
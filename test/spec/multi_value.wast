@@ -0,0 +1,119 @@
+;; Blocks typed by a function type take params and leave several results
+
+(module
+  (type $i32_to_i32x2 (func (param i32) (result i32 i32)))
+  (type $i32x2_to_i32 (func (param i32 i32) (result i32)))
+  (type $i64_to_i64 (func (param i64) (result i64)))
+
+  (func (export "swap") (param i32 i32) (result i32 i32)
+    (local.get 1) (local.get 0))
+
+  (func (export "block_results") (result i32 i64 f32)
+    (block (result i32 i64 f32)
+      (i32.const 1) (i64.const 2) (f32.const 3)))
+
+  (func (export "block_params") (param i32 i32) (result i32)
+    (local.get 0) (local.get 1)
+    (block (type $i32x2_to_i32) (i32.sub)))
+
+  (func (export "dup") (param i32) (result i32 i32)
+    (local.get 0)
+    (block (type $i32_to_i32x2) (local.tee 0) (local.get 0)))
+
+  (func (export "br_results") (param i32) (result i32 i32)
+    (block $done (result i32 i32)
+      (i32.const 1) (i32.const 2)
+      (br_if $done (local.get 0))
+      (drop) (drop)
+      (i32.const 3) (i32.const 4)))
+
+  (func (export "br_table_results") (param i32) (result i32 i64)
+    (block $b (result i32 i64)
+      (block $a (result i32 i64)
+        (i32.const 10) (i64.const 20)
+        (br_table $a $b (local.get 0)))
+      (drop) (drop)
+      (i32.const 30) (i64.const 40)))
+
+  ;; The loop takes the running product and the counter, branching back passes new ones
+  (func (export "factorial") (param i64) (result i64)
+    (i64.const 1) (local.get 0)
+    (loop $next (param i64 i64) (result i64)
+      (local.set 0)
+      (i64.mul (local.get 0))
+      (local.get 0) (i64.sub (i64.const 1))
+      (local.tee 0)
+      (br_if $next (i64.gt_u (local.get 0) (i64.const 1)))
+      (drop)))
+
+  (func (export "if_params") (param i32 i32 i32) (result i32)
+    (local.get 0) (local.get 1)
+    (if (type $i32x2_to_i32) (local.get 2)
+      (then (i32.add))
+      (else (i32.sub))))
+
+  (func (export "if_no_else") (param i64 i32) (result i64)
+    (local.get 0)
+    (if (type $i64_to_i64) (local.get 1)
+      (then (i64.add (i64.const 100)))))
+
+  (func $pair (result i32 i32) (i32.const 6) (i32.const 7))
+  (func (export "call_results") (result i32) (call $pair) (i32.mul))
+
+  (func (export "return_results") (param i32) (result i32 i32)
+    (block
+      (br_if 0 (local.get 0))
+      (return (i32.const 8) (i32.const 9)))
+    (i32.const 10) (i32.const 11))
+)
+
+(assert_return (invoke "swap" (i32.const 1) (i32.const 2)) (i32.const 2) (i32.const 1))
+(assert_return (invoke "block_results") (i32.const 1) (i64.const 2) (f32.const 3))
+(assert_return (invoke "block_params" (i32.const 10) (i32.const 3)) (i32.const 7))
+(assert_return (invoke "dup" (i32.const 5)) (i32.const 5) (i32.const 5))
+(assert_return (invoke "br_results" (i32.const 1)) (i32.const 1) (i32.const 2))
+(assert_return (invoke "br_results" (i32.const 0)) (i32.const 3) (i32.const 4))
+(assert_return (invoke "br_table_results" (i32.const 0)) (i32.const 30) (i64.const 40))
+(assert_return (invoke "br_table_results" (i32.const 1)) (i32.const 10) (i64.const 20))
+(assert_return (invoke "br_table_results" (i32.const 5)) (i32.const 10) (i64.const 20))
+(assert_return (invoke "factorial" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "factorial" (i64.const 20)) (i64.const 2432902008176640000))
+(assert_return (invoke "if_params" (i32.const 10) (i32.const 3) (i32.const 1)) (i32.const 13))
+(assert_return (invoke "if_params" (i32.const 10) (i32.const 3) (i32.const 0)) (i32.const 7))
+(assert_return (invoke "if_no_else" (i64.const 1) (i32.const 1)) (i64.const 101))
+(assert_return (invoke "if_no_else" (i64.const 1) (i32.const 0)) (i64.const 1))
+(assert_return (invoke "call_results") (i32.const 42))
+(assert_return (invoke "return_results" (i32.const 0)) (i32.const 8) (i32.const 9))
+(assert_return (invoke "return_results" (i32.const 1)) (i32.const 10) (i32.const 11))
+
+(assert_invalid
+  (module
+    (type (func (param i32) (result i32)))
+    (func (result i32) (block (type 0) (i32.const 0))))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (type (func (param i32) (result i64)))
+    (func (result i64)
+      (i32.const 0)
+      (loop (type 0) (br 0 (i64.const 0)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (type (func (param i32 i32) (result i32)))
+    (func (result i32)
+      (i32.const 0)
+      (if (type 0) (i32.const 1) (then (i32.add)) (else (i32.sub)))))
+  "type mismatch"
+)
+(assert_invalid
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\01\04\01\60\00\00"       ;; type section: () -> ()
+    "\03\02\01\00"             ;; function section
+    "\0a\07\01\05\00\02\01\0b\0b"  ;; code: block (type 1) end
+  )
+  "unknown type"
+)
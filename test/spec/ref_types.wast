@@ -0,0 +1,54 @@
+;; Reference typed locals, parameters and typed select
+
+(module
+  (table $t 2 funcref)
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (elem declare func $one $two)
+
+  (func $call (param $f funcref) (result i32)
+    (table.set $t (i32.const 0) (local.get $f))
+    (call_indirect $t (result i32) (i32.const 0)))
+
+  (func (export "null_locals") (result i32 i32)
+    (local funcref funcref)
+    (ref.is_null (local.get 0))
+    (ref.is_null (local.get 1)))
+
+  (func (export "ref_param") (param $f funcref) (result i32)
+    (ref.is_null (local.get $f)))
+
+  (func (export "pass_ref") (result i32)
+    (call $call (ref.func $two)))
+
+  (func (export "select_ref") (param $cond i32) (result i32)
+    (call $call
+      (select (result funcref) (ref.func $one) (ref.func $two) (local.get $cond))))
+
+  (func (export "select_i64") (param $cond i32) (result i64)
+    (select (result i64) (i64.const 10) (i64.const 20) (local.get $cond)))
+
+  (func (export "local_ref") (result i32)
+    (local $f funcref)
+    (local.set $f (ref.func $one))
+    (call $call (local.tee $f (local.get $f))))
+
+  (func (export "null_local") (result funcref)
+    (local $f funcref)
+    (local.get $f))
+)
+
+(assert_return (invoke "null_locals") (i32.const 1) (i32.const 1))
+(assert_return (invoke "ref_param" (ref.null func)) (i32.const 1))
+(assert_return (invoke "pass_ref") (i32.const 2))
+(assert_return (invoke "select_ref" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "select_ref" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "select_i64" (i32.const 1)) (i64.const 10))
+(assert_return (invoke "select_i64" (i32.const 0)) (i64.const 20))
+(assert_return (invoke "local_ref") (i32.const 1))
+(assert_return (invoke "null_local") (ref.null func))
+
+(assert_invalid
+  (module (func (result funcref) (select (ref.null func) (ref.null func) (i32.const 0))))
+  "type mismatch"
+)
@@ -0,0 +1,91 @@
+(module
+  (table $t 1 3 funcref)
+  (func $f (result i32) (i32.const 1))
+  (elem declare func $f)
+  (func (export "grow") (param i32) (result i32)
+    (table.grow $t (ref.func $f) (local.get 0)))
+  (func (export "size") (result i32) (table.size $t))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect $t (result i32) (local.get 0)))
+)
+
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 2))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 1))
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+(assert_return (invoke "grow" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "size") (i32.const 3))
+(assert_trap (invoke "call" (i32.const 3)) "out of bounds table access")
+
+(module
+  (memory 0 2)
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const -1)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+
+(module
+  (memory 0)
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "grow" (i32.const 65537)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 0))
+
+(module
+  (table $t 4 funcref)
+  (func $f (result i32) (i32.const 1))
+  (func $g (result i32) (i32.const 2))
+  (elem $passive func $f $g)
+  (elem $active (table $t) (i32.const 0) func $g)
+  (func (export "init") (param i32 i32 i32)
+    (table.init $t $passive (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "init-active") (param i32)
+    (table.init $t $active (i32.const 0) (i32.const 0) (local.get 0)))
+  (func (export "drop") (elem.drop $passive))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect $t (result i32) (local.get 0)))
+)
+
+(assert_return (invoke "call" (i32.const 0)) (i32.const 2))
+;; Active segments are dropped once they initialized the table
+(assert_return (invoke "init-active" (i32.const 0)))
+(assert_trap (invoke "init-active" (i32.const 1)) "out of bounds table access")
+;; Nothing is written when part of the range is out of bounds
+(assert_trap (invoke "init" (i32.const 3) (i32.const 0) (i32.const 2)) "out of bounds table access")
+(assert_trap (invoke "call" (i32.const 3)) "uninitialized element")
+(assert_trap (invoke "init" (i32.const 1) (i32.const 1) (i32.const 2)) "out of bounds table access")
+(assert_trap (invoke "call" (i32.const 1)) "uninitialized element")
+(assert_return (invoke "init" (i32.const 2) (i32.const 0) (i32.const 2)))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 3)) (i32.const 2))
+(assert_return (invoke "drop"))
+(assert_return (invoke "init" (i32.const 0) (i32.const 0) (i32.const 0)))
+(assert_trap (invoke "init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds table access")
+(assert_return (invoke "call" (i32.const 0)) (i32.const 2))
+
+(module
+  (table $t 3 funcref)
+  (func $f (result i32) (i32.const 1))
+  (func $g (result i32) (i32.const 2))
+  (elem (table $t) (i32.const 0) func $f $g)
+  (func (export "copy") (param i32 i32 i32)
+    (table.copy $t $t (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect $t (result i32) (local.get 0)))
+)
+
+;; Nothing is written when part of the destination is out of bounds
+(assert_trap (invoke "copy" (i32.const 2) (i32.const 0) (i32.const 2)) "out of bounds table access")
+(assert_trap (invoke "call" (i32.const 2)) "uninitialized element")
+(assert_trap (invoke "copy" (i32.const 0) (i32.const 2) (i32.const 2)) "out of bounds table access")
+(assert_return (invoke "call" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "copy" (i32.const 1) (i32.const 0) (i32.const 2)))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 2))